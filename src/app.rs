@@ -1,122 +1,468 @@
-use std::{sync::mpsc::{self, Receiver, Sender}, thread};
+use std::{cmp::Ordering, collections::BTreeSet, ops::RangeInclusive, time::{Duration, SystemTime, UNIX_EPOCH}};
 
-use eframe::{egui::{self, Align2, Color32, Pos2, Shape, Stroke, TextStyle}, epi};
+use eframe::{egui::{self, output::{OutputEvent, WidgetInfo}, plot::{Legend, Line, Plot, Points, VLine, Value, Values}, Align2, Color32, FontDefinitions, Pos2, Rect, Sense, Shape, Stroke, TextStyle, Vec2, WidgetType}, epi};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 
-use crate::{core::{PuzzleGui}, parser::{Parser, PuzzleListing}, solver::{PuzzleState, Solver, StepResult}};
+use crate::{config::Config, engine::{Command, EngineHandle, EngineResources, JobStatus, Update}, history::HistoryEntry, core::{Bits, Puzzle, PuzzleGui}, difficulty::DifficultyModel, export::GraphFormat, generator::Suggestion, hypergraph::Hypergraph, narration::narrate, parser::{ParseMode, PuzzleListing}, race::Race, report::{Badge, SolveReport}, solver::{Action, Alternatives, Constraint, CrossOrder, InferenceTier, Preset, Provable, PuzzleState, SolverConfig, SolverStats, StepGranularity, StepResult, Uniqueness}, technique::Technique};
 
-#[derive(PartialEq, Eq)]
-pub enum Command {
-    Run,
-    Load(PuzzleListing),
-    Step,
-    Stop,
-}
 
-pub enum Update {
-    PuzzleListing(Vec<PuzzleListing>),
-    NewPuzzle(PuzzleState, PuzzleGui),
-    Step(PuzzleState, StepResult),
+const GHOST_MINE: Color32 = Color32::from_rgb(230, 140, 20);
+const GHOST_SAFE: Color32 = Color32::from_rgb(120, 200, 120);
+const SELECTED: Color32 = Color32::YELLOW;
+/// Squares whose constraints the step shown changed.
+const RIPPLE: Color32 = Color32::from_rgb(0, 220, 220);
+/// Squares the puzzle file has problems with.
+const BROKEN: Color32 = Color32::from_rgb(200, 0, 200);
+
+const MAX_ZOOM: f32 = 20.0;
+/// The least a square found by searching is zoomed in to.
+const FIND_ZOOM: f32 = 4.0;
+/// How long a found square pulses, in seconds.
+const PULSE_TIME: f32 = 1.5;
+
+/// How long a square has to be held down to flag it, in seconds.
+const LONG_PRESS: f64 = 0.5;
+/// How far the pointer can wander while a square is held, in points.
+const LONG_PRESS_SLOP: f32 = 6.0;
+
+/// The direction on screen each arrow key moves the selection.
+const ARROW_KEYS: [(egui::Key, Vec2); 4] = [
+    (egui::Key::ArrowLeft, Vec2::new(-1.0, 0.0)),
+    (egui::Key::ArrowRight, Vec2::new(1.0, 0.0)),
+    (egui::Key::ArrowUp, Vec2::new(0.0, -1.0)),
+    (egui::Key::ArrowDown, Vec2::new(0.0, 1.0)),
+];
+
+/// Unrevealed squares in the game's color hints, one color per hint in the order they're listed.
+const GROUP_COLORS: [Color32; 6] = [
+    Color32::from_rgb(150, 60, 200),
+    Color32::from_rgb(20, 150, 150),
+    Color32::from_rgb(200, 120, 20),
+    Color32::from_rgb(60, 150, 40),
+    Color32::from_rgb(200, 60, 130),
+    Color32::from_rgb(110, 110, 40),
+];
+
+const HIGH_CONTRAST_REVEALED: Color32 = Color32::from_rgb(230, 230, 230);
+/// The length of the longer side of the minimap.
+const MINIMAP_SIZE: f32 = 160.0;
+
+/// The width the control panel starts at, and the widths it can be dragged to.
+const SIDEBAR_WIDTH: f32 = 200.0;
+const SIDEBAR_WIDTHS: RangeInclusive<f32> = 160.0..=480.0;
+/// Space left between the board and the edges of its panel.
+const BOARD_PADDING: f32 = 12.0;
+
+/// Which side of the window the control panel is docked to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Dock {
+    Left,
+    Right,
 }
 
-pub fn start_engine(send: Sender<Update>, recieve: Receiver<Command>) {
-    let parser = Parser::new();
-    let puzzles = parser.read_all_puzzles();
-    send.send(Update::PuzzleListing(puzzles)).unwrap();
-    let mut solver = None;
-    let mut running = false;
-    loop {
-        let command = if running {
-            recieve.try_recv().unwrap_or(Command::Run)
-        } else {
-            recieve.recv().unwrap()
-        };
+impl Dock {
+    pub const ALL: [Dock; 2] = [Dock::Left, Dock::Right];
 
-        if command == Command::Run {
-            running = true;
-        } else {
-            running = false;
+    pub fn name(self) -> &'static str {
+        match self {
+            Dock::Left => "left",
+            Dock::Right => "right",
         }
+    }
 
-        match command {
-            Command::Load(listing) => {
-                let (puzzle, gui) = listing.read();
-                let new_solver = Solver::new(puzzle, 3, 9);
-                send.send(Update::NewPuzzle(new_solver.puzzle.clone(), gui)).unwrap();
-                solver = Some(new_solver);
-            }
-            Command::Run | Command::Step => {
-                if let Some(s) = solver.as_mut() {
-                    let response = s.step();
-                    match response {
-                        StepResult::Finished | StepResult::UnexpectedStop(_) => {
-                            running = false
-                        }
-                        _ => {}
-                    }
-                    send.send(Update::Step(s.puzzle.clone(), response)).unwrap();
-                }
-            }
-            Command::Stop => {}
+    pub fn from_name(name: &str) -> Option<Dock> {
+        Dock::ALL.iter().copied().find(|dock| dock.name() == name)
+    }
+}
+
+/// How big the interface is drawn and where its panels go, saved between runs.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    /// Multiplies the display's own pixels per point.
+    pub ui_scale: f32,
+    pub text_scale: f32,
+    /// Width of the outline around squares, in points.
+    pub line_width: f32,
+    pub dock: Dock,
+    /// Whether the engine's step rate, memory and backlog are shown over the board.
+    pub resources: bool,
+    /// Pure colors and white text, with squares told apart by outline rather than by dimming.
+    pub high_contrast: bool,
+    /// Whether tapping a square reveals it and holding it down or right clicking flags it, rather
+    /// than only selecting it.
+    pub touch_play: bool,
+    /// Hides everything worked out from where the mines are, like other layouts and what an
+    /// unresolved square holds, so puzzles can be played blind.
+    pub player_view: bool,
+    /// How many unsolved constraints a run can build up before it pauses to ask how to go on.
+    pub constraint_warning: Option<usize>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        DisplaySettings {
+            ui_scale: 1.0,
+            text_scale: 1.0,
+            line_width: 1.0,
+            dock: Dock::Left,
+            resources: false,
+            high_contrast: false,
+            touch_play: false,
+            player_view: false,
+            constraint_warning: Some(20_000),
         }
     }
 }
 
+const DISPLAY_KEY: &str = "display";
+
+/// The time of day in UTC, as hours, minutes and seconds.
+fn timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    format!("{:02}:{:02}:{:02} UTC", seconds / 3600 % 24, seconds / 60 % 60, seconds % 60)
+}
+
 pub struct TemplateApp {
     step: usize,
     display_puzzle: bool,
-    send: Sender<Command>,
-    recieve: Receiver<Update>,
+    engine: EngineHandle,
     listing: Vec<PuzzleListing>,
+    /// Badges of the puzzles in `listing`, as they are worked out.
+    badges: Vec<Option<Badge>>,
+    /// Puzzles picked with ctrl or shift clicks, by index in `listing`.
+    selection: BTreeSet<usize>,
+    /// Where a shift click selects from.
+    selection_anchor: Option<usize>,
+    /// Reports of queued puzzles, in the order they finished.
+    results: Vec<(PuzzleListing, SolveReport)>,
+    show_statistics: bool,
+    /// The solves in the history, while it's being browsed.
+    history: Option<Vec<HistoryEntry>>,
+    /// Only solves of puzzles with this in their title are shown in the history.
+    history_filter: String,
+    /// Queued solves by number, with the puzzle's name.
+    jobs: Vec<(String, JobStatus)>,
+    show_dashboard: bool,
+    /// Limits the puzzle list to queued puzzles picked out in the statistics.
+    library_filter: Option<LibraryFilter>,
+    /// Messages shown briefly in the corner, with the time they disappear once shown.
+    notifications: Vec<(String, Option<f64>)>,
+    /// Engine errors, with the time they arrived.
+    errors: Vec<(String, String)>,
     puzzle: Option<PuzzleDisplay>,
+    config: SolverConfig,
+    /// How the next puzzle loaded is parsed.
+    parse_mode: ParseMode,
+    show_settings: bool,
+    /// The alternative layout drawn over the board, if any.
+    ghost: Option<usize>,
+    /// The race against the solver on the shown puzzle, while one is on.
+    race: Option<Race>,
+    /// How far the board is zoomed in.  At 1 the whole board fits.
+    zoom: f32,
+    /// The point of the board, in puzzle coordinates, shown in the middle of the view.
+    center: Pos2,
+    /// The square shown in the inspector.
+    selected: Option<usize>,
+    /// When the pointer went down on the board, and whether it has been held long enough to flag.
+    press: Option<(f64, bool)>,
+    /// Whether the constraint graph is shown instead of the board.
+    show_hypergraph: bool,
+    show_timeline: bool,
+    /// Whether squares whose constraints changed in the step shown are outlined.
+    show_ripple: bool,
+    /// Whether the view stays on the newest step as the solver adds them, until scrubbed back.
+    follow_live: bool,
+    /// What the constraint graph is exported as.
+    graph_format: GraphFormat,
+    filter_text: String,
+    /// What was typed into the search for a square.
+    find_text: String,
+    /// How many mines fixing the shown puzzle may move, and the seed that picks them.
+    repair_moves: usize,
+    repair_seed: u64,
+    /// The square last found by searching, and when, while it pulses.
+    pulse: Option<(usize, f64)>,
+    display: DisplaySettings,
+    /// What the display settings are reset to, from the config file.
+    display_defaults: DisplaySettings,
+    /// The display settings in effect, to notice when they change.
+    applied_display: Option<DisplaySettings>,
+    /// The engine's latest report on the shown puzzle.
+    resources: Option<EngineResources>,
+    /// Why the shown puzzle's run was paused, until the user picks how to go on.
+    warning: Option<String>,
+    /// Why the engine stopped, until it's restarted.
+    crashed: Option<String>,
+    /// How many updates were waiting at the start of the last frame.
+    backlog: usize,
 }
 
 pub struct PuzzleDisplay {
+    listing: PuzzleListing,
     starting_state: PuzzleState,
     gui: PuzzleGui,
-    steps: Vec<(PuzzleState, StepResult)>
+    steps: Vec<(PuzzleState, StepResult)>,
+    stats: SolverStats,
+    uniqueness: Option<Uniqueness>,
+    alternatives: Option<Alternatives>,
+    report: Option<SolveReport>,
+    /// The solver's latest constraints on the selected square.
+    constraints: Vec<Constraint>,
+    hypergraph: Hypergraph,
+    /// Unsolved constraints matching the filter, once one is set.
+    filtered: Option<Result<Vec<Constraint>, String>>,
+    /// Single changes that would let the solver finish, once asked for.
+    suggestions: Option<Option<Vec<Suggestion>>>,
+    /// Squares that can be proven after the latest step, once asked for.  Cleared by the next step.
+    provable: Option<Provable>,
+    /// The solver's stats after each step, from the start.
+    timeline: Vec<TimelinePoint>,
+    /// For each step, the squares whose unsolved constraints it added, removed or tightened.
+    ripples: Vec<Bits>,
+    /// How long each step took.
+    durations: Vec<Duration>,
+}
+
+/// How much work the solver had left after a step.
+struct TimelinePoint {
+    step: usize,
+    unsolved: usize,
+    solved: usize,
+    remaining: usize,
+    difficulty: f64,
+    /// How long the step took, in milliseconds.
+    milliseconds: f64,
 }
 
 impl Default for TemplateApp {
     fn default() -> Self {
-        let (tx1, rx1) = mpsc::channel();
-        let (tx2, rx2) = mpsc::channel();
-
-        thread::spawn(move || start_engine(tx1, rx2));
+        let (file_config, errors) = match Config::load() {
+            Ok(file_config) => (file_config, Vec::new()),
+            Err(error) => (Config::default(), vec![(timestamp(), error)]),
+        };
+        let folders = file_config.puzzles.clone();
+        let display_defaults = DisplaySettings { high_contrast: file_config.high_contrast, dock: file_config.dock, constraint_warning: file_config.constraint_warning, ..DisplaySettings::default() };
+        // Browsers have no threads to run the engine on
+        let engine = if cfg!(target_arch = "wasm32") { EngineHandle::local(folders) } else { EngineHandle::spawn(folders) };
 
         Self {
             step: 0,
-            send: tx2,
-            recieve: rx1,
+            engine,
             listing: Vec::new(),
+            badges: Vec::new(),
+            selection: BTreeSet::new(),
+            selection_anchor: None,
+            results: Vec::new(),
+            show_statistics: false,
+            history: None,
+            history_filter: String::new(),
+            jobs: Vec::new(),
+            show_dashboard: false,
+            library_filter: None,
+            notifications: Vec::new(),
+            errors,
             puzzle: None,
             display_puzzle: false,
+            config: file_config.solver,
+            parse_mode: file_config.parse_mode,
+            show_settings: false,
+            ghost: None,
+            race: None,
+            zoom: 1.0,
+            center: Pos2::ZERO,
+            selected: None,
+            press: None,
+            show_hypergraph: false,
+            show_timeline: false,
+            show_ripple: false,
+            follow_live: false,
+            graph_format: file_config.graph_format,
+            filter_text: String::new(),
+            find_text: String::new(),
+            repair_moves: 0,
+            repair_seed: 0,
+            pulse: None,
+            display: display_defaults,
+            display_defaults,
+            applied_display: None,
+            resources: None,
+            warning: None,
+            crashed: None,
+            backlog: 0,
         }
     }
 }
 
 impl TemplateApp {
+    /// Starts a new engine after the last one crashed, and forgets what the old one was showing and
+    /// solving.
+    fn restart_engine(&mut self) {
+        self.engine.restart();
+        self.crashed = None;
+        self.puzzle = None;
+        self.display_puzzle = false;
+        self.step = 0;
+        self.selected = None;
+        self.ghost = None;
+        self.race = None;
+        self.pulse = None;
+        self.resources = None;
+        self.warning = None;
+        // The new engine numbers its jobs from 0
+        self.jobs.clear();
+        // Send it the constraint warning again
+        self.applied_display = None;
+        if self.show_hypergraph {
+            self.engine.send(Command::WatchConstraints(true));
+        }
+    }
+
     fn recieve_updates(&mut self) {
-        while let Ok(update) = self.recieve.try_recv() {
+        self.backlog = 0;
+        for update in self.engine.updates() {
+            self.backlog += 1;
             match update {
-                Update::NewPuzzle(state, gui) => {
+                Update::NewPuzzle(listing, state, gui) => {
+                    let hypergraph = Hypergraph::new(&gui);
+                    self.warning = None;
+                    self.zoom = 1.0;
+                    self.center = Pos2::new((gui.min_x + gui.max_x) / 2.0, (gui.min_y + gui.max_y) / 2.0);
                     self.puzzle = Some(PuzzleDisplay {
+                        listing,
                         starting_state: state,
                         gui,
-                        steps: vec![]
+                        steps: vec![],
+                        stats: SolverStats::default(),
+                        uniqueness: None,
+                        alternatives: None,
+                        report: None,
+                        constraints: Vec::new(),
+                        hypergraph,
+                        filtered: None,
+                        suggestions: None,
+                        provable: None,
+                        timeline: Vec::new(),
+                        ripples: Vec::new(),
+                        durations: Vec::new(),
                     });
+                    self.selected = None;
+                    self.ghost = None;
+                    self.race = None;
+                    self.pulse = None;
+                    self.resources = None;
                     self.step = 0;
                     self.display_puzzle = true;
                 }
                 Update::PuzzleListing(listing) => {
+                    self.badges = vec![None; listing.len()];
+                    self.selection.clear();
                     self.listing = listing
                 }
-                Update::Step(state, result) => {
-                    let display = self.puzzle.as_mut().expect("Not in a puzzle!");
-                    if self.step == display.steps.len() {
-                        self.step += 1;
+                Update::Badge(index, badge) => {
+                    self.badges[index] = Some(badge);
+                }
+                Update::Suggestions(suggestions) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").suggestions = Some(suggestions);
+                }
+                Update::Repaired(changes) => {
+                    let name = &self.puzzle.as_ref().expect("Not in a puzzle!").listing.name;
+                    let summary = if changes.is_empty() { String::from("already solvable") } else { changes.join("\n") };
+                    self.notifications.push((format!("{}: {}", name, summary), None));
+                }
+                Update::Provable(provable) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").provable = Some(provable);
+                }
+                Update::Ripple(ripple) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").ripples.push(ripple);
+                }
+                Update::RaceMove(played) => {
+                    if let Some(race) = self.race.as_mut() {
+                        race.player.push(played);
                     }
-
+                }
+                Update::RaceSolver(run) => {
+                    if let Some(race) = self.race.as_mut().filter(|race| race.solver.is_none()) {
+                        race.solver = Some(run);
+                    }
+                }
+                Update::Exported(path) => {
+                    self.notifications.push((format!("Wrote {}", path), None));
+                }
+                Update::Error(message) => {
+                    self.errors.push((timestamp(), message));
+                }
+                Update::Warning(message) => {
+                    self.warning = Some(message);
+                }
+                Update::EngineCrashed(reason) => {
+                    self.errors.push((timestamp(), format!("The engine stopped: {}", reason)));
+                    self.crashed = Some(reason);
+                }
+                Update::Resources(resources) => {
+                    self.resources = Some(resources);
+                }
+                Update::JobQueued(id, name) => {
+                    debug_assert_eq!(id, self.jobs.len(), "Jobs are numbered in the order they're queued");
+                    self.jobs.push((name, JobStatus::Waiting));
+                }
+                Update::Job(id, status) => {
+                    self.jobs[id].1 = status;
+                }
+                Update::Queued(listing, report) => {
+                    self.notifications.push((format!("{}: {}", report.title, report.outcome), None));
+                    self.results.push((listing, report));
+                }
+                Update::Step(state, result, took) => {
+                    let display = self.puzzle.as_mut().expect("Not in a puzzle!");
                     display.steps.push((state, result));
+                    display.durations.push(took);
+                    if self.follow_live || self.step + 1 == display.steps.len() {
+                        self.step = display.steps.len();
+                    }
+                    display.provable = None;
+                }
+                Update::Stats(stats) => {
+                    let display = self.puzzle.as_mut().expect("Not in a puzzle!");
+                    let point = TimelinePoint {
+                        step: display.steps.len(),
+                        unsolved: stats.unsolved_constraints,
+                        solved: stats.solved_constraints,
+                        remaining: stats.cells_remaining,
+                        difficulty: DifficultyModel::default().score(&stats),
+                        milliseconds: display.durations.last().filter(|_| !display.steps.is_empty()).map_or(0.0, |took| took.as_secs_f64() * 1000.0),
+                    };
+                    // Stats are sent again without a step when a puzzle is picked up where it was left
+                    if display.timeline.last().is_some_and(|last| last.step == point.step) {
+                        display.timeline.pop();
+                    }
+                    display.timeline.push(point);
+                    display.stats = stats;
+                }
+                Update::Uniqueness(uniqueness) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").uniqueness = Some(uniqueness);
+                }
+                Update::Alternatives(alternatives) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").alternatives = Some(alternatives);
+                    self.ghost = None;
+                }
+                Update::History(entries) => {
+                    self.history = Some(entries);
+                }
+                Update::Report(report) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").report = Some(report);
+                }
+                Update::Constraints(constraints) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").constraints = constraints;
+                }
+                Update::Filtered(filtered) => {
+                    self.puzzle.as_mut().expect("Not in a puzzle!").filtered = filtered;
+                }
+                Update::Unsolved(constraints) => {
+                    let display = self.puzzle.as_mut().expect("Not in a puzzle!");
+                    display.hypergraph.set_constraints(constraints, &display.gui);
                 }
             }   
         }
@@ -133,16 +479,53 @@ impl epi::App for TemplateApp {
         &mut self,
         _ctx: &egui::CtxRef,
         _frame: &mut epi::Frame<'_>,
-        _storage: Option<&dyn epi::Storage>
+        storage: Option<&dyn epi::Storage>
     ) {
+        if let Some(display) = storage.and_then(|storage| epi::get_value(storage, DISPLAY_KEY)) {
+            self.display = display;
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn epi::Storage) {
+        epi::set_value(storage, DISPLAY_KEY, &self.display);
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
         self.recieve_updates();
+        // Keep taking updates while they come, as the engine waits once the GUI falls behind
+        if self.backlog > 0 {
+            ctx.request_repaint();
+        }
+        if crash_window(ctx, self.crashed.as_deref()) {
+            self.restart_engine();
+        }
+        if self.applied_display != Some(self.display) {
+            apply_display(ctx, frame, &self.display);
+            if self.applied_display.is_none_or(|applied| applied.constraint_warning != self.display.constraint_warning) {
+                self.engine.send(Command::WarnAt(self.display.constraint_warning));
+            }
+            self.applied_display = Some(self.display);
+        }
+        if let (true, Some(display)) = (self.show_hypergraph, self.puzzle.as_mut()) {
+            if display.hypergraph.relax() {
+                ctx.request_repaint();
+            }
+        }
 
-        let Self {step, listing, puzzle, send, display_puzzle, .. } = self;
+        let Self {step, listing, badges, selection, selection_anchor, results, show_statistics, history, history_filter, jobs, show_dashboard, library_filter, notifications, errors, puzzle, engine, display_puzzle, config, parse_mode, show_settings, ghost, race, zoom, center, selected, press, show_hypergraph, show_timeline, show_ripple, follow_live, graph_format, filter_text, find_text, repair_moves, repair_seed, pulse, display, display_defaults, warning, .. } = self;
+
+        settings_window(ctx, show_settings, config, parse_mode, display, *display_defaults, Some(&*engine).filter(|_| puzzle.is_some() && *display_puzzle));
+        statistics_window(ctx, show_statistics, results, library_filter);
+        history_window(ctx, history, history_filter);
+        dashboard_window(ctx, show_dashboard, jobs, engine);
+        if display.resources {
+            resource_overlay(ctx, self.resources.as_ref(), self.backlog);
+        }
+        show_notifications(ctx, notifications);
+        warning_window(ctx, warning, puzzle.as_ref().map_or(0, |display| display.stats.unsolved_constraints), engine);
+        error_console(ctx, errors);
 
         // Examples of how to create different panels and windows.
         // Pick whichever suits you.
@@ -158,17 +541,46 @@ impl epi::App for TemplateApp {
                 }
             };
 
-            let sidebar_width = 200.0;
-
-            
-
-            egui::SidePanel::left("side_panel").min_width(sidebar_width).max_width(sidebar_width).resizable(false).show(ctx, |ui| {
+            let side_panel = match display.dock {
+                Dock::Left => egui::SidePanel::left("side_panel"),
+                Dock::Right => egui::SidePanel::right("side_panel"),
+            };
+            side_panel.default_width(SIDEBAR_WIDTH).width_range(SIDEBAR_WIDTHS).resizable(true).show(ctx, |ui| {
                 ui.heading("Control Panel");
-                if ui.button("Back").clicked() {
-                    *display_puzzle = false;
-                    send.send(Command::Stop).unwrap();
+                ui.horizontal(|ui| {
+                    if ui.button("Back").clicked() {
+                        *display_puzzle = false;
+                        engine.send(Command::Stop);
+                    }
+                    if ui.button("Settings").clicked() {
+                        *show_settings = true;
+                    }
+                });
+                if ui.checkbox(show_hypergraph, "Show constraint graph").changed() {
+                    engine.send(Command::WatchConstraints(*show_hypergraph));
                 }
+                ui.checkbox(show_timeline, "Show timeline");
+                ui.checkbox(show_ripple, "Outline changed constraints").on_hover_text("Outline every square whose constraints the step shown added, removed or tightened");
+                ui.horizontal(|ui| {
+                    if ui.button("Export constraint graph").on_hover_text("Write the constraints after the latest step to the current folder").clicked() {
+                        engine.send(Command::ExportGraph(*graph_format));
+                    }
+                    egui::ComboBox::from_id_source("graph_format").selected_text(graph_format.name()).width(70.0).show_ui(ui, |ui| {
+                        for &format in GraphFormat::ALL.iter() {
+                            ui.selectable_value(graph_format, format, format.name());
+                        }
+                    });
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Export steps").on_hover_text("Write the steps taken so far to the current folder as JSON").clicked() {
+                        engine.send(Command::ExportSteps);
+                    }
+                    if ui.button("Save analysis").on_hover_text("Save the puzzle with the steps taken so far to the current folder, to open again from a puzzle folder").clicked() {
+                        engine.send(Command::SaveBundle);
+                    }
+                });
 
+                let viewed = *step;
                 ui.horizontal(|ui| {
                     if ui.button("<").clicked() {
                         *step = step.saturating_sub(1);
@@ -179,108 +591,1428 @@ impl epi::App for TemplateApp {
                     if ui.button(">").clicked() {
                         *step = puzzle_display.steps.len().min(1+*step);
                     }
+                    if ui.checkbox(follow_live, "Follow live").on_hover_text("Keep showing the newest step as the solver runs").changed() && *follow_live {
+                        *step = puzzle_display.steps.len();
+                    }
                 });
 
+                if let Some(jump) = step_scrubber(ui, puzzle_display, ui.available_width()) {
+                    *step = jump;
+                }
+                // Going back to look at an earlier step lets go of the newest
+                if *step < viewed {
+                    *follow_live = false;
+                }
+
+                // While racing the solver only plays on a copy, out of sight
+                if race.is_none() {
+                    ui.horizontal(|ui| {
+                        if ui.button("Start").clicked() {
+                            engine.send(Command::Run);
+                        }
+                        if ui.button("Step").clicked() {
+                            engine.send(Command::Step);
+                        }
+                        if ui.button("Stop").clicked() {
+                            engine.send(Command::Stop);
+                        }
+                        if ui.button("Race the solver").on_hover_text("Solve the rest yourself while the solver does too, then compare").clicked() {
+                            *race = Some(Race::default());
+                            *step = puzzle_display.steps.len();
+                            engine.send(Command::Race(true));
+                        }
+                    });
+                }
+
+                let text = current_step.map(|result| {
+                    let took = puzzle_display.durations[*step - 1].as_secs_f64() * 1000.0;
+                    format!("{} ({:.2} ms)", describe_step(result, &puzzle_display.starting_state.base), took)
+                }).unwrap_or_default();
+
+                ui.label(text);
+
+                if let Some(StepResult::Progress{deductions, ..}) = current_step {
+                    for deduction in deductions {
+                        let action = match deduction.action {
+                            Action::Reveal => "reveal",
+                            Action::Flag => "flag",
+                        };
+                        let c = deduction.constraint;
+                        ui.label(format!("{} ({}->{}/{}): {} {}", c.technique, c.min_mines, c.max_mines, c.size, action, format_text(deduction.cells.count_ones())));
+                    }
+                }
+                if let Some(result @ (StepResult::Progress{..} | StepResult::CrossConstraint(_))) = current_step {
+                    egui::CollapsingHeader::new("Tutorial").id_source("tutorial").show(ui, |ui| {
+                        let before = match *step {
+                            1 => &puzzle_display.starting_state,
+                            step => &puzzle_display.steps[step - 2].0,
+                        };
+                        for sentence in narrate(result, before) {
+                            ui.label(sentence);
+                        }
+                    });
+                }
+
+                ui.separator();
+                let stats = &puzzle_display.stats;
+                ui.add(egui::ProgressBar::new(stats.progress()).text(format!("{} of {} squares left", stats.cells_remaining, stats.cells_total)));
+                let trend = match stats.unsolved_constraints.cmp(&stats.previous_unsolved_constraints) {
+                    Ordering::Less => "falling",
+                    Ordering::Equal => "steady",
+                    Ordering::Greater => "rising",
+                };
+                ui.label(format!("Unsolved constraints: {} ({}), {} pending", stats.unsolved_constraints, trend, stats.pending_constraints));
+                ui.label(format!("Frontier uncertainty: {:.1} bits", stats.frontier_entropy));
+                ui.label(format!("Difficulty so far: {:.1}", DifficultyModel::default().score(stats)));
+                if let Some(report) = &puzzle_display.report {
+                    ui.label(format!("Last solve: {} in {} steps, difficulty {:.1}", report.outcome, report.stats.steps, report.difficulty));
+                    for (rank, guess) in report.best_guesses.iter().enumerate() {
+                        let label = &puzzle_display.starting_state.base.id_labels[guess.square];
+                        ui.label(format!("Guess {}: {} ({:.0}% safe, settles {:.1})", rank + 1, label, guess.safety * 100.0, guess.information));
+                    }
+                }
+
                 ui.horizontal(|ui| {
-                    if ui.button("Start").clicked() {
-                        send.send(Command::Run).unwrap();
+                    if ui.button("Check uniqueness").clicked() {
+                        engine.send(Command::VerifyUnique);
                     }
-                    if ui.button("Step").clicked() {
-                        send.send(Command::Step).unwrap();
+                    ui.label(match puzzle_display.uniqueness {
+                        None => String::new(),
+                        Some(Uniqueness::Unique) => String::from("Unique"),
+                        Some(Uniqueness::Ambiguous(_)) if display.player_view => String::from("Ambiguous"),
+                        Some(Uniqueness::Ambiguous(alternative)) => format!("Ambiguous ({} differ)", format_text((alternative ^ puzzle_display.starting_state.base.mines).count_ones())),
+                        Some(Uniqueness::Undetermined) => String::from("Gave up"),
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Show provable squares").on_hover_text("Everything that follows from the latest step, found all at once").clicked() {
+                        engine.send(Command::ProveCurrent);
                     }
-                    if ui.button("Stop").clicked() {
-                        send.send(Command::Stop).unwrap();
+                    if let Some(provable) = &puzzle_display.provable {
+                        ui.label(format!("{} safe, {} mines{}", provable.safe.count_ones(), provable.mines.count_ones(), if provable.complete { "" } else { " (gave up)" }));
                     }
                 });
 
-                let text = match current_step.as_ref() {
-                    None => String::new(),
-                    Some(StepResult::CrossConstraint(c)) => format!("Crossing constraint.  Min: {} Max: {}", c.min_mines, c.max_mines),
-                    Some(StepResult::Progress{revealed, flagged}) => {
-                        if revealed.any() {
-                            if flagged.any() {
-                                format!("Found {} to be revealed and {} to be flagged", format_text(revealed.count_ones()), format_text(flagged.count_ones()))
-                            } else {
-                                format!("Found {} squares to be revealed", format_text(revealed.count_ones()))
-                            }
+                // Other layouts, fixes and suggestions all come from where the mines really are
+                if !display.player_view {
+                    ui.horizontal(|ui| {
+                        if ui.button("Show alternative layouts").clicked() {
+                            engine.send(Command::FindAlternatives);
+                        }
+                        if ui.button("Suggest fixes").on_hover_text("Find single hints or revealed squares that let the puzzle be solved").clicked() {
+                            engine.send(Command::SuggestFixes);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Fix solvability").on_hover_text("Move up to this many mines, then add hints until the puzzle can be solved without guessing").clicked() {
+                            engine.send(Command::Repair(*repair_moves, *repair_seed));
+                        }
+                        ui.add(egui::DragValue::new(repair_moves));
+                        ui.label("Mines to move");
+                        ui.add(egui::DragValue::new(repair_seed));
+                        ui.label("Seed");
+                    });
+                    match &puzzle_display.suggestions {
+                        Some(None) => {
+                            ui.label("The solver doesn't get stuck");
+                        }
+                        Some(Some(suggestions)) if suggestions.is_empty() => {
+                            ui.label("No single hint or revealed square lets the solver finish");
+                        }
+                        Some(Some(suggestions)) => {
+                            egui::ScrollArea::from_max_height(100.0).show(ui, |ui| {
+                                for suggestion in suggestions {
+                                    ui.label(suggestion.describe(&puzzle_display.starting_state.base));
+                                }
+                            });
+                        }
+                        None => {}
+                    }
+                    if let Some(alternatives) = &puzzle_display.alternatives {
+                        if alternatives.layouts.is_empty() {
+                            ui.label("No other layout fits the clues");
                         } else {
-                            format!("Found {} squares to be flagged", format_text(flagged.count_ones()))
+                            ui.label(format!("{} alternative layouts{} over {}", alternatives.layouts.len(), if alternatives.complete { "" } else { " (and more)" }, format_text(alternatives.ambiguous.count_ones())));
+                            ui.radio_value(ghost, None, "Real layout");
+                            for i in 0..alternatives.layouts.len() {
+                                ui.radio_value(ghost, Some(i), format!("Layout {}", i + 1));
+                            }
                         }
                     }
-                    Some(StepResult::Finished) => String::from("Finished!"),
-                    Some(StepResult::UnexpectedStop(why)) => format!("Unexpected stop! Reason: {}", why),
-                    Some(StepResult::CliqueConstraint(_)) => format!("Found maximal clique!  Adding remaining squares to constraint"),
-                };
+                }
+
+                let techniques = &puzzle_display.stats.techniques;
+                ui.label(format!("Deductions: {}", techniques.total()));
+                ui.label(format!("Crossings: {} ({} skipped, {} mirrored, {} triple)", puzzle_display.stats.crosses, puzzle_display.stats.crosses_skipped, puzzle_display.stats.crosses_mirrored, puzzle_display.stats.triple_crosses));
+                ui.label(format!("Techniques used: {}", techniques));
 
-                ui.label(text)
+                ui.separator();
+                let filter_width = ui.available_width() - 90.0;
+                // Squares are found by the ID in the puzzle's file, or failing that by index
+                ui.horizontal(|ui| {
+                    let edit = ui.add(egui::TextEdit::singleline(find_text).desired_width(filter_width).hint_text("Square ID or index"));
+                    let entered = edit.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                    if ui.button("Find").clicked() || entered {
+                        let base = &puzzle_display.starting_state.base;
+                        let wanted = find_text.trim();
+                        let found = base.id_labels.iter().position(|label| label == wanted).or_else(|| wanted.parse().ok().filter(|&index| index < base.size()));
+                        match found {
+                            Some(square) => {
+                                let object = &puzzle_display.gui.squares[square];
+                                *center = Pos2::new(object.x, object.y);
+                                *zoom = zoom.max(FIND_ZOOM);
+                                *selected = Some(square);
+                                *pulse = Some((square, ui.input().time));
+                                engine.send(Command::Inspect(*selected));
+                            }
+                            None => notifications.push((format!("No square has the ID or index {}", wanted), None)),
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(filter_text).desired_width(filter_width).hint_text("size<=3 && max-min==1"));
+                    if ui.button("Filter").clicked() {
+                        let source = Some(filter_text.trim().to_string()).filter(|source| !source.is_empty());
+                        engine.send(Command::Filter(source));
+                    }
+                });
+                match &puzzle_display.filtered {
+                    Some(Ok(constraints)) => {
+                        ui.label(format!("{} matching constraints after the latest step", constraints.len()));
+                        egui::ScrollArea::from_max_height(150.0).show(ui, |ui| {
+                            for c in constraints {
+                                ui.label(format!("{} ({}->{}/{}), depth {}", c.technique, c.min_mines, c.max_mines, c.size, c.depth));
+                            }
+                        });
+                    }
+                    Some(Err(why)) => {
+                        ui.colored_label(Color32::RED, why);
+                    }
+                    None => {}
+                }
 
+                if let Some(square) = *selected {
+                    ui.separator();
+                    cell_inspector(ui, puzzle_display, current_state, square, display.player_view);
+                }
             });
     
+            egui::TopBottomPanel::top("puzzle_header").show(ctx, |ui| {
+                puzzle_header(ui, puzzle_display);
+            });
+
+            if race.is_some() {
+                let latest = puzzle_display.steps.last().map_or(&puzzle_display.starting_state, |(state, _)| state);
+                let done = (latest.revealed | latest.flagged).count_ones() == latest.base.size();
+                race_window(ctx, race, &latest.base, done, engine);
+            }
+
+            if *show_timeline {
+                egui::TopBottomPanel::bottom("timeline").resizable(true).default_height(160.0).show(ctx, |ui| {
+                    timeline(ui, &puzzle_display.timeline, *step);
+                });
+            }
+
             egui::CentralPanel::default().show(ctx, |ui| {
-                let margin = 50.0;
-                let window_width = ui.available_width() - margin*2.0;
-                let window_height = ui.available_height() - margin*2.0;
-                
-                let offset_x = puzzle_display.gui.min_x;
-                let offset_y = puzzle_display.gui.min_y;
-                let display_width = puzzle_display.gui.max_x - offset_x;
-                let display_height = puzzle_display.gui.max_y - offset_y;
-                let scale = (window_width/display_width).min(window_height/display_height);
-
-                for (i, object) in puzzle_display.gui.squares.iter().enumerate() {
-                    let (mut color, text) = if current_state.revealed[i] {
-                        (Color32::GRAY, if current_state.base.unknowns[i] {
-                            String::from("?")
+                if *show_hypergraph {
+                    // The graph follows the solver, so color squares by its latest state rather than the step being viewed
+                    let latest = puzzle_display.steps.last().map_or(&puzzle_display.starting_state, |(state, _)| state);
+                    let hypergraph = &puzzle_display.hypergraph;
+                    let panel = ui.available_rect_before_wrap();
+                    hypergraph.draw(&ui.painter_at(panel), panel.shrink(BOARD_PADDING), |square| if latest.flagged[square] { Color32::RED } else { Color32::BLUE });
+                    if hypergraph.hidden > 0 {
+                        ui.label(format!("{} constraints too large to draw", hypergraph.hidden));
+                    }
+                    return;
+                }
+
+                // The whole board fits the panel at zoom 1, keeping its shape, with bars on the sides it doesn't fill
+                let panel = ui.available_rect_before_wrap();
+                let gui = &puzzle_display.gui;
+                let bounds = Rect::from_min_max(Pos2::new(gui.min_x, gui.min_y), Pos2::new(gui.max_x, gui.max_y));
+                let extent = board_extent(gui);
+                let board = letterbox(panel.shrink(BOARD_PADDING), extent.size());
+
+                let minimap_scale = MINIMAP_SIZE / extent.width().max(extent.height());
+                let minimap = Rect::from_min_size(panel.right_bottom() - extent.size()*minimap_scale - Vec2::splat(BOARD_PADDING), extent.size()*minimap_scale);
+                let show_minimap = *zoom > 1.0;
+
+                // Scrolling zooms, dragging pans, and clicking or dragging on the minimap jumps there
+                // On a touch screen pinching zooms and dragging with two fingers pans
+                let response = ui.interact(panel, ui.id().with("board"), Sense::click_and_drag());
+                let playing = display.touch_play || race.is_some();
+                let (scroll, pinch, touch, time, held_still) = {
+                    let input = ui.input();
+                    let pointer = &input.pointer;
+                    let held_still = pointer.press_origin().zip(pointer.interact_pos()).is_some_and(|(origin, pos)| origin.distance(pos) < LONG_PRESS_SLOP);
+                    (input.scroll_delta.y, input.zoom_delta(), input.multi_touch().map(|touch| touch.translation_delta), input.time, held_still)
+                };
+                if response.hovered() || touch.is_some() {
+                    *zoom = (*zoom * (scroll / 200.0).exp() * pinch).clamp(1.0, MAX_ZOOM);
+                }
+                let scale = board.width()/extent.width() * *zoom;
+                let mut clicked = None;
+                match response.interact_pointer_pos() {
+                    _ if touch.is_some() => *center -= touch.unwrap_or(Vec2::ZERO)/scale,
+                    Some(pos) if show_minimap && minimap.expand(BOARD_PADDING/2.0).contains(pos) => {
+                        *center = extent.min + (pos - minimap.min)/minimap_scale;
+                    }
+                    Some(pos) if response.clicked() => clicked = Some(pos),
+                    _ if response.dragged() => *center -= response.drag_delta()/scale,
+                    _ => {}
+                }
+
+                // Holding a square still flags it, and the click when it is let go doesn't count
+                let mut long_pressed = None;
+                match (response.is_pointer_button_down_on(), *press) {
+                    (true, None) => *press = Some((time, false)),
+                    (true, Some((start, false))) if playing && held_still && touch.is_none() => {
+                        if time - start >= LONG_PRESS {
+                            long_pressed = response.interact_pointer_pos();
+                            *press = Some((start, true));
                         } else {
-                            (current_state.base.neighbors[i] & current_state.base.mines & !current_state.flagged).count_ones().to_string()
-                        })
-                    } else if current_state.flagged[i] {
-                        (Color32::RED, String::new())
-                    } else {
-                        (Color32::BLUE, String::new())
-                    };
+                            ui.ctx().request_repaint();
+                        }
+                    }
+                    (false, Some((_, flagged))) => {
+                        *press = None;
+                        if flagged {
+                            clicked = None;
+                        }
+                    }
+                    _ => {}
+                }
+                let right_clicked = response.interact_pointer_pos().filter(|_| response.secondary_clicked());
 
-                    let should_highlight = match current_step {
-                        Some(StepResult::CrossConstraint(constraint)) => constraint.bits[i],
-                        Some(StepResult::Progress{revealed, flagged}) => revealed[i] | flagged[i],
-                        Some(StepResult::CliqueConstraint(constraint)) => constraint.bits[i],
-                        _ => false,
-                    };
-                    
-                    if !should_highlight {
+                // Once the board has keyboard focus, from a click or tab, the arrow keys move the selection to
+                // the neighbor that way, keeping it in view
+                let previous = *selected;
+                if response.has_focus() {
+                    let input = ui.input();
+                    let arrow = ARROW_KEYS.iter().find(|(key, _)| input.key_pressed(*key)).map(|&(_, direction)| direction);
+                    if let Some(direction) = arrow {
+                        *selected = match *selected {
+                            Some(square) => square_toward(gui, current_state.base.neighbors[square], square, direction).or(*selected),
+                            None => (!gui.squares.is_empty()).then_some(0),
+                        };
+                        if let Some(square) = selected.filter(|_| *zoom > 1.0) {
+                            *center = Pos2::new(gui.squares[square].x, gui.squares[square].y);
+                        }
+                    } else if input.key_pressed(egui::Key::Escape) {
+                        *selected = None;
+                    }
+                }
+                *center = if *zoom > 1.0 { bounds.clamp(*center) } else { extent.center() };
+
+                let to_screen = |x: f32, y: f32| board.center() + (Pos2::new(x, y) - *center)*scale;
+
+                let square_at = |pos: Pos2| gui.squares.iter().position(|object| {
+                    let points = object.points.iter().map(|a| to_screen(object.x + a.0, object.y + a.1)).collect::<SmallVec<[Pos2; 8]>>();
+                    polygon_contains(&points, pos)
+                });
+                // Clicking a square selects it for the inspector, and clicking it again deselects it.  Playing by
+                // touch, tapping also reveals it
+                if let Some(pos) = clicked {
+                    let square = square_at(pos);
+                    *selected = if square == *selected && !playing { None } else { square };
+                    response.request_focus();
+                    if let Some(square) = square.filter(|_| playing) {
+                        engine.send(Command::Play(square, Action::Reveal));
+                    }
+                }
+                if let Some(square) = long_pressed.or(right_clicked).filter(|_| playing).and_then(square_at) {
+                    engine.send(Command::Play(square, Action::Flag));
+                }
+                if *selected != previous {
+                    engine.send(Command::Inspect(*selected));
+                    // Screen readers announce whatever gains focus
+                    if let Some(square) = *selected {
+                        ui.output().events.push(OutputEvent::FocusGained(WidgetInfo::labeled(WidgetType::Other, describe_square(current_state, square))));
+                    }
+                }
+                response.widget_info(|| WidgetInfo::labeled(WidgetType::Other, format!("Board of {} squares, arrow keys move between them", gui.squares.len())));
+                if let Some(square) = response.hover_pos().and_then(square_at) {
+                    egui::show_tooltip_text(ui.ctx(), ui.id().with("square"), describe_square(current_state, square));
+                }
+                let painter = ui.painter_at(panel);
+
+                let highlighted = current_step.map(highlighted_squares).unwrap_or_else(Bits::zeroed);
+                for (i, object) in gui.squares.iter().enumerate() {
+                    let (mut color, text) = square_appearance(current_state, i);
+                    let should_highlight = highlighted[i];
+                    let group = color_group(gui, &current_state.base, i).filter(|_| !current_state.revealed[i] && !current_state.flagged[i]);
+                    if let Some(group) = group {
+                        color = GROUP_COLORS[group % GROUP_COLORS.len()];
+                    }
+
+                    if display.high_contrast {
+                        color = high_contrast(color);
+                    } else if !should_highlight {
                         color = color.linear_multiply(0.5)
                     }
 
-                    let base_position_x = (object.x - offset_x)*scale + margin + sidebar_width + 50.0;
-                    let base_position_y = (object.y - offset_y)*scale + margin;
-                    ui.painter().add(Shape::Path {
-                        points: object.points.iter().map(|a| Pos2 {
-                            x: a.0*scale + base_position_x,
-                            y: a.1*scale + base_position_y
-                        }).collect(),
+                    let ghost_layout = puzzle_display.alternatives.as_ref().zip(*ghost).filter(|_| !display.player_view).map(|(a, g)| (a.ambiguous, a.layouts[g]));
+                    if let Some((ambiguous, layout)) = ghost_layout {
+                        if ambiguous[i] {
+                            color = if layout[i] { GHOST_MINE } else { GHOST_SAFE };
+                        }
+                    }
+
+                    // Only drawn on the latest step, which is what they were proven from
+                    if let Some(provable) = puzzle_display.provable.as_ref().filter(|_| *step == puzzle_display.steps.len()) {
+                        if provable.mines[i] || provable.safe[i] {
+                            color = if provable.mines[i] { GHOST_MINE } else { GHOST_SAFE };
+                        }
+                    }
+
+                    if gui.diagnostics.iter().any(|diagnostic| diagnostic.node == current_state.base.id_labels[i]) {
+                        color = BROKEN;
+                    }
+
+                    let is_selected = *selected == Some(i);
+                    let rippled = *show_ripple && step.checked_sub(1).and_then(|shown| puzzle_display.ripples.get(shown)).is_some_and(|ripple| ripple[i]);
+                    // High contrast outlines the squares a step is about instead of dimming the rest
+                    let outline = match (is_selected, rippled, display.high_contrast && should_highlight) {
+                        (true, _, _) => Stroke::new(display.line_width * 3.0, SELECTED),
+                        (false, true, _) => Stroke::new(display.line_width * 3.0, RIPPLE),
+                        (false, false, true) => Stroke::new(display.line_width * 3.0, Color32::WHITE),
+                        (false, false, false) => Stroke::new(display.line_width, Color32::BLACK),
+                    };
+                    painter.add(Shape::Path {
+                        points: object.points.iter().map(|a| to_screen(object.x + a.0, object.y + a.1)).collect(),
                         closed: true,
                         fill: color,
-                        stroke: Stroke {
-                            width: 1.0,
-                            color: Color32::BLACK,
-                        }
+                        stroke: outline,
                     });
-                    ui.painter().text( Pos2 { x: base_position_x, y: base_position_y }, Align2::CENTER_CENTER, text, TextStyle::Body, Color32::WHITE);
+                    let text_color = if display.high_contrast && color == HIGH_CONTRAST_REVEALED { Color32::BLACK } else { Color32::WHITE };
+                    painter.text(to_screen(object.x, object.y), Align2::CENTER_CENTER, text, TextStyle::Body, text_color);
+                    if let Some(group) = group {
+                        let radius = object.points.iter().map(|a| a.0.hypot(a.1)).fold(f32::MAX, f32::min) * scale * 0.3;
+                        group_symbol(&painter, to_screen(object.x, object.y), radius, group, text_color);
+                    }
+                }
+
+                // A ring swells and shrinks around a square that was just found
+                if let Some((square, found)) = *pulse {
+                    let elapsed = (time - found) as f32;
+                    if elapsed < PULSE_TIME {
+                        let object = &gui.squares[square];
+                        let radius = object.points.iter().map(|a| a.0.hypot(a.1)).fold(0.0, f32::max) * scale;
+                        let swell = 1.2 + 0.3 * (elapsed * std::f32::consts::TAU * 2.0).sin();
+                        painter.circle_stroke(to_screen(object.x, object.y), radius * swell, Stroke::new(display.line_width * 3.0, SELECTED));
+                        ui.ctx().request_repaint();
+                    } else {
+                        *pulse = None;
+                    }
+                }
+
+                // Number the best guesses where the last solve got stuck
+                let stuck = *step == puzzle_display.steps.len() && puzzle_display.steps.last().is_some_and(|(_, result)| matches!(result, StepResult::Stuck));
+                if let Some(report) = puzzle_display.report.as_ref().filter(|_| stuck) {
+                    for (rank, guess) in report.best_guesses.iter().enumerate() {
+                        let object = &gui.squares[guess.square];
+                        painter.text(to_screen(object.x, object.y), Align2::CENTER_CENTER, format!("#{}", rank + 1), TextStyle::Body, SELECTED);
+                    }
+                }
+
+                // Label each highlighted constraint on the board, moving labels apart where they would overlap.
+                // A filter replaces the step's own constraints.
+                let mut highlighted: Vec<Constraint> = Vec::new();
+                match current_step {
+                    _ if matches!(puzzle_display.filtered, Some(Ok(_))) => {
+                        highlighted.extend(puzzle_display.filtered.iter().flatten().flatten().copied());
+                    }
+                    Some(StepResult::CrossConstraint(c)) | Some(StepResult::CliqueConstraint(c)) => highlighted.push(*c),
+                    Some(StepResult::Contradiction(constraints)) => highlighted.extend(constraints.iter().copied()),
+                    Some(StepResult::Progress{deductions, ..}) => {
+                        for deduction in deductions {
+                            if !highlighted.contains(&deduction.constraint) {
+                                highlighted.push(deduction.constraint);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                let mut labels: Vec<Rect> = Vec::new();
+                for c in highlighted {
+                    let cells = c.bits.iter_ones().map(|square| to_screen(gui.squares[square].x, gui.squares[square].y).to_vec2()).collect::<Vec<_>>();
+                    if cells.is_empty() {
+                        continue;
+                    }
+                    let centroid = (cells.iter().fold(Vec2::ZERO, |sum, &cell| sum + cell) / cells.len() as f32).to_pos2();
+                    let galley = painter.layout_no_wrap(TextStyle::Body, format!("{}->{}/{}", c.min_mines, c.max_mines, c.size));
+                    let label = place_label(centroid, galley.size + Vec2::splat(4.0), &labels);
+                    painter.rect_filled(label, 3.0, Color32::from_black_alpha(200));
+                    painter.galley(label.min + Vec2::splat(2.0), galley, Color32::WHITE);
+                    labels.push(label);
+                }
+
+                if !gui.color_hints.is_empty() {
+                    color_legend(&painter, panel.left_top() + Vec2::splat(BOARD_PADDING), gui, current_state);
+                }
+
+                if show_minimap {
+                    let frame = minimap.expand(BOARD_PADDING/2.0);
+                    let minimap_painter = painter.sub_region(frame);
+                    minimap_painter.rect_filled(frame, 2.0, Color32::from_black_alpha(200));
+                    draw_miniature(&minimap_painter, minimap, gui, current_state);
+
+                    let to_minimap = |p: Pos2| minimap.min + (p - extent.min)*minimap_scale;
+                    let visible = Rect::from_center_size(*center, panel.size()/scale);
+                    minimap_painter.rect_stroke(Rect::from_two_pos(to_minimap(visible.min), to_minimap(visible.max)), 0.0, Stroke::new(1.5, Color32::WHITE));
                 }
             });
         } else {
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.heading("Puzzles");
+                ui.horizontal(|ui| {
+                    if ui.button("Settings").clicked() {
+                        *show_settings = true;
+                    }
+                    if ui.add(egui::Button::new("Statistics").enabled(!results.is_empty())).on_hover_text("Difficulty, size and techniques across the queued puzzles").clicked() {
+                        *show_statistics = true;
+                    }
+                    if ui.add(egui::Button::new("Dashboard").enabled(!jobs.is_empty())).on_hover_text("Progress of the queued solves").clicked() {
+                        *show_dashboard = true;
+                    }
+                    if ui.button("History").on_hover_text("Every solve finished, here or from the command line").clicked() {
+                        engine.send(Command::History);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.add(egui::Button::new(format!("Add to queue ({})", selection.len())).enabled(!selection.is_empty())).clicked() {
+                        // Reading an unreadable puzzle would take down the queue
+                        let queued = selection.iter().filter(|&&index| badges[index] != Some(Badge::Unreadable)).map(|&index| listing[index].clone()).collect();
+                        engine.send(Command::Queue(queued, *config));
+                        selection.clear();
+                        *show_dashboard = true;
+                    }
+                    if ui.add(egui::Button::new("Clear selection").enabled(!selection.is_empty())).clicked() {
+                        selection.clear();
+                    }
+                    ui.label("Ctrl or shift click to select");
+                });
+
+                if !results.is_empty() {
+                    egui::CollapsingHeader::new(format!("Queue results ({})", results.len())).show(ui, |ui| {
+                        egui::Grid::new("queue_results").striped(true).show(ui, |ui| {
+                            ui.label(egui::Label::new("Puzzle").strong());
+                            ui.label(egui::Label::new("Result").strong());
+                            ui.label(egui::Label::new("Steps").strong());
+                            ui.label(egui::Label::new("Difficulty").strong());
+                            ui.end_row();
+                            for (_, report) in results.iter() {
+                                ui.label(&report.title);
+                                ui.label(report.outcome.to_string());
+                                ui.label(report.stats.steps.to_string());
+                                ui.label(format!("{:.1}", report.difficulty));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                }
+
+                if let Some(filter) = *library_filter {
+                    let mut clear = false;
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Showing queued puzzles {}", filter.describe()));
+                        clear = ui.button("Show all").clicked();
+                    });
+                    if clear {
+                        *library_filter = None;
+                    }
+                }
+
                 egui::ScrollArea::auto_sized().show(ui, |ui| {
-                    for item in listing.iter().cloned() {
-                        if ui.button(item.name.to_string()).clicked() {
-                            send.send(Command::Load(item)).unwrap();
+                    let shown = |index: usize| library_filter.is_none_or(|filter| results.iter().any(|(queued, report)| *queued == listing[index] && filter.matches(report)));
+                    let entries = (0..listing.len()).filter(|&index| shown(index)).collect::<Vec<_>>();
+                    if let Some(index) = puzzle_tree(ui, listing, badges, selection, &entries, 0) {
+                        let modifiers = ui.input().modifiers;
+                        match *selection_anchor {
+                            Some(anchor) if modifiers.shift => selection.extend(anchor.min(index)..=anchor.max(index)),
+                            _ if modifiers.shift || modifiers.command => {
+                                if !selection.remove(&index) {
+                                    selection.insert(index);
+                                }
+                            }
+                            _ => engine.send(Command::Load(listing[index].clone(), *config, *parse_mode)),
+                        }
+                        *selection_anchor = Some(index);
+                    }
+                });
+            });
+        }
+    }
+}
+
+/// Scales the interface and its text and sets its colors.  Rebuilding the fonts is slow, so only
+/// call this on changes.
+fn apply_display(ctx: &egui::CtxRef, frame: &epi::Frame<'_>, display: &DisplaySettings) {
+    let native = frame.info().native_pixels_per_point.unwrap_or(1.0);
+    ctx.set_pixels_per_point(native * display.ui_scale);
+
+    let mut visuals = egui::Visuals::dark();
+    if display.high_contrast {
+        visuals.override_text_color = Some(Color32::WHITE);
+        visuals.extreme_bg_color = Color32::BLACK;
+        visuals.widgets.noninteractive.bg_fill = Color32::BLACK;
+        visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+        visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+        visuals.selection.stroke = Stroke::new(2.0, SELECTED);
+    }
+    ctx.set_visuals(visuals);
+
+    let mut fonts = FontDefinitions::default();
+    for (_, size) in fonts.family_and_size.values_mut() {
+        *size *= display.text_scale;
+    }
+    ctx.set_fonts(fonts);
+}
+
+/// Solver and parser settings, which apply to the next puzzle that is loaded, and display settings,
+/// which apply immediately.  With `shown`, which a puzzle on screen is sent to, it can also be solved
+/// again with the solver settings, from the start or from where it is.
+fn settings_window(ctx: &egui::CtxRef, open: &mut bool, config: &mut SolverConfig, parse_mode: &mut ParseMode, display: &mut DisplaySettings, defaults: DisplaySettings, shown: Option<&EngineHandle>) {
+    egui::Window::new("Settings").open(open).show(ctx, |ui| {
+        ui.add(egui::Slider::new(&mut display.ui_scale, 0.5..=3.0).text("Interface scale"));
+        ui.add(egui::Slider::new(&mut display.text_scale, 0.5..=3.0).text("Text size"));
+        ui.add(egui::Slider::new(&mut display.line_width, 0.5..=5.0).text("Line width"));
+        ui.checkbox(&mut display.resources, "Show engine resources");
+        ui.checkbox(&mut display.high_contrast, "High contrast");
+        ui.checkbox(&mut display.touch_play, "Tap to reveal, hold or right click to flag");
+        ui.checkbox(&mut display.player_view, "Player view").on_hover_text("Hide anything that gives away unresolved squares");
+        ui.horizontal(|ui| {
+            ui.label("Control panel:");
+            for dock in Dock::ALL.iter().copied() {
+                ui.radio_value(&mut display.dock, dock, dock.name());
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut warn = display.constraint_warning.is_some();
+            ui.checkbox(&mut warn, "Pause runs past this many unsolved constraints");
+            match (warn, display.constraint_warning.as_mut()) {
+                (true, Some(limit)) => {
+                    ui.add(egui::DragValue::new(limit));
+                }
+                (true, None) => display.constraint_warning = Some(20_000),
+                (false, _) => display.constraint_warning = None,
+            }
+        });
+        if ui.button("Reset display").clicked() {
+            *display = defaults;
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Preset:");
+            for preset in Preset::ALL.iter().copied() {
+                if ui.button(preset.name()).clicked() {
+                    *config = config.with_preset(preset);
+                }
+            }
+        });
+
+        ui.separator();
+        egui::ComboBox::from_label("Inference").selected_text(config.tier.name()).show_ui(ui, |ui| {
+            for tier in InferenceTier::ALL.iter().copied() {
+                ui.selectable_value(&mut config.tier, tier, tier.name());
+            }
+        });
+        egui::ComboBox::from_label("Crossing order").selected_text(config.order.name()).show_ui(ui, |ui| {
+            for order in CrossOrder::ALL.iter().copied() {
+                ui.selectable_value(&mut config.order, order, order.name());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut config.max_cells));
+            ui.label("Max cells");
+            ui.add(egui::DragValue::new(&mut config.max_mines));
+            ui.label("Max mines");
+        });
+        ui.checkbox(&mut config.triple_crossing, "Cross three constraints when pairs stall");
+        ui.horizontal(|ui| {
+            let mut limited = config.budget.is_some();
+            ui.checkbox(&mut limited, "Step budget");
+            match (limited, config.budget.as_mut()) {
+                (true, Some(budget)) => {
+                    ui.add(egui::DragValue::new(budget));
+                }
+                (true, None) => config.budget = Some(10_000),
+                (false, _) => config.budget = None,
+            }
+        });
+
+        ui.separator();
+        ui.checkbox(&mut config.known_mine_count, "Total mine count is known");
+        ui.checkbox(&mut config.player_knowledge_only, "Only use numbers a player could see");
+        egui::ComboBox::from_label("Step size").selected_text(config.granularity.name()).show_ui(ui, |ui| {
+            for granularity in StepGranularity::ALL.iter().copied() {
+                ui.selectable_value(&mut config.granularity, granularity, granularity.name());
+            }
+        });
+        if let Some(engine) = shown {
+            ui.horizontal(|ui| {
+                if ui.button("Re-solve from start").on_hover_text("Solve the shown puzzle again with these settings").clicked() {
+                    engine.send(Command::Reconfigure(*config, true));
+                }
+                if ui.button("Continue with these settings").on_hover_text("Keep what's been found and carry on with these settings").clicked() {
+                    engine.send(Command::Reconfigure(*config, false));
+                }
+            });
+        }
+
+        ui.separator();
+        egui::ComboBox::from_label("Parsing").selected_text(parse_mode.name()).show_ui(ui, |ui| {
+            for mode in ParseMode::ALL.iter().copied() {
+                ui.selectable_value(parse_mode, mode, mode.name());
+            }
+        });
+    });
+}
+
+/// Every queued solve with how far it has got, and buttons to cancel the ones still going.
+fn dashboard_window(ctx: &egui::CtxRef, open: &mut bool, jobs: &[(String, JobStatus)], engine: &EngineHandle) {
+    egui::Window::new("Solve dashboard").open(open).show(ctx, |ui| {
+        let count = |done: fn(&JobStatus) -> bool| jobs.iter().filter(|(_, status)| done(status)).count();
+        let running = count(|status| matches!(status, JobStatus::Running(..)));
+        let waiting = count(|status| *status == JobStatus::Waiting);
+        let finished = count(|status| matches!(status, JobStatus::Finished(_)));
+        let failed = count(|status| matches!(status, JobStatus::Failed(_)));
+        ui.horizontal(|ui| {
+            ui.label(format!("{} running, {} waiting, {} finished, {} failed", running, waiting, finished, failed));
+            if ui.add(egui::Button::new("Cancel all").enabled(running + waiting > 0)).clicked() {
+                for (id, _) in jobs.iter().enumerate().filter(|(_, (_, status))| !status.is_done()) {
+                    engine.send(Command::CancelJob(id));
+                }
+            }
+        });
+
+        egui::ScrollArea::auto_sized().show(ui, |ui| {
+            egui::Grid::new("solve_dashboard").striped(true).show(ui, |ui| {
+                for (id, (name, status)) in jobs.iter().enumerate() {
+                    ui.label(name);
+                    match status {
+                        JobStatus::Waiting => ui.label("waiting"),
+                        JobStatus::Running(resolved, total) => {
+                            let progress = *resolved as f32 / (*total).max(1) as f32;
+                            ui.add(egui::ProgressBar::new(progress).desired_width(160.0).text(format!("{} of {} squares", resolved, total)))
                         }
+                        JobStatus::Finished(outcome) => ui.label(outcome.to_string()),
+                        JobStatus::Failed(why) => ui.colored_label(Color32::RED, "failed").on_hover_text(why),
+                        JobStatus::Cancelled => ui.label("cancelled"),
+                    };
+                    if ui.add(egui::Button::new("Cancel").enabled(!status.is_done())).clicked() {
+                        engine.send(Command::CancelJob(id));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    });
+}
+
+/// How the player is getting on against the solver, and once the board is `done` how their moves
+/// compare with its: in what order, how fast and which of them were guesses.
+fn race_window(ctx: &egui::CtxRef, race: &mut Option<Race>, base: &Puzzle, done: bool, engine: &EngineHandle) {
+    let mut open = true;
+    let mut end = false;
+    let current = race.as_ref().expect("Not racing!");
+    egui::Window::new("Race against the solver").open(&mut open).show(ctx, |ui| {
+        let seconds = |duration: Duration| format!("{:.1} s", duration.as_secs_f64());
+        let accepted = current.accepted().count();
+        let refused = current.player.len() - accepted;
+        let player = match (current.accepted().last(), current.per_move()) {
+            (Some(last), Some(per_move)) => format!("You: {} moves in {}, {} a move", accepted, seconds(last.at), seconds(per_move)),
+            _ => String::from("You: no moves yet.  Tap to reveal, hold or right click to flag"),
+        };
+        ui.label(if refused > 0 { format!("{}, {} refused", player, refused) } else { player });
+        match &current.solver {
+            Some(run) => {
+                let ending = if run.finished { "" } else { ", then got stuck" };
+                ui.label(format!("Solver: {} deductions in {} steps, {:.2} ms, {:.3} ms a deduction{}", run.deductions, run.steps, run.elapsed.as_secs_f64() * 1000.0, run.per_deduction().as_secs_f64() * 1000.0, ending));
+            }
+            None => {
+                ui.label("Solver: still working");
+            }
+        }
+        if !done {
+            ui.label("The comparison shows once every square is resolved.");
+        } else if let Some(run) = &current.solver {
+            let (agreeing, pairs) = current.in_order(run);
+            ui.label(format!("{} of {} moves came no earlier than the solver would make them", agreeing, pairs));
+            let guesses = current.unjustified().map(|played| base.id_labels[played.square].as_str()).collect::<Vec<_>>();
+            if guesses.is_empty() {
+                ui.label("Every move followed from what the board showed");
+            } else {
+                ui.colored_label(BROKEN, format!("Not yet proven when played: {}", guesses.join(", ")));
+            }
+
+            egui::ScrollArea::from_max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("race_moves").striped(true).show(ui, |ui| {
+                    ui.label("Move");
+                    ui.label("You");
+                    ui.label("Time");
+                    ui.label("Solver");
+                    ui.end_row();
+                    let mut previous = Duration::ZERO;
+                    for (number, played) in current.player.iter().enumerate() {
+                        let verb = match played.action {
+                            Action::Reveal => "reveal",
+                            Action::Flag => "flag",
+                        };
+                        ui.label((number + 1).to_string());
+                        ui.label(format!("{} {}", verb, base.id_labels[played.square]));
+                        ui.label(format!("+{}", seconds(played.at - previous)));
+                        let solver_step = run.step_of(played.square).map_or_else(|| String::from("never"), |step| format!("step {}", step));
+                        match (played.correct, played.justified) {
+                            (false, _) => ui.colored_label(Color32::RED, "wrong, refused"),
+                            (true, false) => ui.colored_label(BROKEN, format!("{}, a guess", solver_step)),
+                            (true, true) => ui.label(solver_step),
+                        };
+                        ui.end_row();
+                        previous = played.at;
+                    }
+                });
+            });
+        }
+        if ui.button("End race").clicked() {
+            end = true;
+        }
+    });
+    if end || !open {
+        *race = None;
+        engine.send(Command::Race(false));
+    }
+}
+
+/// Which queued puzzles the puzzle list is limited to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LibraryFilter {
+    /// Difficulty from `low` up to but not including `high`.
+    Difficulty { low: f64, high: f64 },
+    /// Solves that made a deduction with the technique.
+    Technique(Technique),
+}
+
+impl LibraryFilter {
+    fn matches(self, report: &SolveReport) -> bool {
+        match self {
+            LibraryFilter::Difficulty { low, high } => low <= report.difficulty && report.difficulty < high,
+            LibraryFilter::Technique(technique) => report.stats.techniques.get(technique) > 0,
+        }
+    }
+
+    fn describe(self) -> String {
+        match self {
+            LibraryFilter::Difficulty { low, high } => format!("with difficulty {:.0} to {:.0}", low, high),
+            LibraryFilter::Technique(technique) => format!("that need {}", technique),
+        }
+    }
+}
+
+/// How many bars the difficulty histogram has.
+const DIFFICULTY_BINS: usize = 10;
+
+/// Charts of the queued puzzles: how difficulty is spread, how steps grow with size and how often
+/// each technique is used.  Clicking a bar limits the puzzle list to the puzzles in it.
+fn statistics_window(ctx: &egui::CtxRef, open: &mut bool, results: &[(PuzzleListing, SolveReport)], filter: &mut Option<LibraryFilter>) {
+    egui::Window::new("Statistics").open(open).show(ctx, |ui| {
+        ui.label(format!("{} queued puzzles", results.len()));
+
+        ui.heading("Difficulty");
+        let hardest = results.iter().map(|(_, report)| report.difficulty).fold(0.0, f64::max);
+        let width = (hardest / DIFFICULTY_BINS as f64).ceil().max(1.0);
+        let bins = (0..DIFFICULTY_BINS).map(|bin| LibraryFilter::Difficulty { low: bin as f64 * width, high: (bin + 1) as f64 * width }).collect::<Vec<_>>();
+        let bars = bins.iter().map(|&bin| {
+            let label = match bin {
+                LibraryFilter::Difficulty { low, .. } => format!("{:.0}", low),
+                _ => unreachable!(),
+            };
+            (label, results.iter().filter(|(_, report)| bin.matches(report)).count(), *filter == Some(bin))
+        }).collect::<Vec<_>>();
+        if let Some(bar) = bar_chart(ui, "difficulty_histogram", &bars) {
+            *filter = Some(bins[bar]);
+        }
+
+        ui.heading("Squares and steps");
+        let points = Values::from_values(results.iter().map(|(_, report)| Value::new(report.stats.cells_total as f64, report.stats.steps as f64)).collect());
+        ui.add(Plot::new("size_scatter").points(Points::new(points).radius(3.0).name("Puzzle")).include_x(0.0).include_y(0.0).height(160.0).allow_drag(false).allow_zoom(false));
+
+        ui.heading("Techniques");
+        let bars = Technique::ALL.iter().map(|&technique| {
+            let uses = results.iter().map(|(_, report)| report.stats.techniques.get(technique)).sum();
+            (technique.name().to_string(), uses, *filter == Some(LibraryFilter::Technique(technique)))
+        }).collect::<Vec<_>>();
+        if let Some(bar) = bar_chart(ui, "technique_frequency", &bars) {
+            *filter = Some(LibraryFilter::Technique(Technique::ALL[bar]));
+        }
+    });
+}
+
+/// The solves in the history whose puzzle's title has `filter` in it, with how their difficulty went.
+fn history_window(ctx: &egui::CtxRef, history: &mut Option<Vec<HistoryEntry>>, filter: &mut String) {
+    let mut open = history.is_some();
+    if let Some(entries) = history.as_ref() {
+        egui::Window::new("Solve history").open(&mut open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Title:");
+                ui.text_edit_singleline(filter);
+            });
+            let needle = filter.to_lowercase();
+            let shown = entries.iter().filter(|entry| entry.report.title.to_lowercase().contains(&needle)).collect::<Vec<_>>();
+            ui.label(format!("{} of {} solves", shown.len(), entries.len()));
+
+            let points = Values::from_values(shown.iter().enumerate().map(|(i, entry)| Value::new(i as f64, entry.report.difficulty)).collect());
+            ui.add(Plot::new("history_difficulty").line(Line::new(points).name("Difficulty")).include_y(0.0).height(120.0).allow_drag(false).allow_zoom(false));
+
+            egui::ScrollArea::auto_sized().show(ui, |ui| {
+                egui::Grid::new("solve_history").striped(true).show(ui, |ui| {
+                    for entry in shown.iter().rev() {
+                        ui.label(entry.date());
+                        ui.label(&entry.report.title).on_hover_text(&entry.puzzle);
+                        ui.label(entry.config.tier.name()).on_hover_text(format!("At most {} cells and {} mines crossed", entry.config.max_cells, entry.config.max_mines));
+                        ui.label(entry.report.outcome.to_string());
+                        ui.label(format!("{} steps", entry.report.stats.steps));
+                        ui.label(format!("difficulty {:.1}", entry.report.difficulty));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    }
+    if !open {
+        *history = None;
+    }
+}
+
+/// Draws a bar for each `(label, value, selected)`, labelled underneath and with the value shown on
+/// hover.  Returns the bar clicked, if any.
+fn bar_chart(ui: &mut egui::Ui, id_source: &str, bars: &[(String, usize, bool)]) -> Option<usize> {
+    let label_height = ui.fonts().row_height(TextStyle::Small);
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width().max(240.0), 120.0 + label_height), Sense::hover());
+    let tallest = bars.iter().map(|bar| bar.1).max().unwrap_or(0).max(1);
+    let slot = rect.width() / bars.len().max(1) as f32;
+    let mut clicked = None;
+    for (i, (label, value, selected)) in bars.iter().enumerate() {
+        let left = rect.left() + slot * i as f32;
+        let column = Rect::from_min_max(Pos2::new(left, rect.top()), Pos2::new(left + slot, rect.bottom() - label_height));
+        let response = ui.interact(column, ui.id().with(id_source).with(i), Sense::click()).on_hover_text(format!("{}: {}", label, value));
+        let height = column.height() * *value as f32 / tallest as f32;
+        let bar = Rect::from_min_max(Pos2::new(column.left() + 2.0, column.bottom() - height), Pos2::new(column.right() - 2.0, column.bottom()));
+        let color = if *selected { SELECTED } else if response.hovered() { Color32::LIGHT_BLUE } else { Color32::from_rgb(80, 120, 200) };
+        ui.painter().rect_filled(bar, 1.0, color);
+        ui.painter().text(Pos2::new(column.center().x, rect.bottom()), Align2::CENTER_BOTTOM, label, TextStyle::Small, ui.visuals().text_color());
+        if response.clicked() {
+            clicked = Some(i);
+        }
+    }
+    clicked
+}
+
+/// Lists the puzzles at `entries`, which all share their first `depth` sections, with a collapsible
+/// header for each deeper section.  Returns the puzzle clicked on, if any.
+fn puzzle_tree(ui: &mut egui::Ui, listing: &[PuzzleListing], badges: &[Option<Badge>], selection: &BTreeSet<usize>, entries: &[usize], depth: usize) -> Option<usize> {
+    let mut clicked = None;
+    let mut sections: Vec<(&str, Vec<usize>)> = Vec::new();
+    for &index in entries {
+        match listing[index].sections().get(depth) {
+            // Entries are sorted by section, so each section's puzzles are together
+            Some(&section) => match sections.last_mut() {
+                Some((last, members)) if *last == section => members.push(index),
+                _ => sections.push((section, vec![index])),
+            },
+            None => {
+                ui.horizontal(|ui| {
+                    // Loading an unreadable puzzle would take down the engine
+                    let badge = badges[index];
+                    let mut button = egui::Button::new(&listing[index].name).enabled(badge != Some(Badge::Unreadable));
+                    if selection.contains(&index) {
+                        button = button.fill(SELECTED.linear_multiply(0.4));
+                    }
+                    if ui.add(button).clicked() {
+                        clicked = Some(index);
                     }
+                    match badge {
+                        Some(badge) => ui.colored_label(badge_color(badge), badge.name()),
+                        None => ui.colored_label(Color32::from_gray(100), "checking..."),
+                    };
                 });
+            }
+        }
+    }
+
+    for (section, members) in sections {
+        let solved = members.iter().filter(|&&index| badges[index] == Some(Badge::Solved)).count();
+        let id = listing[members[0]].sections()[..=depth].join("/");
+        egui::CollapsingHeader::new(format!("{} ({}/{} solved)", section, solved, members.len())).id_source(id).show(ui, |ui| {
+            if let Some(index) = puzzle_tree(ui, listing, badges, selection, &members, depth + 1) {
+                clicked = Some(index);
+            }
+        });
+    }
+
+    clicked
+}
+
+/// Asks how to go on with a run paused for building up too many constraints.
+fn warning_window(ctx: &egui::CtxRef, warning: &mut Option<String>, unsolved: usize, engine: &EngineHandle) {
+    let Some(message) = warning.as_ref() else {
+        return;
+    };
+
+    let mut choice = None;
+    egui::Window::new("Solver paused").collapsible(false).show(ctx, |ui| {
+        ui.label(message.as_str());
+        ui.horizontal(|ui| {
+            if ui.button("Continue").on_hover_text("Carry on without asking again").clicked() {
+                choice = Some(Command::Resume(None));
+            }
+            let again = unsolved.saturating_mul(2).max(1);
+            if ui.button(format!("Continue, and ask again at {}", again)).clicked() {
+                choice = Some(Command::Resume(Some(again)));
+            }
+            if ui.button("Prove the rest by search").on_hover_text("Try every layout of the open squares instead of crossing constraints").clicked() {
+                choice = Some(Command::ProveRest);
+            }
+            if ui.button("Stay paused").clicked() {
+                choice = Some(Command::Stop);
+            }
+        });
+    });
+    if let Some(command) = choice {
+        engine.send(command);
+        *warning = None;
+    }
+}
+
+/// Says why the engine stopped, if it has.  Returns whether it should be restarted.
+fn crash_window(ctx: &egui::CtxRef, crashed: Option<&str>) -> bool {
+    let Some(reason) = crashed else {
+        return false;
+    };
+
+    let mut restart = false;
+    egui::Window::new("Engine stopped").collapsible(false).show(ctx, |ui| {
+        ui.label(format!("The engine crashed: {}", reason));
+        ui.label("Nothing will be loaded, solved or analysed until it's restarted.  The shown puzzle and queued solves will be lost.");
+        restart = ui.button("Restart the engine").clicked();
+    });
+    restart
+}
+
+/// A strip along the bottom listing engine errors, once there are any.
+fn error_console(ctx: &egui::CtxRef, errors: &mut Vec<(String, String)>) {
+    if errors.is_empty() {
+        return;
+    }
+
+    egui::TopBottomPanel::bottom("error_console").show(ctx, |ui| {
+        egui::CollapsingHeader::new(format!("Errors ({})", errors.len())).default_open(true).show(ui, |ui| {
+            if ui.button("Clear").clicked() {
+                errors.clear();
+            }
+            egui::ScrollArea::from_max_height(120.0).show(ui, |ui| {
+                for (time, message) in errors.iter().rev() {
+                    ui.colored_label(Color32::RED, format!("[{}] {}", time, message));
+                }
+            });
+        });
+    });
+}
+
+/// Shows each notification in the bottom right corner for a few seconds.
+fn show_notifications(ctx: &egui::CtxRef, notifications: &mut Vec<(String, Option<f64>)>) {
+    let now = ctx.input().time;
+    notifications.retain(|(_, until)| until.is_none_or(|until| until > now));
+    if notifications.is_empty() {
+        return;
+    }
+
+    egui::Area::new("notifications").anchor(Align2::RIGHT_BOTTOM, Vec2::new(-10.0, -10.0)).show(ctx, |ui| {
+        for (message, until) in notifications.iter_mut() {
+            until.get_or_insert(now + 5.0);
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(message.as_str());
+            });
+        }
+    });
+    // Keep repainting so notifications disappear on time
+    ctx.request_repaint();
+}
+
+fn badge_color(badge: Badge) -> Color32 {
+    match badge {
+        Badge::Solved => Color32::GREEN,
+        Badge::BeyondSolver => Color32::LIGHT_BLUE,
+        Badge::NeedsGuessing => Color32::YELLOW,
+        Badge::Stuck => Color32::GRAY,
+        Badge::Unreadable => Color32::RED,
+    }
+}
+
+/// The title strip above the board.
+fn puzzle_header(ui: &mut egui::Ui, display: &PuzzleDisplay) {
+    let base = &display.starting_state.base;
+    ui.horizontal(|ui| {
+        ui.heading(&display.listing.name);
+        if let Some(author) = &display.listing.author {
+            ui.label(format!("by {}", author));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label(display.listing.file_name());
+        ui.separator();
+        ui.label(format!("{} squares, {} mines, {} hints", base.size(), base.mines.count_ones(), base.hints.len()));
+        if let Some(report) = &display.report {
+            ui.separator();
+            ui.label(format!("Difficulty {:.1}", report.difficulty));
+        }
+        let diagnostics = &display.gui.diagnostics;
+        if !diagnostics.is_empty() {
+            ui.separator();
+            ui.colored_label(BROKEN, format!("{} problems in the file", diagnostics.len())).on_hover_ui(|ui| {
+                for diagnostic in diagnostics {
+                    ui.label(diagnostic.to_string());
+                }
             });
         }
+    });
+}
+
+/// The explanation shown for a step, naming squares by their IDs in the puzzle file.
+pub fn describe_step(result: &StepResult, puzzle: &Puzzle) -> String {
+    match result {
+        StepResult::CrossConstraint(c) => format!("Crossing constraint on {}.  Min: {} Max: {}", puzzle.labels(c.bits), c.min_mines, c.max_mines),
+        StepResult::Progress{revealed, flagged, ..} => {
+            if revealed.any() {
+                if flagged.any() {
+                    format!("Found {} to be revealed ({}) and {} to be flagged ({})", format_text(revealed.count_ones()), puzzle.labels(*revealed), format_text(flagged.count_ones()), puzzle.labels(*flagged))
+                } else {
+                    format!("Found {} to be revealed: {}", format_text(revealed.count_ones()), puzzle.labels(*revealed))
+                }
+            } else {
+                format!("Found {} to be flagged: {}", format_text(flagged.count_ones()), puzzle.labels(*flagged))
+            }
+        }
+        StepResult::Finished => String::from("Finished!"),
+        StepResult::Stuck => String::from("Stuck! Nothing more can be deduced"),
+        StepResult::UnexpectedStop(why) => format!("Unexpected stop! Reason: {}", why),
+        StepResult::Contradiction(constraints) => format!("Contradiction! These {} constraints can't all hold:\n{}", constraints.len(), constraints.iter().map(|c| format!("{} ({}->{}/{}) on {}", c.technique, c.min_mines, c.max_mines, c.size, puzzle.labels(c.bits))).collect::<Vec<_>>().join("\n")),
+        StepResult::CliqueConstraint(c) => format!("Found maximal clique!  Adding remaining squares to constraint on {}", puzzle.labels(c.bits)),
+    }
+}
+
+/// Which of the puzzle's color hints `square` is in, counting only color hints, if any.
+fn color_group(gui: &PuzzleGui, base: &Puzzle, square: usize) -> Option<usize> {
+    gui.color_hints.iter().position(|&hint| base.hints.get(hint).is_some_and(|bits| bits[square]))
+}
+
+/// Draws the symbol that tells color group `group` apart without its color: a circle, triangle,
+/// square, diamond, plus or cross.
+fn group_symbol(painter: &egui::Painter, center: Pos2, radius: f32, group: usize, color: Color32) {
+    let stroke = Stroke::new((radius / 4.0).max(1.0), color);
+    let polygon = |corners: usize, turn: f32| {
+        let points = (0..corners).map(|i| {
+            let angle = turn + i as f32 * std::f32::consts::TAU / corners as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        }).collect();
+        Shape::Path { points, closed: true, fill: Color32::TRANSPARENT, stroke }
+    };
+    let cross = |turn: f32| {
+        for i in 0..2 {
+            let angle = turn + i as f32 * std::f32::consts::FRAC_PI_2;
+            let arm = Vec2::new(angle.cos(), angle.sin()) * radius;
+            painter.line_segment([center - arm, center + arm], stroke);
+        }
+    };
+    match group % GROUP_COLORS.len() {
+        0 => {
+            painter.circle_stroke(center, radius, stroke);
+        }
+        1 => {
+            painter.add(polygon(3, -std::f32::consts::FRAC_PI_2));
+        }
+        2 => {
+            painter.add(polygon(4, std::f32::consts::FRAC_PI_4));
+        }
+        3 => {
+            painter.add(polygon(4, 0.0));
+        }
+        4 => cross(0.0),
+        _ => cross(std::f32::consts::FRAC_PI_4),
+    }
+}
+
+/// A key to the color groups in the corner of the board, with the mines left in each as the game
+/// shows them.
+fn color_legend(painter: &egui::Painter, corner: Pos2, gui: &PuzzleGui, state: &PuzzleState) {
+    let base = &state.base;
+    let row_height = painter.fonts().row_height(TextStyle::Body) + 4.0;
+    let rows = gui.color_hints.iter().filter_map(|&hint| base.hints.get(hint)).enumerate().map(|(group, &bits)| {
+        let mines = base.displayed_count(bits) - (bits & state.flagged).count_ones();
+        (group, format!("{} mines left", mines))
+    }).collect::<Vec<_>>();
+    let width = rows.iter().map(|(_, text)| painter.layout_no_wrap(TextStyle::Body, text.clone()).size.x).fold(0.0, f32::max) + row_height + 12.0;
+    painter.rect_filled(Rect::from_min_size(corner, Vec2::new(width, row_height * rows.len() as f32 + 4.0)), 3.0, Color32::from_black_alpha(200));
+    for (row, (group, text)) in rows.into_iter().enumerate() {
+        let top = corner + Vec2::new(4.0, 2.0 + row as f32 * row_height);
+        let swatch = Rect::from_min_size(top, Vec2::splat(row_height - 4.0));
+        painter.rect_filled(swatch, 2.0, GROUP_COLORS[group % GROUP_COLORS.len()]);
+        group_symbol(painter, swatch.center(), swatch.width() * 0.35, group, Color32::WHITE);
+        painter.text(Pos2::new(swatch.right() + 6.0, swatch.center().y), Align2::LEFT_CENTER, text, TextStyle::Body, Color32::WHITE);
+    }
+}
+
+/// The square among `candidates` in `direction` from `from` on the board, preferring the closest
+/// and most nearly straight that way.  Falls back to any square if no candidate lies that way.
+fn square_toward(gui: &PuzzleGui, candidates: Bits, from: usize, direction: Vec2) -> Option<usize> {
+    let origin = Pos2::new(gui.squares[from].x, gui.squares[from].y);
+    let best = |squares: &mut dyn Iterator<Item = usize>| squares.filter(|&square| square != from).filter_map(|square| {
+        let offset = Pos2::new(gui.squares[square].x, gui.squares[square].y) - origin;
+        let unit = offset.normalized();
+        let alignment = unit.x * direction.x + unit.y * direction.y;
+        // Within about 60 degrees of the arrow
+        (alignment > 0.5).then(|| (square, offset.length() / alignment))
+    }).min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal)).map(|(square, _)| square);
+    best(&mut candidates.iter_ones()).or_else(|| best(&mut (0..gui.squares.len())))
+}
+
+/// A square as a screen reader or tooltip puts it: its ID and what is known about it.
+fn describe_square(state: &PuzzleState, square: usize) -> String {
+    let base = &state.base;
+    let status = if state.flagged[square] {
+        String::from("flagged as a mine")
+    } else if !state.revealed[square] {
+        String::from("unresolved")
+    } else if base.unknowns[square] {
+        String::from("revealed, number hidden")
+    } else {
+        format!("revealed, {} mines left around it", square_appearance(state, square).1)
+    };
+    let open = (base.neighbors[square] & !state.revealed & !state.flagged).count_ones();
+    format!("{}: {}, {} of {} neighbors unresolved", base.id_labels[square], status, open, base.neighbors[square].count_ones())
+}
+
+/// The square colors `square_appearance` gives, made as far apart as they can be.
+fn high_contrast(color: Color32) -> Color32 {
+    match color {
+        Color32::GRAY => HIGH_CONTRAST_REVEALED,
+        Color32::RED => Color32::from_rgb(255, 0, 0),
+        Color32::BLUE => Color32::from_rgb(0, 0, 160),
+        other => other,
+    }
+}
+
+/// The squares a step is about, which are drawn brighter than the rest.
+pub fn highlighted_squares(result: &StepResult) -> Bits {
+    match result {
+        StepResult::CrossConstraint(constraint) | StepResult::CliqueConstraint(constraint) => constraint.bits,
+        StepResult::Progress{revealed, flagged, ..} => *revealed | *flagged,
+        StepResult::Contradiction(constraints) => constraints.iter().fold(Bits::zeroed(), |bits, c| bits | c.bits),
+        _ => Bits::zeroed(),
+    }
+}
+
+/// The color of a square and the text drawn on it.
+pub fn square_appearance(state: &PuzzleState, square: usize) -> (Color32, String) {
+    if state.revealed[square] {
+        (Color32::GRAY, if state.base.unknowns[square] {
+            String::from("?")
+        } else {
+            (state.base.neighbors[square] & state.base.mines & !state.flagged).count_ones().to_string()
+        })
+    } else if state.flagged[square] {
+        (Color32::RED, String::new())
+    } else {
+        (Color32::BLUE, String::new())
+    }
+}
+
+/// Everything known about one square, for working out why the solver treats it the way it does.
+/// With `player_view`, what an unresolved square holds is left out.
+fn cell_inspector(ui: &mut egui::Ui, display: &PuzzleDisplay, state: &PuzzleState, square: usize, player_view: bool) {
+    let base = &state.base;
+    ui.label(format!("Square {} (ID {})", square, base.id_labels[square]));
+    ui.label(format!("Neighbors: {}", base.labels(base.neighbors[square])));
+    for diagnostic in display.gui.diagnostics.iter().filter(|diagnostic| diagnostic.node == base.id_labels[square]) {
+        ui.colored_label(BROKEN, &diagnostic.problem);
+    }
+
+    let resolved = state.revealed[square] || state.flagged[square];
+    let contents = match (base.mines[square], base.unknowns[square]) {
+        _ if player_view && !resolved => "hidden",
+        (true, _) => "mine",
+        (false, true) => "secret",
+        (false, false) => "safe",
+    };
+    let status = if state.revealed[square] {
+        "revealed"
+    } else if state.flagged[square] {
+        "flagged"
+    } else {
+        "unresolved"
+    };
+    ui.label(format!("Contents: {}, currently {}", contents, status));
+
+    let resolved_at = display.steps.iter().position(|(s, _)| s.revealed[square] || s.flagged[square]);
+    ui.label(match resolved_at {
+        _ if display.starting_state.revealed[square] => String::from("Revealed from the start"),
+        Some(step) => format!("Resolved at step {}", step + 1),
+        None => String::from("Not resolved yet"),
+    });
+
+    ui.label(format!("Constraints after the latest step: {}", display.constraints.len()));
+    for c in &display.constraints {
+        ui.label(format!("{} ({}->{}/{}), depth {}", c.technique, c.min_mines, c.max_mines, c.size, c.depth));
+    }
+}
+
+/// The engine's step rate and the memory its solver holds for the shown puzzle, with how many
+/// updates piled up between frames, in the top right corner.
+fn resource_overlay(ctx: &egui::CtxRef, resources: Option<&EngineResources>, backlog: usize) {
+    egui::Area::new("resources").anchor(Align2::RIGHT_TOP, Vec2::new(-10.0, 40.0)).show(ctx, |ui| {
+        egui::Frame::popup(ui.style()).show(ui, |ui| {
+            let kilobytes = |bytes: usize| format!("{:.1} KB", bytes as f64 / 1024.0);
+            match resources {
+                Some(resources) => {
+                    let memory = &resources.memory;
+                    ui.label(format!("Steps per second: {:.1}", resources.steps_per_second));
+                    ui.label(format!("Solver memory: {}", kilobytes(memory.total())));
+                    ui.label(format!("    constraints {}, index {}, queues {}, other {}", kilobytes(memory.constraints), kilobytes(memory.index), kilobytes(memory.queues), kilobytes(memory.other)));
+                }
+                None => {
+                    ui.label("No steps taken yet");
+                }
+            }
+            ui.label(format!("Updates waiting last frame: {}", backlog));
+        });
+    });
+}
+
+/// Charts the solver's unsolved constraints, solved constraints waiting to be applied, unresolved
+/// squares, difficulty so far and how long each step took against the step, with a line at the step
+/// being viewed.
+fn timeline(ui: &mut egui::Ui, points: &[TimelinePoint], step: usize) {
+    let series = |value: fn(&TimelinePoint) -> f64| Values::from_values(points.iter().map(|point| Value::new(point.step as f64, value(point))).collect());
+    let plot = Plot::new("timeline")
+        .line(Line::new(series(|point| point.unsolved as f64)).name("Unsolved constraints"))
+        .line(Line::new(series(|point| point.solved as f64)).name("Solved, waiting"))
+        .line(Line::new(series(|point| point.remaining as f64)).name("Squares remaining"))
+        .line(Line::new(series(|point| point.difficulty)).name("Difficulty"))
+        .line(Line::new(series(|point| point.milliseconds)).name("Step time (ms)"))
+        .vline(VLine::new(step as f64).color(SELECTED))
+        .include_y(0.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false);
+    ui.add(plot);
+}
+
+/// The area the board's squares cover, outlines included, in puzzle coordinates.
+fn board_extent(gui: &PuzzleGui) -> Rect {
+    let extent = gui.squares.iter().flat_map(|object| object.points.iter().map(move |a| Pos2::new(object.x + a.0, object.y + a.1))).fold(Rect::NOTHING, |extent, p| extent.union(Rect::from_min_max(p, p)));
+    if extent.width() > 0.0 && extent.height() > 0.0 { extent } else { Rect::from_center_size(extent.center(), Vec2::splat(1.0)) }
+}
+
+/// The largest rect with the proportions of `size` that fits in the middle of `available`.
+fn letterbox(available: Rect, size: Vec2) -> Rect {
+    let scale = (available.width()/size.x).min(available.height()/size.y).max(0.0);
+    Rect::from_center_size(available.center(), size*scale)
+}
+
+/// Draws the board's squares in their resolved colors, scaled to fit `rect`.
+fn draw_miniature(painter: &egui::Painter, rect: Rect, gui: &PuzzleGui, state: &PuzzleState) {
+    let bounds = board_extent(gui);
+    let rect = letterbox(rect, bounds.size());
+    let scale = rect.width()/bounds.width();
+    for (i, object) in gui.squares.iter().enumerate() {
+        let color = if state.revealed[i] {
+            Color32::GRAY
+        } else if state.flagged[i] {
+            Color32::RED
+        } else {
+            Color32::BLUE
+        };
+        painter.add(Shape::Path {
+            points: object.points.iter().map(|a| rect.min + (Pos2::new(object.x + a.0, object.y + a.1) - bounds.min)*scale).collect(),
+            closed: true,
+            fill: color,
+            stroke: Stroke::none(),
+        });
+    }
+}
+
+/// A strip under the step slider with a tick for every step that revealed or flagged squares.  Hovering
+/// near a tick previews the board after it, and clicking jumps there.  Returns the step to jump to.
+fn step_scrubber(ui: &mut egui::Ui, display: &PuzzleDisplay, width: f32) -> Option<usize> {
+    let (response, painter) = ui.allocate_painter(Vec2::new(width, 14.0), Sense::click());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, Color32::from_gray(40));
+    if display.steps.is_empty() {
+        return None;
+    }
+
+    // Steps are numbered from 1, since 0 is the starting state
+    let x_of = |step: usize| rect.left() + rect.width() * step as f32 / display.steps.len() as f32;
+    let landmarks = display.steps.iter().enumerate().filter_map(|(i, (_, result))| match result {
+        StepResult::Progress{revealed, flagged, ..} => Some((i + 1, revealed.count_ones(), flagged.count_ones())),
+        _ => None,
+    }).collect::<Vec<_>>();
+
+    for &(step, revealed, flagged) in &landmarks {
+        let color = if flagged > revealed { Color32::RED } else { Color32::LIGHT_GRAY };
+        // Taller ticks for steps that resolved more squares
+        let height = rect.height() * (0.4 + 0.6 * ((revealed + flagged) as f32 / 8.0).min(1.0));
+        let x = x_of(step);
+        painter.line_segment([Pos2::new(x, rect.bottom()), Pos2::new(x, rect.bottom() - height)], Stroke::new(1.0, color));
+    }
+
+    let pointer = response.hover_pos()?;
+    let (nearest, revealed, flagged) = *landmarks.iter().min_by(|a, b| (x_of(a.0) - pointer.x).abs().partial_cmp(&(x_of(b.0) - pointer.x).abs()).unwrap())?;
+    let clicked = response.clicked();
+    response.on_hover_ui(|ui| {
+        ui.label(format!("Step {}: {} revealed, {} flagged", nearest, revealed, flagged));
+        let (_, preview) = ui.allocate_painter(Vec2::splat(120.0), Sense::hover());
+        draw_miniature(&preview, preview.clip_rect(), &display.gui, &display.steps[nearest - 1].0);
+    });
+
+    if clicked {
+        Some(nearest)
+    } else {
+        None
+    }
+}
+
+/// A rectangle of `size` as close to being centered on `anchor` as it can be without covering any
+/// of `placed`.  Tries rings of positions further and further out, giving up after a few.
+fn place_label(anchor: Pos2, size: Vec2, placed: &[Rect]) -> Rect {
+    let first = Rect::from_center_size(anchor, size);
+    for ring in 0..6 {
+        let distance = ring as f32 * size.y;
+        let directions = if ring == 0 { 1 } else { 8 };
+        for direction in 0..directions {
+            let angle = direction as f32 * std::f32::consts::TAU / 8.0;
+            let candidate = first.translate(Vec2::angled(angle) * distance);
+            if !placed.iter().any(|other| other.intersects(candidate)) {
+                return candidate;
+            }
+        }
+    }
+    first
+}
+
+/// Whether `point` is inside the polygon with corners `points`, by counting edge crossings.
+fn polygon_contains(points: &[Pos2], point: Pos2) -> bool {
+    let mut inside = false;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
     }
+    inside
 }
 
 fn format_text(count: usize) -> String {
@@ -0,0 +1,95 @@
+use crate::core::PuzzleGui;
+
+/// Where each square goes in a text drawing of the board.  Squares are binned into columns and
+/// rows by their position, so a square grid draws as a grid and a hex grid as staggered rows.
+#[derive(Clone, Default)]
+pub struct Layout {
+    /// The column and row of each square.  Empty if the squares couldn't be told apart, in which
+    /// case the board is drawn as a single row.
+    cells: Vec<(usize, usize)>,
+    width: usize,
+    height: usize,
+}
+
+impl Layout {
+    pub fn new(gui: &PuzzleGui) -> Layout {
+        // Squares closer than a quarter of a square along an axis share a column or row, so the half
+        // square steps between hex columns still make separate rows
+        let extent = |axis: fn(&(f32, f32)) -> f32| gui.squares.iter().map(|square| {
+            let (low, high) = square.points.iter().map(axis).fold((f32::MAX, f32::MIN), |(low, high), p| (low.min(p), high.max(p)));
+            high - low
+        }).fold(f32::MAX, f32::min);
+        let columns = bins(gui.squares.iter().map(|square| square.x).collect(), extent(|p| p.0) / 4.0);
+        let rows = bins(gui.squares.iter().map(|square| square.y).collect(), extent(|p| p.1) / 4.0);
+
+        let cells = columns.into_iter().zip(rows).collect::<Vec<_>>();
+        let mut taken = cells.clone();
+        taken.sort_unstable();
+        taken.dedup();
+        if taken.len() != cells.len() {
+            return Layout::default();
+        }
+        let width = cells.iter().map(|cell| cell.0 + 1).max().unwrap_or(0);
+        let height = cells.iter().map(|cell| cell.1 + 1).max().unwrap_or(0);
+        Layout { cells, width, height }
+    }
+
+    /// The layout of only `squares`, in that order, moved up and left to where the first of them
+    /// starts.
+    pub fn extract(&self, squares: &[usize]) -> Layout {
+        let Some(cells) = squares.iter().map(|&square| self.cells.get(square).copied()).collect::<Option<Vec<_>>>().filter(|cells| !cells.is_empty()) else {
+            return Layout::default();
+        };
+        let left = cells.iter().map(|cell| cell.0).min().unwrap_or(0);
+        let top = cells.iter().map(|cell| cell.1).min().unwrap_or(0);
+        let cells = cells.into_iter().map(|(column, row)| (column - left, row - top)).collect::<Vec<_>>();
+        let width = cells.iter().map(|cell| cell.0 + 1).max().unwrap_or(0);
+        let height = cells.iter().map(|cell| cell.1 + 1).max().unwrap_or(0);
+        Layout { cells, width, height }
+    }
+
+    /// This layout of `size` squares followed by `other`'s, moved right and down by `offset`.  If
+    /// any squares land on each other, the board is drawn as a single row.
+    pub fn compose(&self, size: usize, other: &Layout, other_size: usize, offset: (usize, usize)) -> Layout {
+        if self.cells.len() != size || other.cells.len() != other_size {
+            return Layout::default();
+        }
+        let cells = self.cells.iter().copied().chain(other.cells.iter().map(|&(column, row)| (column + offset.0, row + offset.1))).collect::<Vec<_>>();
+        let mut taken = cells.clone();
+        taken.sort_unstable();
+        taken.dedup();
+        if taken.len() != cells.len() {
+            return Layout::default();
+        }
+        let width = cells.iter().map(|cell| cell.0 + 1).max().unwrap_or(0);
+        let height = cells.iter().map(|cell| cell.1 + 1).max().unwrap_or(0);
+        Layout { cells, width, height }
+    }
+
+    /// Draws `size` squares with the character `glyph` gives each, one line per row.
+    pub fn draw(&self, size: usize, glyph: impl Fn(usize) -> char) -> String {
+        if self.cells.len() != size {
+            return (0..size).map(glyph).collect();
+        }
+        let mut grid = vec![vec![' '; self.width]; self.height];
+        for (square, &(column, row)) in self.cells.iter().enumerate() {
+            grid[row][column] = glyph(square);
+        }
+        grid.into_iter().map(|row| row.into_iter().collect::<String>().trim_end().to_string()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Numbers positions along one axis by bin, starting a new bin wherever the gap to the previous
+/// position is more than `tolerance`.
+fn bins(positions: Vec<f32>, tolerance: f32) -> Vec<usize> {
+    let tolerance = if tolerance.is_finite() && tolerance > 0.0 { tolerance } else { 0.001 };
+    let mut sorted = positions.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut starts = Vec::new();
+    for pair in sorted.windows(2) {
+        if pair[1] - pair[0] > tolerance {
+            starts.push(pair[1]);
+        }
+    }
+    positions.iter().map(|&position| starts.iter().filter(|&&start| start <= position).count()).collect()
+}
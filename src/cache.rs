@@ -0,0 +1,132 @@
+use std::{collections::HashMap, fs, hash::{Hash, Hasher}, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{core::Fingerprint, solver::SolverConfig};
+
+/// Bumped whenever the solver's results change, so that results saved by an older solver aren't
+/// used.  Saved results are also tied to the version of the program.
+const CACHE_VERSION: u32 = 1;
+
+/// Results worked out from a puzzle, such as completed solve reports, saved in the local data
+/// directory.  Keyed by the puzzle's contents and whatever else the result depends on.
+pub struct SolveCache {
+    dir: PathBuf,
+}
+
+impl SolveCache {
+    /// The cache in the user's data directory, or `None` if there isn't one.
+    pub fn open() -> Option<SolveCache> {
+        let mut dir = dirs::data_local_dir()?;
+        dir.push("tametsi");
+        dir.push("solves");
+        fs::create_dir_all(&dir).ok()?;
+        Some(SolveCache { dir })
+    }
+
+    pub fn key(contents: &[u8], config: &SolverConfig, guesses: bool) -> String {
+        let mut hasher = Fingerprint::default();
+        CACHE_VERSION.hash(&mut hasher);
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        contents.hash(&mut hasher);
+        config.hash(&mut hasher);
+        guesses.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// The key for a puzzle's badge, which is always worked out with the default settings.
+    pub fn badge_key(contents: &[u8]) -> String {
+        format!("badge-{}", SolveCache::key(contents, &SolverConfig::default(), false))
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let contents = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Saves a result.  Failing to write it only costs working it out again later, so errors are ignored.
+    pub fn put<T: Serialize>(&self, key: &str, value: &T) {
+        if let Ok(json) = serde_json::to_string(value) {
+            let _ = fs::write(self.path(key), json);
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+/// When a file was last changed and how long it is, to tell whether it changed since it was listed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct FileStamp {
+    modified_secs: u64,
+    modified_nanos: u32,
+    len: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<FileStamp> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(FileStamp { modified_secs: modified.as_secs(), modified_nanos: modified.subsec_nanos(), len: metadata.len() })
+    }
+}
+
+/// What the puzzle list shows for a file, as read when it was last listed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListedFile {
+    pub stamp: FileStamp,
+    pub name: String,
+    pub author: Option<String>,
+    pub pack: Option<String>,
+    pub chapter: Option<String>,
+}
+
+/// The title and the rest of every puzzle file listed before, so listing a folder again only reads
+/// files that changed.  Kept in one file in the local data directory.
+pub struct ListingCache {
+    path: PathBuf,
+    files: HashMap<String, ListedFile>,
+    changed: bool,
+}
+
+impl ListingCache {
+    /// The cache in the user's data directory, or `None` if there isn't one.  A missing or
+    /// unreadable cache file starts an empty cache.
+    pub fn open() -> Option<ListingCache> {
+        let mut path = dirs::data_local_dir()?;
+        path.push("tametsi");
+        fs::create_dir_all(&path).ok()?;
+        path.push("listings.json");
+        let files = fs::read_to_string(&path).ok().and_then(|contents| serde_json::from_str(&contents).ok()).unwrap_or_default();
+        Some(ListingCache { path, files, changed: false })
+    }
+
+    /// The file's listing, if it hasn't changed since.
+    pub fn get(&self, path: &Path, stamp: FileStamp) -> Option<&ListedFile> {
+        self.files.get(&ListingCache::key(path)).filter(|listed| listed.stamp == stamp)
+    }
+
+    pub fn put(&mut self, path: &Path, listed: ListedFile) {
+        self.files.insert(ListingCache::key(path), listed);
+        self.changed = true;
+    }
+
+    /// Files are known by their full path, so the same folder is found however it's given.
+    fn key(path: &Path) -> String {
+        fs::canonicalize(path).as_deref().unwrap_or(path).to_string_lossy().into_owned()
+    }
+
+    /// Saves the cache if anything was added, dropping files that are gone.  Errors are ignored, as
+    /// for solves.
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+        self.files.retain(|path, _| Path::new(path).is_file());
+        if let Ok(json) = serde_json::to_string(&self.files) {
+            let _ = fs::write(&self.path, json);
+        }
+        self.changed = false;
+    }
+}
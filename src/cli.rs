@@ -0,0 +1,1055 @@
+use std::{collections::hash_map::DefaultHasher, fs, hash::{Hash, Hasher}, hint::black_box, path::{Path, PathBuf}, sync::Arc, time::Instant};
+
+use crate::{cache::SolveCache, config::{self, Config}, core::{Bits, BitsKey}, convert::{self, Format, PuzzleFile}, difficulty::{self, DifficultyModel}, export::{self, GraphFormat, TableFormat, Theme}, generator::{self, GeneratorConfig, Shape}, history::{History, HistoryEntry}, parser::{BUNDLE_EXTENSION, Bundle, ParseMode, Parser, PuzzleListing}, report::{GuessPolicy, GuessSimulation, PlayerSimulation, SolveReport}, rules, script, solver::{InferenceRule, Preset, SolveOutcome, Solver, SolverConfig, Uniqueness}, technique::{Technique, TechniqueCounts}, trace::Trace, video};
+
+const USAGE: &str = "Usage:
+    tametsi                                  Start the GUI
+    tametsi solve [OPTIONS] [PUZZLE_DIR]     Solve every puzzle in a folder (defaults to the Steam install)
+    tametsi solve [OPTIONS] -                Solve an XML or JSON puzzle read from stdin and print its report as JSON
+    tametsi verify [OPTIONS] [PUZZLE_DIR]    Check that every puzzle in a folder has a unique solution, and suggest
+                                             fixes for ones that need guessing
+    tametsi simulate [OPTIONS] [PUZZLE_DIR]  Report where a player who only knows some techniques gets stuck in
+                                             each puzzle in a folder
+    tametsi simulate --guessing [OPTIONS] [DIR]
+                                             Play each puzzle in a folder to the end, guessing against the real
+                                             layout whenever stuck, and report how often each guess policy wins
+    tametsi bench [OPTIONS] [PUZZLE_DIR]     Time listing and reading the puzzles in a folder, then solving each one
+                                             and hashing the constraints found along the way bit by bit and by
+                                             fingerprint
+    tametsi calibrate [OPTIONS] [PUZZLE_DIR] Fit the difficulty model to a ranking of the puzzles in a folder
+    tametsi export [OPTIONS] [PUZZLE_DIR]    Write a step by step HTML walkthrough of every puzzle in a folder
+    tametsi steps [OPTIONS] [PUZZLE_DIR]     Write every step of solving each puzzle in a folder as JSON
+    tametsi steps --schema                   Print the JSON Schema the steps follow
+    tametsi techniques [OPTIONS] [DIR]       Write how many deductions each technique makes solving every puzzle in a
+                                             folder, as a table with a row per puzzle
+    tametsi graph [OPTIONS] [PUZZLE_DIR]     Write the constraints left where the solver stops in each puzzle as a graph
+    tametsi video [OPTIONS] [PUZZLE_DIR]     Write a picture of the board after every step of each puzzle in a folder,
+                                             and with --ffmpeg stitch them into a WebM video
+    tametsi markdown [OPTIONS] [PUZZLE_DIR]  Write a Markdown log of solving each puzzle in a folder, with a PNG of
+                                             the board after every step that resolves squares
+    tametsi trace record [OPTIONS] [DIR]     Save the squares each step resolves in every puzzle in a folder
+    tametsi trace check [OPTIONS] [DIR]      Solve every puzzle in a folder again and report how the steps differ
+                                             from the saved ones
+    tametsi diff-trace A B [OPTIONS]         Line up two solves step by step and report where they part, what each
+                                             deduces that the other doesn't and how many steps each takes.  A and B
+                                             are saved traces or puzzle files, which are solved first
+    tametsi render FILE [OPTIONS]            Draw the board after a step of solving a puzzle as SVG, without the GUI
+    tametsi record FILE [OPTIONS]            Solve a puzzle and save it with every step in one bundle file
+    tametsi replay BUNDLE                    Print the steps saved in a bundle, then solve it again and report how
+                                             the steps differ
+    tametsi convert FILE --to <FORMAT>       Print a puzzle in another format: xml, json, hexcells, text or toml
+    tametsi generate [OPTIONS]               Print a new puzzle that can be solved without guessing
+    tametsi generate --pack <N> [OPTIONS]    Write N puzzles that get harder one by one, with an index
+    tametsi generate --sweep <N> [OPTIONS]   Make a puzzle from each of N seeds and write the hardest, with their
+                                             reports and an index
+    tametsi repair FILE [OPTIONS]            Print a puzzle changed to be solvable without guessing
+    tametsi history [OPTIONS] [TITLE]        Print every solve recorded, oldest first, or only those of puzzles whose
+                                             title has TITLE in it
+    tametsi script FILE [ARGS]               Run a Rhai script that can load, change, solve and save puzzles.  ARGS
+                                             are given to it as ARGS
+
+Options:
+    --preset <fast|thorough|human-like|exhaustive>
+    --unknown-mine-count                     Don't use the total number of mines
+    --strict                                 Only use numbers a player could see
+    --triple                                 Also combine constraints three at a time when crossing pairs stalls
+    --parse <strict|permissive>              Whether any problem in a file fails it, defaults to permissive (verify)
+    --guesses                                Report how many squares need guessing at each inference tier (solve)
+    --rule <disjoint-count>                  Also try this deduction rule when crossing runs out, and don't use or
+                                             save reports (solve).  Can be given more than once
+    --no-cache                               Solve again even if a saved report exists (solve, techniques)
+    --techniques <LIST>                      The techniques the player knows, separated by commas, defaults to
+                                             naked-single,hint,subset.  Also cross and mine-counting (simulate)
+    --repeat <N>                             How many times to solve each puzzle, defaults to 10 (bench, simulate
+                                             --guessing)
+    --policy <safest|frontier|random>        Only guess this way, instead of each way in turn (simulate --guessing).
+                                             Can be given more than once
+    --fixture <FILE>                         Calibration set to use instead of the built in one (calibrate)
+    --output <DIR>                           Where to write files, defaults to the current folder (export, steps,
+                                             techniques, graph, video, markdown, generate)
+    --table-format <csv|json>                Defaults to csv (techniques, history)
+    --traces <DIR>                           Where saved traces are kept, defaults to the current folder (trace)
+    --graph-format <dot|graphml>             For Graphviz or for Gephi, defaults to dot (graph)
+    --resolution <WxH>                       The size of each frame in pixels, defaults to 1280x720 (video, markdown)
+    --fps <N>                                Steps shown per second, defaults to 2 (video)
+    --theme <solver|tametsi>                 Draw boards in the GUI's colors or like the game, defaults to solver
+                                             (export, video, markdown, render)
+    --ffmpeg                                 Run ffmpeg, which has to be installed, to make the frames a video (video)
+    --walkthrough                            Also write how the solver finishes each puzzle, as HTML (generate)
+    --step <N>                               The step to draw the board after, defaults to 0, the start (render)
+    -o <FILE>                                Where to write the picture, defaults to printing it (render), or the
+                                             bundle, defaults to the puzzle's title in the current folder (record)
+    --from <FORMAT>                          The format of the puzzle, if its extension doesn't say (convert, repair,
+                                             render, record)
+    --shape <square|hex>                     The cells of the board, defaults to square (generate)
+    --size <WxH>                             The size of the board, defaults to 8x8 (generate)
+    --outline <FILE>                         An SVG path to cut the board to (generate)
+    --density <FRACTION>                     The fraction of squares that are mines, defaults to 0.2 (generate)
+    --secrets <N>                            How many safe squares hide their number, defaults to 0 (generate)
+    --starters <N>                           How many squares start revealed, defaults to 1 (generate)
+    --moves <N>                              How many mines to move, defaults to 0 (repair)
+    --seed <N>                               Picks the puzzle, or the first seed of a sweep, defaults to 0 (generate,
+                                             repair)
+    --keep <N>                               How many of the hardest puzzles a sweep writes, defaults to 5 (generate)
+    --to <FORMAT>                            The format to print, defaults to xml (convert, generate, repair)
+    --extract <IDS>                          Only print these squares, separated by commas, with the numbers that
+                                             counted squares left out turned into hints (convert)
+
+Other defaults for --preset, --unknown-mine-count, --strict, --triple, --parse, --output, --theme, --resolution, --fps,
+--graph-format and --table-format, and folders of puzzles to use instead of the Steam install, can be set in
+tametsi/config.toml in the user's config folder.  The GUI uses it too.";
+
+/// Runs the command line interface.  `args` excludes the program name.
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("solve") => solve(args),
+        Some("verify") => verify(args),
+        Some("simulate") => simulate(args),
+        Some("bench") => bench(args),
+        Some("calibrate") => calibrate(args),
+        Some("export") => export(args),
+        Some("steps") => steps(args),
+        Some("techniques") => techniques(args),
+        Some("graph") => graph(args),
+        Some("video") => video(args),
+        Some("markdown") => markdown(args),
+        Some("trace") => trace(args),
+        Some("diff-trace") => diff_trace(args),
+        Some("render") => render(args),
+        Some("record") => record(args),
+        Some("replay") => replay(args),
+        Some("convert") => convert(args),
+        Some("generate") => generate(args),
+        Some("repair") => repair(args),
+        Some("history") => history(args),
+        Some("script") => {
+            let file = args.next().ok_or(format!("script needs a script file\n\n{}", USAGE))?;
+            script::run(Path::new(&file), args.collect())
+        }
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(format!("Unknown command: {}\n\n{}", other, USAGE)),
+        None => Err(String::from(USAGE)),
+    }
+}
+
+/// Options shared by every command that runs the solver over a folder of puzzles.
+struct Options {
+    config: SolverConfig,
+    /// The folder of puzzles, or `None` for the Steam install.
+    folder: Option<String>,
+    /// The config file's folders of puzzles, used when there's no folder.
+    puzzles: Vec<PathBuf>,
+    guesses: bool,
+    use_cache: bool,
+    fixture: Option<String>,
+    output: PathBuf,
+    parse_mode: ParseMode,
+    graph_format: GraphFormat,
+    table_format: TableFormat,
+    traces: PathBuf,
+    techniques: Vec<Technique>,
+    resolution: (usize, usize),
+    fps: u32,
+    ffmpeg: bool,
+    theme: Theme,
+    repeat: usize,
+    rules: Vec<Arc<dyn InferenceRule>>,
+    guessing: bool,
+    policies: Vec<GuessPolicy>,
+}
+
+fn parse_options(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let Config { puzzles, solver: mut config, mut parse_mode, mut output, mut theme, mut resolution, mut fps, mut graph_format, mut table_format, .. } = Config::load()?;
+    let mut folder = None;
+    let mut guesses = false;
+    let mut use_cache = true;
+    let mut fixture = None;
+    let mut traces = PathBuf::from(".");
+    let mut techniques = vec![Technique::NakedSingle, Technique::Hint, Technique::Subset];
+    let mut ffmpeg = false;
+    let mut repeat = 10;
+    let mut rules = Vec::new();
+    let mut guessing = false;
+    let mut policies = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--preset" => {
+                let name = args.next().ok_or("--preset requires a name")?;
+                let preset = Preset::from_name(&name).ok_or(format!("Unknown preset: {}", name))?;
+                config = config.with_preset(preset);
+            }
+            "--unknown-mine-count" => config.known_mine_count = false,
+            "--strict" => config.player_knowledge_only = true,
+            "--triple" => config.triple_crossing = true,
+            "--guesses" => guesses = true,
+            "--no-cache" => use_cache = false,
+            "--fixture" => fixture = Some(args.next().ok_or("--fixture requires a file")?),
+            "--output" => output = PathBuf::from(args.next().ok_or("--output requires a folder")?),
+            "--parse" => {
+                let name = args.next().ok_or("--parse requires a mode")?;
+                parse_mode = ParseMode::from_name(&name).ok_or(format!("Unknown parse mode: {}", name))?;
+            }
+            "--techniques" => {
+                let list = args.next().ok_or("--techniques requires a list")?;
+                techniques = list.split(',').map(|name| Technique::from_name(&name.trim().replace('-', " ")).ok_or(format!("Unknown technique: {}", name))).collect::<Result<_, _>>()?;
+            }
+            "--table-format" => {
+                let name = args.next().ok_or("--table-format requires a format")?;
+                table_format = TableFormat::from_name(&name).ok_or(format!("Unknown table format: {}", name))?;
+            }
+            "--traces" => traces = PathBuf::from(args.next().ok_or("--traces requires a folder")?),
+            "--graph-format" => {
+                let name = args.next().ok_or("--graph-format requires a format")?;
+                graph_format = GraphFormat::from_name(&name).ok_or(format!("Unknown graph format: {}", name))?;
+            }
+            "--resolution" => {
+                let size = args.next().ok_or("--resolution requires a size")?;
+                resolution = config::parse_resolution(&size)?;
+            }
+            "--repeat" => {
+                let number = args.next().ok_or("--repeat requires a number")?;
+                repeat = number.parse().ok().filter(|&repeat| repeat > 0).ok_or(format!("Repeats are whole numbers above 0, not {}", number))?;
+            }
+            "--fps" => {
+                let number = args.next().ok_or("--fps requires a number")?;
+                fps = number.parse().ok().filter(|&fps| fps > 0).ok_or(format!("Frame rates are whole numbers above 0, not {}", number))?;
+            }
+            "--ffmpeg" => ffmpeg = true,
+            "--theme" => {
+                let name = args.next().ok_or("--theme requires a name")?;
+                theme = Theme::from_name(&name).ok_or(format!("Unknown theme: {}", name))?;
+            }
+            "--rule" => {
+                let name = args.next().ok_or("--rule requires a name")?;
+                rules.push(rules::from_name(&name).ok_or(format!("Unknown rule: {}, expected one of {}", name, rules::NAMES.join(", ")))?);
+            }
+            "--guessing" => guessing = true,
+            "--policy" => {
+                let name = args.next().ok_or("--policy requires a name")?;
+                policies.push(GuessPolicy::from_name(&name).ok_or(format!("Unknown guess policy: {}", name))?);
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+            _ => folder = Some(arg),
+        }
+    }
+
+    Ok(Options { config, folder, puzzles, guesses, use_cache, fixture, output, parse_mode, graph_format, table_format, traces, techniques, resolution, fps, ffmpeg, theme, repeat, rules, guessing, policies })
+}
+
+/// Reads puzzles from `folder`, or from the config file's folders or the Steam install if there
+/// isn't one.  Only made when a command needs it, since looking for Steam prints a warning when it
+/// isn't there.
+fn puzzle_folder(folder: Option<String>, puzzles: Vec<PathBuf>) -> Parser {
+    match folder {
+        Some(folder) => Parser::from_folder(folder),
+        None => Parser::from_folders(puzzles),
+    }
+}
+
+fn solve(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, guesses, use_cache, rules, .. } = parse_options(args)?;
+    let stdin = folder.as_deref() == Some("-");
+    let listings = if stdin { vec![PuzzleListing::from_stdin()?] } else { puzzle_folder(folder, puzzles).read_all_puzzles() };
+    let cache = if use_cache { SolveCache::open() } else { None };
+    let history = History::open();
+    for listing in listings {
+        let report = cached_report(cache.as_ref(), history.as_ref(), &listing, config, &rules, guesses);
+
+        if stdin {
+            println!("{}", serde_json::to_string(&report).map_err(|e| e.to_string())?);
+            continue;
+        }
+
+        let stats = &report.stats;
+        println!("{}: {} in {} steps, {} crossings ({} skipped, {} mirrored, {} triple), difficulty {:.1} ({})", listing.name, report.outcome, stats.steps, stats.crosses, stats.crosses_skipped, stats.crosses_mirrored, stats.triple_crosses, report.difficulty, stats.techniques);
+        if !report.best_guesses.is_empty() {
+            let (puzzle, _) = listing.read();
+            let guesses = report.best_guesses.iter().map(|guess| format!("{} ({:.0}% safe, settles {:.1})", puzzle.id_labels[guess.square], guess.safety * 100.0, guess.information)).collect::<Vec<_>>();
+            println!("    best guesses: {}", guesses.join(", "));
+        }
+        if let SolveOutcome::Contradiction(_) = report.outcome {
+            let (puzzle, _) = listing.read();
+            match Solver::new(puzzle.clone(), config).unsat_core() {
+                Some(core) => {
+                    println!("    these clues can't all hold:");
+                    for clue in core {
+                        println!("        {} ({} mines) on {}", clue.technique, clue.min_mines, puzzle.labels(clue.bits));
+                    }
+                }
+                None => println!("    the puzzle's clues fit together, so the solver is at fault"),
+            }
+        }
+        for accounting in &report.guesses {
+            let ending = if accounting.finished { "" } else { ", unfinished" };
+            println!("    {}: {} forced, {} guessed{}", accounting.tier.name(), accounting.forced, accounting.guessed, ending);
+        }
+    }
+
+    Ok(())
+}
+
+/// The saved report for solving `listing` with `config`, or a new one, saved if there's a cache.
+/// Saved reports are only for solves without extra rules, so solves with `rules` aren't saved or
+/// added to the history.
+fn cached_report(cache: Option<&SolveCache>, history: Option<&History>, listing: &PuzzleListing, config: SolverConfig, rules: &[Arc<dyn InferenceRule>], guesses: bool) -> SolveReport {
+    let cache = cache.filter(|_| rules.is_empty());
+    let contents = listing.contents().ok();
+    let key = contents.as_ref().map(|contents| SolveCache::key(contents, &config, guesses));
+    if let Some(report) = cache.zip(key.as_ref()).and_then(|(cache, key)| cache.get(key)) {
+        return report;
+    }
+    let (puzzle, _) = listing.read();
+    let report = SolveReport::generate(&listing.name, &puzzle, config, rules, guesses);
+    if let Some((cache, key)) = cache.zip(key.as_ref()) {
+        cache.put(key, &report);
+    }
+    if let Some((history, contents)) = history.filter(|_| rules.is_empty()).zip(contents) {
+        history.record(&HistoryEntry::new(&contents, config, report.clone()));
+    }
+    report
+}
+
+fn history(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { folder: title, table_format, .. } = parse_options(args)?;
+    let history = History::open().ok_or("Unable to open the history, which another tametsi may be using")?;
+    let mut entries = history.entries();
+    if let Some(title) = title {
+        let title = title.to_lowercase();
+        entries.retain(|entry| entry.report.title.to_lowercase().contains(&title));
+    }
+    print!("{}", export::history_table(&entries, table_format));
+    Ok(())
+}
+
+fn verify(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, parse_mode, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    let mut failed = 0;
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, gui) = match listing.read_checked(parse_mode) {
+            Ok(loaded) => loaded,
+            Err(problems) => {
+                println!("{}: rejected\n    {}", listing.name, problems.replace('\n', "\n    "));
+                failed += 1;
+                continue;
+            }
+        };
+        let mines = puzzle.mines;
+        let result = match Solver::new(puzzle.clone(), config).verify_unique() {
+            Uniqueness::Unique => String::from("unique"),
+            Uniqueness::Ambiguous(alternative) => format!("ambiguous, another layout differs on {} squares", (alternative ^ mines).count_ones()),
+            Uniqueness::Undetermined => String::from("undetermined, the search gave up"),
+        };
+        println!("{}: {}", listing.name, result);
+        for diagnostic in &gui.diagnostics {
+            println!("    {}", diagnostic);
+        }
+
+        match generator::suggest_fixes(&puzzle, &gui, config) {
+            Some(suggestions) if suggestions.is_empty() => println!("    needs guessing, and no single hint or revealed square fixes it"),
+            Some(suggestions) => {
+                println!("    needs guessing, any of these would fix it:");
+                for suggestion in suggestions {
+                    println!("        {}", suggestion.describe(&puzzle));
+                }
+            }
+            None => {}
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("Puzzles with problems: {}", failed));
+    }
+    Ok(())
+}
+
+fn simulate(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, techniques, guessing, policies, repeat, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    if guessing {
+        let policies = if policies.is_empty() { GuessPolicy::ALL.to_vec() } else { policies };
+        for listing in parser.read_all_puzzles() {
+            let (puzzle, _) = listing.read();
+            println!("{}:", listing.name);
+            for &policy in &policies {
+                let simulation = GuessSimulation::run(&puzzle, config, &techniques, policy, repeat);
+                print!("    {}: won {} of {} games ({:.0}%), {:.1} guesses a game", policy.name(), simulation.wins, simulation.games, simulation.win_rate() * 100.0, simulation.guesses_per_game());
+                if simulation.unfinished > 0 {
+                    print!(", {} not counted because the solver stopped", simulation.unfinished);
+                }
+                println!();
+            }
+        }
+        return Ok(());
+    }
+
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, _) = listing.read();
+        let simulation = PlayerSimulation::run(&puzzle, config, &techniques);
+        println!("{}: {} with {} of {} squares resolved", listing.name, simulation.outcome, simulation.resolved, simulation.total);
+        if simulation.outcome == SolveOutcome::Stuck && simulation.frontier.any() {
+            println!("    looking at {}", puzzle.labels(simulation.frontier));
+            for row in simulation.board.lines() {
+                println!("        {}", row);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn calibrate(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, fixture, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    let contents = match fixture {
+        Some(path) => fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path, e))?,
+        None => String::from(difficulty::CALIBRATION),
+    };
+    let ranking = difficulty::parse_calibration(&contents)?;
+
+    let listings = parser.read_all_puzzles();
+    let mut samples = Vec::new();
+    for (title, rank) in ranking {
+        match listings.iter().find(|listing| listing.name == title) {
+            Some(listing) => {
+                let (puzzle, _) = listing.read();
+                let mut solver = Solver::new(puzzle, config);
+                solver.solve_guessing();
+                samples.push((solver.stats, rank));
+            }
+            None => println!("Skipping {}: not in the library", title),
+        }
+    }
+
+    if samples.len() < 2 {
+        return Err(String::from("Calibration needs at least two ranked puzzles from the library"));
+    }
+
+    let ranks = samples.iter().map(|(_, rank)| *rank as f64).collect::<Vec<_>>();
+    let correlation = |model: &DifficultyModel| {
+        let scores = samples.iter().map(|(stats, _)| model.score(stats)).collect::<Vec<_>>();
+        difficulty::rank_correlation(&scores, &ranks)
+    };
+
+    let current = DifficultyModel::default();
+    let fitted = current.calibrate(&samples);
+    println!("Calibrated against {} puzzles", samples.len());
+    println!("Current rank correlation: {:.3}", correlation(&current));
+    println!("Fitted rank correlation:  {:.3}", correlation(&fitted));
+    println!("{:#?}", fitted);
+    Ok(())
+}
+
+fn bench(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, repeat, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    let started = Instant::now();
+    let listings = parser.read_all_puzzles();
+    let listed = started.elapsed().as_secs_f64() * 1000.0;
+    let started = Instant::now();
+    let puzzles = listings.iter().map(|listing| listing.read().0).collect::<Vec<_>>();
+    let read = started.elapsed().as_secs_f64() * 1000.0;
+    println!("Listed {} puzzles in {:.1} ms and read them in {:.1} ms", listings.len(), listed, read);
+
+    for (listing, puzzle) in listings.iter().zip(puzzles) {
+        let started = Instant::now();
+        let mut crossings = 0;
+        for _ in 0..repeat {
+            let mut solver = Solver::new(puzzle.clone(), config);
+            solver.solve();
+            crossings = solver.stats.crosses;
+        }
+        let per_solve = started.elapsed().as_secs_f64() * 1000.0 / repeat as f64;
+        println!("{}: {:.2} ms per solve over {} solves, {} crossings", listing.name, per_solve, repeat, crossings);
+
+        // The unsolved constraints after every step, roughly what the solver's maps are asked about
+        let mut solver = Solver::new(puzzle, config);
+        let mut keys = Vec::new();
+        loop {
+            let result = solver.step();
+            keys.extend(solver.unsolved_constraints().into_iter().map(|c| c.bits));
+            if SolveOutcome::from_step(&result).is_some() {
+                break;
+            }
+        }
+        let bitwise = time_hashes(&keys, repeat, |bits| hash(&bits));
+        let fingerprinted = time_hashes(&keys, repeat, |bits| hash(&BitsKey::from(bits)));
+        println!("    hashing {} constraints: {:.1} ns each bit by bit, {:.1} ns each by fingerprint", keys.len(), bitwise, fingerprinted);
+    }
+
+    Ok(())
+}
+
+/// The average time `hash` takes on each of `keys`, in nanoseconds, going through them `repeat` times.
+fn time_hashes(keys: &[Bits], repeat: usize, hash: impl Fn(Bits) -> u64) -> f64 {
+    let started = Instant::now();
+    for _ in 0..repeat {
+        for &bits in keys {
+            black_box(hash(black_box(bits)));
+        }
+    }
+    started.elapsed().as_nanos() as f64 / (keys.len() * repeat).max(1) as f64
+}
+
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn export(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, output, theme, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, gui) = listing.read();
+        let mut solver = Solver::new(puzzle, config);
+        let start = solver.puzzle.clone();
+        let steps = export::solve_steps(&mut solver);
+
+        let path = output.join(format!("{}.html", export::file_stem(&listing.name)));
+        fs::write(&path, export::html_walkthrough(&listing.name, &gui, &start, &steps, &solver.stats, theme)).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        println!("{}: {} steps written to {}", listing.name, steps.len(), path.display());
+    }
+
+    Ok(())
+}
+
+fn steps(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args = args.peekable();
+    if args.peek().map(String::as_str) == Some("--schema") {
+        print!("{}", export::STEPS_SCHEMA);
+        return Ok(());
+    }
+    let Options { config, folder, puzzles, output, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, gui) = listing.read();
+        let mut solver = Solver::new(puzzle, config);
+        let start = solver.puzzle.clone();
+        let (steps, durations) = export::timed_steps(&mut solver);
+
+        let path = output.join(format!("{}.steps.json", export::file_stem(&listing.name)));
+        fs::write(&path, export::steps_json(&listing.name, &gui, &start, &steps, &durations)).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        println!("{}: {} steps written to {}", listing.name, steps.len(), path.display());
+    }
+
+    Ok(())
+}
+
+fn techniques(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, use_cache, output, table_format, .. } = parse_options(args)?;
+    let cache = if use_cache { SolveCache::open() } else { None };
+    let history = History::open();
+    let reports = puzzle_folder(folder, puzzles).read_all_puzzles().iter().map(|listing| cached_report(cache.as_ref(), history.as_ref(), listing, config, &[], false)).collect::<Vec<_>>();
+
+    fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+    let path = output.join(format!("techniques.{}", table_format.extension()));
+    fs::write(&path, export::technique_table(&reports, table_format)).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+    println!("{} puzzles written to {}", reports.len(), path.display());
+    Ok(())
+}
+
+fn graph(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, output, graph_format, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, _) = listing.read();
+        let mut solver = Solver::new(puzzle, config);
+        let outcome = SolveOutcome::from_step(&solver.solve()).expect("Solving stopped early!");
+        let constraints = solver.unsolved_constraints();
+
+        let path = output.join(format!("{}.{}", export::file_stem(&listing.name), graph_format.extension()));
+        fs::write(&path, export::constraint_graph(&listing.name, &solver.puzzle.base, &constraints, graph_format)).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        println!("{}: {}, {} constraints left, written to {}", listing.name, outcome, constraints.len(), path.display());
+    }
+
+    Ok(())
+}
+
+fn diff_trace(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = || format!("diff-trace needs two traces or puzzle files\n\n{}", USAGE);
+    let first = args.next().filter(|arg| !arg.starts_with("--")).ok_or_else(usage)?;
+    let second = args.next().filter(|arg| !arg.starts_with("--")).ok_or_else(usage)?;
+    let Options { config, .. } = parse_options(args)?;
+    let first = load_trace(&first, config)?;
+    let second = load_trace(&second, config)?;
+
+    let diff = first.diff(&second);
+    match diff.first_divergence {
+        None => println!("Every step resolves the same squares"),
+        Some(step) => {
+            let describe = |trace: &Trace| match trace.steps.get(step) {
+                Some(resolved) => resolved.iter().map(|r| r.describe()).collect::<Vec<_>>().join(", "),
+                None => String::from("nothing, the solve has ended"),
+            };
+            println!("First divergence at step {}", step + 1);
+            println!("    {}: {}", first.title, describe(&first));
+            println!("    {}: {}", second.title, describe(&second));
+        }
+    }
+    for (trace, only) in [(&first, &diff.only_first), (&second, &diff.only_second)] {
+        if !only.is_empty() {
+            println!("Only in {}: {}", trace.title, only.iter().map(|r| r.describe()).collect::<Vec<_>>().join(", "));
+        }
+    }
+    if first.settings != second.settings {
+        println!("Recorded with different solver settings");
+    }
+    println!("{}: {} steps, {}: {} steps ({:+})", first.title, first.steps.len(), second.title, second.steps.len(), diff.step_delta);
+    if first.outcome != second.outcome {
+        println!("{} is {}, {} is {}", first.title, first.outcome, second.title, second.outcome);
+    }
+    Ok(())
+}
+
+/// A saved trace, or the trace of solving a puzzle file.
+fn load_trace(path: &str, config: SolverConfig) -> Result<Trace, String> {
+    let file = Path::new(path);
+    if let Ok(trace) = fs::read_to_string(file).map_err(|e| e.to_string()).and_then(|json| serde_json::from_str::<Trace>(&json).map_err(|e| e.to_string())) {
+        return Ok(trace);
+    }
+    let format = Format::of(file).ok_or(format!("{} is neither a trace nor a puzzle file", path))?;
+    let (puzzle_file, _) = convert::read(file, format)?;
+    Ok(Trace::record(path, &puzzle_file.puzzle, config))
+}
+
+fn video(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, output, resolution: (width, height), fps, ffmpeg, theme, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, gui) = listing.read();
+        let mut solver = Solver::new(puzzle, config);
+        let start = solver.puzzle.clone();
+        let steps = export::solve_steps(&mut solver);
+
+        let stem = export::file_stem(&listing.name);
+        let folder = output.join(&stem);
+        let frames = video::write_frames(&folder, &gui, &start, &steps, width, height, theme)?;
+        if ffmpeg {
+            let path = output.join(format!("{}.webm", stem));
+            video::stitch(&folder, &path, fps)?;
+            println!("{}: {} frames, {:.1} seconds, written to {}", listing.name, frames, frames as f32 / fps as f32, path.display());
+        } else {
+            println!("{}: {} frames written to {}", listing.name, frames, folder.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn markdown(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let Options { config, folder, puzzles, output, resolution: (width, height), theme, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, gui) = listing.read();
+        let mut solver = Solver::new(puzzle, config);
+        let start = solver.puzzle.clone();
+        let steps = export::solve_steps(&mut solver);
+
+        // The pictures go in a folder named after the puzzle, beside the document
+        let stem = export::file_stem(&listing.name);
+        let images = output.join(&stem);
+        fs::create_dir_all(&images).map_err(|e| format!("Unable to create {}: {}", images.display(), e))?;
+        let renderer = video::Renderer::new(&gui, width, height, theme);
+        let frames = std::iter::once((0, renderer.frame(&gui, &start, None))).chain(steps.iter().enumerate().filter(|(_, (_, result))| export::significant(result)).map(|(i, (state, result))| (i + 1, renderer.frame(&gui, state, Some(result)))));
+        let mut pictures = 0;
+        for (number, frame) in frames {
+            let path = images.join(export::step_image(number));
+            fs::write(&path, frame.png()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+            pictures += 1;
+        }
+
+        let path = output.join(format!("{}.md", stem));
+        fs::write(&path, export::markdown_log(&listing.name, &start, &steps, &solver.stats, &stem)).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        println!("{}: {} steps and {} pictures written to {}", listing.name, steps.len(), pictures, path.display());
+    }
+
+    Ok(())
+}
+
+fn trace(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let record = match args.next().as_deref() {
+        Some("record") => true,
+        Some("check") => false,
+        _ => return Err(format!("trace needs record or check\n\n{}", USAGE)),
+    };
+    let Options { config, folder, puzzles, traces, .. } = parse_options(args)?;
+    let parser = puzzle_folder(folder, puzzles);
+    fs::create_dir_all(&traces).map_err(|e| format!("Unable to create {}: {}", traces.display(), e))?;
+    let mut differing = 0;
+    let mut total = 0;
+    for listing in parser.read_all_puzzles() {
+        let (puzzle, _) = listing.read();
+        let actual = Trace::record(&listing.name, &puzzle, config);
+        let path = traces.join(format!("{}.json", export::file_stem(&listing.name)));
+        total += 1;
+        if record {
+            let json = serde_json::to_string_pretty(&actual).map_err(|e| e.to_string())?;
+            fs::write(&path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+            println!("{}: {} steps recorded in {}", listing.name, actual.steps.len(), path.display());
+            continue;
+        }
+
+        let saved = fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path.display(), e)).and_then(|json| {
+            serde_json::from_str::<Trace>(&json).map_err(|e| format!("Unable to read {}: {}", path.display(), e))
+        });
+        let differences = match saved {
+            Ok(expected) => expected.compare(&actual),
+            Err(why) => vec![why],
+        };
+        if differences.is_empty() {
+            println!("{}: unchanged", listing.name);
+        } else {
+            differing += 1;
+            println!("{}: {} differences", listing.name, differences.len());
+            for difference in differences {
+                println!("    {}", difference);
+            }
+        }
+    }
+
+    if differing > 0 {
+        return Err(format!("{} of {} puzzles differ from their traces", differing, total));
+    }
+    Ok(())
+}
+
+fn convert(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut input = None;
+    let mut from = None;
+    let mut to = None;
+    let mut extract = None;
+    let mut args = args.peekable();
+    let format = |name: Option<String>| {
+        let name = name.ok_or("--from and --to require a format")?;
+        Format::from_name(&name).ok_or(format!("Unknown format: {}.  Use xml, json, hexcells, text or toml", name))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(format(args.next())?),
+            "--to" => to = Some(format(args.next())?),
+            "--extract" => extract = Some(args.next().ok_or("--extract requires a list of squares")?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+            _ => input = Some(arg),
+        }
+    }
+
+    let input = input.ok_or(format!("convert needs a puzzle file\n\n{}", USAGE))?;
+    let path = Path::new(&input);
+    let from = from.or_else(|| Format::of(path)).ok_or(format!("Can't tell the format of {}, use --from", input))?;
+    let to = to.ok_or("convert needs --to")?;
+    let (mut file, lost) = convert::read(path, from)?;
+    for what in lost {
+        eprintln!("Left out: {}", what);
+    }
+    if let Some(list) = extract {
+        let mut cells = Bits::zeroed();
+        for id in list.split(',').map(str::trim) {
+            let square = file.puzzle.id_labels.iter().position(|label| label == id).ok_or(format!("{} has no square {}", file.title, id))?;
+            cells.set(square, true);
+        }
+        file.gui = file.gui.extract(&file.puzzle, cells);
+        file.puzzle = file.puzzle.extract(cells);
+    }
+    print!("{}", convert::write(&file, to)?);
+    Ok(())
+}
+
+fn record(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut from = None;
+    let mut output = None;
+    // Anything else is the puzzle or a solver option, which the shared options read with their values
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(args.next().ok_or("-o requires a file")?),
+            "--from" => {
+                let name = args.next().ok_or("--from requires a format")?;
+                from = Some(Format::from_name(&name).ok_or(format!("Unknown format: {}", name))?);
+            }
+            _ => rest.push(arg),
+        }
+    }
+    let Options { config, folder: input, .. } = parse_options(rest.into_iter())?;
+
+    let input = input.ok_or(format!("record needs a puzzle file\n\n{}", USAGE))?;
+    let path = Path::new(&input);
+    let from = from.or_else(|| Format::of(path)).ok_or(format!("Can't tell the format of {}, use --from", input))?;
+    let (file, _) = convert::read(path, from)?;
+    let trace = Trace::record(&file.title, &file.puzzle, config);
+    let summary = format!("{}: {} steps, {}", file.title, trace.steps.len(), trace.outcome);
+    let mut bundle = Bundle::new(&file)?;
+    bundle.config = Some(config);
+    bundle.replay = Some(trace);
+
+    let output = output.map(PathBuf::from).unwrap_or_else(|| PathBuf::from(format!("{}.{}", export::file_stem(&file.title), BUNDLE_EXTENSION)));
+    bundle.write(&output)?;
+    println!("{}, recorded in {}", summary, output.display());
+    Ok(())
+}
+
+fn replay(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let input = args.next().ok_or(format!("replay needs a bundle\n\n{}", USAGE))?;
+    let path = Path::new(&input);
+    let bundle = Bundle::read(path)?;
+    println!("{}", bundle.title);
+    if let Some(author) = &bundle.author {
+        println!("by {}", author);
+    }
+    for (key, value) in &bundle.metadata {
+        println!("{}: {}", key, value);
+    }
+    let (replay, config) = match (&bundle.replay, bundle.config) {
+        (Some(replay), Some(config)) => (replay, config),
+        _ => return Err(format!("{} has no solve to replay", input)),
+    };
+    for (step, resolved) in replay.steps.iter().enumerate() {
+        println!("Step {}: {}", step + 1, resolved.iter().map(|r| r.describe()).collect::<Vec<_>>().join(", "));
+    }
+    println!("{}", replay.outcome);
+
+    // The bundle's own puzzle, solved again the way it was recorded
+    let (puzzle, _) = PuzzleListing::new(path.to_path_buf(), Vec::new()).read();
+    let differences = replay.compare(&Trace::record(&bundle.title, &puzzle, config));
+    if differences.is_empty() {
+        println!("Solving again takes the same steps");
+        return Ok(());
+    }
+    for difference in &differences {
+        println!("    {}", difference);
+    }
+    Err(format!("Solving again differs from the recording in {} ways", differences.len()))
+}
+
+fn render(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut from = None;
+    let mut step = 0;
+    let mut output = None;
+    // Anything else is the puzzle or a solver or display option, which the shared options read with
+    // their values
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--step" => {
+                let number = args.next().ok_or("--step requires a number")?;
+                step = number.parse().map_err(|_| format!("Steps are whole numbers, not {}", number))?;
+            }
+            "-o" => output = Some(args.next().ok_or("-o requires a file")?),
+            "--from" => {
+                let name = args.next().ok_or("--from requires a format")?;
+                from = Some(Format::from_name(&name).ok_or(format!("Unknown format: {}", name))?);
+            }
+            _ => rest.push(arg),
+        }
+    }
+    let Options { config, theme, folder: input, .. } = parse_options(rest.into_iter())?;
+
+    let input = input.ok_or(format!("render needs a puzzle file\n\n{}", USAGE))?;
+    let path = Path::new(&input);
+    let from = from.or_else(|| Format::of(path)).ok_or(format!("Can't tell the format of {}, use --from", input))?;
+    let (file, _) = convert::read(path, from)?;
+    let mut solver = Solver::new(file.puzzle, config);
+    let start = solver.puzzle.clone();
+    let steps = export::solve_steps(&mut solver);
+    let svg = match step {
+        0 => export::board_svg(&file.gui, &start, None, theme),
+        _ => {
+            let (state, result) = steps.get(step - 1).ok_or(format!("The solve ends after {} steps", steps.len()))?;
+            export::board_svg(&file.gui, state, Some(result), theme)
+        }
+    };
+
+    match output {
+        Some(output) => fs::write(&output, svg).map_err(|e| format!("Unable to write {}: {}", output, e))?,
+        None => println!("{}", svg),
+    }
+    Ok(())
+}
+
+fn generate(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut config = GeneratorConfig::default();
+    let mut to = Format::Xml;
+    let mut pack = None;
+    let mut sweep = None;
+    let mut keep = 5;
+    let mut output = PathBuf::from(".");
+    let mut walkthrough = false;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} requires a value", arg));
+        match arg.as_str() {
+            "--shape" => {
+                let name = value()?;
+                config.shape = Shape::from_name(&name).ok_or(format!("Unknown shape: {}.  Use square or hex", name))?;
+            }
+            "--size" => {
+                let size = value()?;
+                let parsed = size.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+                (config.width, config.height) = parsed.ok_or(format!("Sizes look like 8x8, not {}", size))?;
+            }
+            "--outline" => {
+                let path = value()?;
+                let text = fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+                config.outline = Some(generator::parse_svg_path(&text)?);
+            }
+            "--density" => {
+                let density = value()?;
+                config.density = density.parse().map_err(|_| format!("Densities look like 0.2, not {}", density))?;
+            }
+            "--secrets" | "--starters" => {
+                let count = value()?;
+                let count = count.parse().map_err(|_| format!("{} takes a whole number, not {}", arg, count))?;
+                if arg == "--secrets" {
+                    config.secrets = count;
+                } else {
+                    config.starters = count;
+                }
+            }
+            "--seed" => {
+                let seed = value()?;
+                config.seed = seed.parse().map_err(|_| format!("Seeds are whole numbers, not {}", seed))?;
+            }
+            "--pack" => {
+                let count = value()?;
+                pack = Some(count.parse().map_err(|_| format!("--pack takes a whole number, not {}", count))?);
+            }
+            "--sweep" => {
+                let count = value()?;
+                sweep = Some(count.parse().map_err(|_| format!("--sweep takes a whole number, not {}", count))?);
+            }
+            "--keep" => {
+                let count = value()?;
+                keep = count.parse().map_err(|_| format!("--keep takes a whole number, not {}", count))?;
+            }
+            "--output" => output = PathBuf::from(value()?),
+            "--walkthrough" => walkthrough = true,
+            "--to" => {
+                let name = value()?;
+                to = Format::from_name(&name).ok_or(format!("Unknown format: {}.  Use xml, json, hexcells, text or toml", name))?;
+            }
+            _ => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let write_walkthrough = |file: &PuzzleFile, stem: &str| {
+        let path = output.join(format!("{}.html", stem));
+        fs::write(&path, generator::walkthrough(file)).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        Ok::<_, String>(path)
+    };
+
+    if let Some(count) = sweep {
+        fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+        let mut index = format!("# The hardest {} of {} {} {}x{} puzzles from seed {}\n", keep, count, config.shape.name(), config.width, config.height, config.seed);
+        for puzzle in generator::sweep(&config, count, keep) {
+            let stem = format!("seed{}", puzzle.seed);
+            let name = format!("{}.{}", stem, to.extension());
+            let path = output.join(&name);
+            fs::write(&path, convert::write(&puzzle.file, to)?).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+            let report_path = output.join(format!("{}.report.json", stem));
+            let json = serde_json::to_string_pretty(&puzzle.report).map_err(|e| e.to_string())?;
+            fs::write(&report_path, json).map_err(|e| format!("Unable to write {}: {}", report_path.display(), e))?;
+            if walkthrough {
+                write_walkthrough(&puzzle.file, &stem)?;
+            }
+
+            let report = &puzzle.report;
+            index.push_str(&format!("# seed {}, score {:.1}, {}\n{}\n", puzzle.seed, report.difficulty, report.stats.techniques, name));
+            println!("{}: seed {}, score {:.1}, {} ({})", name, puzzle.seed, report.difficulty, report.outcome, report.stats.techniques);
+        }
+
+        let path = output.join("index.txt");
+        fs::write(&path, index).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        println!("Index written to {}", path.display());
+        return Ok(());
+    }
+
+    let count = match pack {
+        Some(count) => count,
+        None => {
+            let file = generator::generate(&config)?;
+            if walkthrough {
+                fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+                eprintln!("Walkthrough written to {}", write_walkthrough(&file, &export::file_stem(&file.title))?.display());
+            }
+            print!("{}", convert::write(&file, to)?);
+            return Ok(());
+        }
+    };
+
+    fs::create_dir_all(&output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+    let mut index = format!("# {} {}x{} puzzles, seed {}\n", config.shape.name(), config.width, config.height, config.seed);
+    let mut seen = TechniqueCounts::default();
+    for (level, puzzle) in generator::generate_pack(&config, count)?.iter().enumerate() {
+        let stem = format!("level{:02}", level + 1);
+        let name = format!("{}.{}", stem, to.extension());
+        let path = output.join(&name);
+        fs::write(&path, convert::write(&puzzle.file, to)?).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        if walkthrough {
+            write_walkthrough(&puzzle.file, &stem)?;
+        }
+
+        let new = puzzle.techniques.used().filter(|&(technique, _)| seen.get(technique) == 0).map(|(technique, _)| technique.name()).collect::<Vec<_>>();
+        for (technique, _) in puzzle.techniques.used() {
+            seen.record(technique);
+        }
+        let new = if new.is_empty() { String::from("nothing") } else { new.join(", ") };
+        index.push_str(&format!("# score {:.1}, introduces {}\n{}\n", puzzle.score, new, name));
+        println!("{}: score {:.1}, introduces {}", name, puzzle.score, new);
+    }
+
+    let path = output.join("index.txt");
+    fs::write(&path, index).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+    println!("Index written to {}", path.display());
+    Ok(())
+}
+
+fn repair(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut input = None;
+    let mut from = None;
+    let mut to = Format::Xml;
+    let mut moves = 0;
+    let mut seed = 0;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} requires a value", arg));
+        match arg.as_str() {
+            "--from" | "--to" => {
+                let name = value()?;
+                let format = Format::from_name(&name).ok_or(format!("Unknown format: {}.  Use xml, json, hexcells, text or toml", name))?;
+                if arg == "--from" {
+                    from = Some(format);
+                } else {
+                    to = format;
+                }
+            }
+            "--moves" => {
+                let count = value()?;
+                moves = count.parse().map_err(|_| format!("--moves takes a whole number, not {}", count))?;
+            }
+            "--seed" => {
+                let number = value()?;
+                seed = number.parse().map_err(|_| format!("Seeds are whole numbers, not {}", number))?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+            _ => input = Some(arg),
+        }
+    }
+
+    let input = input.ok_or(format!("repair needs a puzzle file\n\n{}", USAGE))?;
+    let path = Path::new(&input);
+    let from = from.or_else(|| Format::of(path)).ok_or(format!("Can't tell the format of {}, use --from", input))?;
+    let (mut file, _) = convert::read(path, from)?;
+    let (puzzle, changes) = generator::repair(&file.puzzle, &file.gui, moves, seed)?;
+    for change in changes {
+        eprintln!("{}", change);
+    }
+    file.puzzle = puzzle;
+    print!("{}", convert::write(&file, to)?);
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::{fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{app::Dock, export::{GraphFormat, TableFormat, Theme}, parser::ParseMode, solver::{Preset, SolverConfig}};
+
+/// Defaults for the GUI and the command line, read from `tametsi/config.toml` in the user's config
+/// folder.  Options given on the command line win.  Every key is optional:
+///
+/// ```toml
+/// puzzles = ["/home/me/puzzles"]  # listed instead of the Steam install
+///
+/// [solver]
+/// preset = "thorough"
+/// unknown_mine_count = false
+/// strict = false
+/// triple_crossing = false
+/// parse = "permissive"
+///
+/// [gui]
+/// high_contrast = false
+/// dock = "left"
+/// constraint_warning = 20000  # 0 never pauses
+///
+/// [export]
+/// output = "."
+/// theme = "solver"
+/// resolution = "1280x720"
+/// fps = 2
+/// graph_format = "dot"
+/// table_format = "csv"
+/// ```
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Folders of puzzles, or none for the Steam install.
+    pub puzzles: Vec<PathBuf>,
+    pub solver: SolverConfig,
+    pub parse_mode: ParseMode,
+    pub high_contrast: bool,
+    pub dock: Dock,
+    /// How many unsolved constraints a GUI run builds up before pausing to ask, if any.
+    pub constraint_warning: Option<usize>,
+    pub output: PathBuf,
+    pub theme: Theme,
+    pub resolution: (usize, usize),
+    pub fps: u32,
+    pub graph_format: GraphFormat,
+    pub table_format: TableFormat,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            puzzles: Vec::new(),
+            solver: SolverConfig::default(),
+            parse_mode: ParseMode::Permissive,
+            high_contrast: false,
+            dock: Dock::Left,
+            constraint_warning: Some(20_000),
+            output: PathBuf::from("."),
+            theme: Theme::Solver,
+            resolution: (1280, 720),
+            fps: 2,
+            graph_format: GraphFormat::Dot,
+            table_format: TableFormat::Csv,
+        }
+    }
+}
+
+/// The file as written, before names are looked up.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    puzzles: Vec<PathBuf>,
+    solver: SolverSection,
+    gui: GuiSection,
+    export: ExportSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SolverSection {
+    preset: Option<String>,
+    unknown_mine_count: bool,
+    strict: bool,
+    triple_crossing: bool,
+    parse: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GuiSection {
+    high_contrast: bool,
+    dock: Option<String>,
+    constraint_warning: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ExportSection {
+    output: Option<PathBuf>,
+    theme: Option<String>,
+    resolution: Option<String>,
+    fps: Option<u32>,
+    graph_format: Option<String>,
+    table_format: Option<String>,
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        let mut path = dirs::config_dir()?;
+        path.push("tametsi");
+        path.push("config.toml");
+        Some(path)
+    }
+
+    /// The settings in the config file, or the defaults if there isn't one.
+    pub fn load() -> Result<Config, String> {
+        let path = match Config::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        };
+        let contents = fs::read_to_string(&path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        Config::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(contents: &str) -> Result<Config, String> {
+        let file = toml::from_str::<ConfigFile>(contents).map_err(|e| e.to_string())?;
+        let defaults = Config::default();
+        let mut solver = defaults.solver;
+        if let Some(name) = file.solver.preset {
+            solver = solver.with_preset(Preset::from_name(&name).ok_or(format!("Unknown preset: {}", name))?);
+        }
+        solver.known_mine_count = !file.solver.unknown_mine_count;
+        solver.player_knowledge_only = file.solver.strict;
+        solver.triple_crossing |= file.solver.triple_crossing;
+
+        Ok(Config {
+            puzzles: file.puzzles,
+            solver,
+            parse_mode: match file.solver.parse {
+                Some(name) => ParseMode::from_name(&name).ok_or(format!("Unknown parse mode: {}", name))?,
+                None => defaults.parse_mode,
+            },
+            high_contrast: file.gui.high_contrast,
+            dock: match file.gui.dock {
+                Some(name) => Dock::from_name(&name).ok_or(format!("Unknown dock: {}", name))?,
+                None => defaults.dock,
+            },
+            constraint_warning: match file.gui.constraint_warning {
+                Some(0) => None,
+                Some(limit) => Some(limit),
+                None => defaults.constraint_warning,
+            },
+            output: file.export.output.unwrap_or(defaults.output),
+            theme: match file.export.theme {
+                Some(name) => Theme::from_name(&name).ok_or(format!("Unknown theme: {}", name))?,
+                None => defaults.theme,
+            },
+            resolution: match file.export.resolution {
+                Some(size) => parse_resolution(&size)?,
+                None => defaults.resolution,
+            },
+            fps: match file.export.fps {
+                Some(0) => return Err(String::from("Frame rates are whole numbers above 0, not 0")),
+                Some(fps) => fps,
+                None => defaults.fps,
+            },
+            graph_format: match file.export.graph_format {
+                Some(name) => GraphFormat::from_name(&name).ok_or(format!("Unknown graph format: {}", name))?,
+                None => defaults.graph_format,
+            },
+            table_format: match file.export.table_format {
+                Some(name) => TableFormat::from_name(&name).ok_or(format!("Unknown table format: {}", name))?,
+                None => defaults.table_format,
+            },
+        })
+    }
+}
+
+/// A size in pixels, like 1280x720.
+pub fn parse_resolution(size: &str) -> Result<(usize, usize), String> {
+    let parsed = size.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+    parsed.filter(|&(w, h)| w > 0 && h > 0).ok_or(format!("Resolutions look like 1280x720, not {}", size))
+}
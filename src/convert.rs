@@ -0,0 +1,974 @@
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ascii::Layout, core::{Bits, Puzzle, PuzzleGui, SquareDimensions}, export::escape, parser::PuzzleListing};
+
+/// The size of a Hexcells level, in cells.
+const HEXCELLS_SIZE: usize = 33;
+const HEXCELLS_HEADER: &str = "Hexcells level v1";
+/// The distance from the centre of a converted hexagon or square to its corners.
+const CELL_RADIUS: f32 = 20.0;
+
+/// The squares a square touches on a square grid.
+const KING_NEIGHBORS: [(i64, i64); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// The puzzle formats that can be converted between.
+///
+/// `text` is a square grid with one character per square: `.` safe, `*` mine, `x` flagged mine, `o`
+/// revealed, `?` safe with a secret number, `!` revealed with a secret number, and a space where
+/// there's no square.
+/// Squares touch their eight neighbours.  The first lines starting with `#` are the title and the
+/// author, and a line like `columns: 0 3` adds a hint for each of those columns.
+///
+/// `toml` is for writing puzzles by hand.  A `grid` uses the same characters as `text`, and
+/// `[[cell]]` tables add cells one at a time.  Cells on the grid are named `x_y`.
+///
+/// ```toml
+/// title = "Corners"
+/// adjacency = "king"   # or "rook", "hex" (odd columns half a cell lower) or "none"
+/// grid = """
+/// .*.
+/// o..
+/// """
+/// columns = [1]
+/// hints = [["0_0", "2_1"]]
+///
+/// [[cell]]
+/// id = "extra"
+/// at = [3, 1]
+/// mine = true
+/// neighbors = ["2_1"]  # instead of the ones adjacency gives
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Xml,
+    Json,
+    Hexcells,
+    Text,
+    Toml,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "xml" => Some(Format::Xml),
+            "json" => Some(Format::Json),
+            "hexcells" => Some(Format::Hexcells),
+            "text" | "txt" => Some(Format::Text),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    /// The extension files in this format are written with.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Xml => "xml",
+            Format::Json => "json",
+            Format::Hexcells => "hexcells",
+            Format::Text => "txt",
+            Format::Toml => "toml",
+        }
+    }
+
+    /// The format a file's extension suggests.
+    pub fn of(path: &Path) -> Option<Format> {
+        path.extension().and_then(|extension| extension.to_str()).and_then(Format::from_name)
+    }
+}
+
+/// A puzzle with everything needed to write it out again.
+#[derive(Clone)]
+pub struct PuzzleFile {
+    pub title: String,
+    pub author: Option<String>,
+    pub puzzle: Puzzle,
+    pub gui: PuzzleGui,
+}
+
+/// Reads a puzzle in any format.  Also returns what the format has that the puzzle can't, which was
+/// left out.
+pub fn read(path: &Path, format: Format) -> Result<(PuzzleFile, Vec<String>), String> {
+    if format == Format::Xml {
+        let listing = PuzzleListing::new(path.to_path_buf(), Vec::new());
+        let (puzzle, gui) = listing.read();
+        let lost = gui.diagnostics.iter().map(|diagnostic| format!("{}: {}", diagnostic.node, diagnostic.problem)).collect();
+        return Ok((PuzzleFile { title: listing.name, author: listing.author, puzzle, gui }, lost));
+    }
+
+    let contents = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    parse(&contents, format)
+}
+
+/// Reads a puzzle in any format but XML, which the parser reads.
+pub fn parse(contents: &str, format: Format) -> Result<(PuzzleFile, Vec<String>), String> {
+    match format {
+        Format::Json => read_json(contents).map(|file| (file, Vec::new())),
+        Format::Hexcells => read_hexcells(contents),
+        Format::Text => read_text(contents).map(|file| (file, Vec::new())),
+        Format::Toml => read_toml(contents).map(|file| (file, Vec::new())),
+        Format::Xml => Err(String::from("XML puzzles are read by the parser")),
+    }
+}
+
+pub fn write(file: &PuzzleFile, format: Format) -> Result<String, String> {
+    match format {
+        Format::Xml => Ok(write_xml(file)),
+        Format::Json => Ok(write_json(file)),
+        Format::Hexcells => write_hexcells(file),
+        Format::Text => write_text(file),
+        Format::Toml => write_toml(file),
+    }
+}
+
+/// A square as the readers and the generator find it, before the puzzle is put together.
+pub struct Square {
+    pub id: String,
+    pub x: f32,
+    pub y: f32,
+    pub points: Vec<(f32, f32)>,
+    pub neighbors: Vec<usize>,
+    pub mine: bool,
+    pub secret: bool,
+    pub revealed: bool,
+    pub flagged: bool,
+}
+
+pub fn assemble(title: String, author: Option<String>, squares: Vec<Square>, hints: Vec<Vec<usize>>) -> Result<PuzzleFile, String> {
+    if squares.is_empty() {
+        return Err(String::from("The puzzle has no squares"));
+    }
+    if squares.len() > Bits::zeroed().len() {
+        return Err(format!("The puzzle has {} squares, but at most {} are supported", squares.len(), Bits::zeroed().len()));
+    }
+    if let Some(square) = squares.iter().find(|square| square.flagged && !square.mine) {
+        return Err(format!("Square {} is flagged but isn't a mine", square.id));
+    }
+    if let Some(square) = squares.iter().find(|square| square.revealed && square.flagged) {
+        return Err(format!("Square {} is both revealed and flagged", square.id));
+    }
+    if let Some(square) = squares.iter().find(|square| square.revealed && square.mine) {
+        return Err(format!("Square {} is revealed but is a mine", square.id));
+    }
+
+    let bits = |indices: &[usize]| indices.iter().fold(Bits::zeroed(), |mut bits, &i| {
+        bits.set(i, true);
+        bits
+    });
+    let mut puzzle = Puzzle {
+        neighbors: squares.iter().map(|square| bits(&square.neighbors)).collect(),
+        mines: Bits::zeroed(),
+        unknowns: Bits::zeroed(),
+        revealed: Bits::zeroed(),
+        flagged: Bits::zeroed(),
+        hints: hints.iter().map(|hint| bits(hint)).collect(),
+        id_labels: squares.iter().map(|square| square.id.clone()).collect(),
+        layout: Layout::default(),
+    };
+    for (i, square) in squares.iter().enumerate() {
+        puzzle.mines.set(i, square.mine);
+        puzzle.unknowns.set(i, square.secret);
+        puzzle.revealed.set(i, square.revealed);
+        puzzle.flagged.set(i, square.flagged);
+    }
+
+    let gui = PuzzleGui {
+        min_x: squares.iter().map(|a| a.x).fold(f32::MAX, f32::min),
+        min_y: squares.iter().map(|a| a.y).fold(f32::MAX, f32::min),
+        max_x: squares.iter().map(|a| a.x).fold(f32::MIN, f32::max),
+        max_y: squares.iter().map(|a| a.y).fold(f32::MIN, f32::max),
+        squares: squares.into_iter().map(|square| SquareDimensions { x: square.x, y: square.y, points: square.points }).collect(),
+        diagnostics: Vec::new(),
+        color_hints: Vec::new(),
+    };
+
+    puzzle.layout = Layout::new(&gui);
+    Ok(PuzzleFile { title, author, puzzle, gui })
+}
+
+/// The IDs of a set of squares.
+fn ids(file: &PuzzleFile, bits: &Bits) -> Vec<String> {
+    bits.iter_ones().map(|i| file.puzzle.id_labels[i].clone()).collect()
+}
+
+fn write_xml(file: &PuzzleFile) -> String {
+    let puzzle = &file.puzzle;
+    let mut xml = String::from("<PUZZLE>");
+    write!(xml, "<TITLE>{}</TITLE>", escape(&file.title)).unwrap();
+    if let Some(author) = &file.author {
+        write!(xml, "<AUTHOR>{}</AUTHOR>", escape(author)).unwrap();
+    }
+    xml.push_str("<GRAPH>");
+    for (i, square) in file.gui.squares.iter().enumerate() {
+        write!(xml, "<NODE><ID>{}</ID><EDGES>{}</EDGES>", escape(&puzzle.id_labels[i]), escape(&ids(file, &puzzle.neighbors[i]).join(","))).unwrap();
+        for (set, tag) in [(puzzle.mines[i], "HAS_MINE"), (puzzle.unknowns[i], "SECRET"), (puzzle.revealed[i], "REVEALED"), (puzzle.flagged[i], "FLAGGED")] {
+            if set {
+                write!(xml, "<{}/>", tag).unwrap();
+            }
+        }
+        let points = square.points.iter().map(|(x, y)| format!("{},{}", x, y)).collect::<Vec<_>>().join(",");
+        write!(xml, "<POS>{},{}</POS><POLY><POINTS>{}</POINTS></POLY></NODE>", square.x, square.y, points).unwrap();
+    }
+    xml.push_str("</GRAPH><HINT_LIST>");
+    for hint in &puzzle.hints {
+        write!(xml, "<HINT><IDS>{}</IDS></HINT>", escape(&ids(file, hint).join(","))).unwrap();
+    }
+    xml.push_str("</HINT_LIST></PUZZLE>\n");
+    xml
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonPuzzle {
+    title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    squares: Vec<JsonSquare>,
+    /// Each hint is the IDs of the squares it counts the mines in.
+    #[serde(default)]
+    hints: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonSquare {
+    id: String,
+    x: f32,
+    y: f32,
+    points: Vec<(f32, f32)>,
+    neighbors: Vec<String>,
+    #[serde(default)]
+    mine: bool,
+    #[serde(default)]
+    secret: bool,
+    #[serde(default)]
+    revealed: bool,
+    #[serde(default)]
+    flagged: bool,
+}
+
+fn write_json(file: &PuzzleFile) -> String {
+    let puzzle = &file.puzzle;
+    let json = JsonPuzzle {
+        title: file.title.clone(),
+        author: file.author.clone(),
+        squares: file.gui.squares.iter().enumerate().map(|(i, square)| JsonSquare {
+            id: puzzle.id_labels[i].clone(),
+            x: square.x,
+            y: square.y,
+            points: square.points.clone(),
+            neighbors: ids(file, &puzzle.neighbors[i]),
+            mine: puzzle.mines[i],
+            secret: puzzle.unknowns[i],
+            revealed: puzzle.revealed[i],
+            flagged: puzzle.flagged[i],
+        }).collect(),
+        hints: puzzle.hints.iter().map(|hint| ids(file, hint)).collect(),
+    };
+    serde_json::to_string_pretty(&json).expect("Unable to write JSON!") + "\n"
+}
+
+fn read_json(contents: &str) -> Result<PuzzleFile, String> {
+    let json: JsonPuzzle = serde_json::from_str(contents).map_err(|e| format!("Unable to parse JSON: {}", e))?;
+    let index = json.squares.iter().enumerate().map(|(i, square)| (square.id.as_str(), i)).collect::<HashMap<_, _>>();
+    let lookup = |id: &String| index.get(id.as_str()).copied().ok_or(format!("Unknown square: {}", id));
+
+    let hints = json.hints.iter().map(|hint| hint.iter().map(lookup).collect()).collect::<Result<_, _>>()?;
+    let squares = json.squares.iter().map(|square| Ok(Square {
+        id: square.id.clone(),
+        x: square.x,
+        y: square.y,
+        points: square.points.clone(),
+        neighbors: square.neighbors.iter().map(lookup).collect::<Result<_, String>>()?,
+        mine: square.mine,
+        secret: square.secret,
+        revealed: square.revealed,
+        flagged: square.flagged,
+    })).collect::<Result<_, String>>()?;
+
+    assemble(json.title, json.author, squares, hints)
+}
+
+/// Where every square is on a lattice with the given spacing, or `None` if some square isn't on one.
+fn lattice(gui: &PuzzleGui, spacing: (f32, f32)) -> Option<Vec<(i64, i64)>> {
+    let cells = gui.squares.iter().map(|square| ((square.x - gui.min_x) / spacing.0, (square.y - gui.min_y) / spacing.1)).collect::<Vec<_>>();
+    let on_lattice = cells.iter().all(|(x, y)| (x - x.round()).abs() < 0.05 && (y - y.round()).abs() < 0.05);
+    on_lattice.then(|| cells.iter().map(|(x, y)| (x.round() as i64, y.round() as i64)).collect())
+}
+
+/// The smallest gap between the squares' positions along one axis, or 1 if they're all in line.
+fn spacing(positions: impl Iterator<Item = f32>) -> f32 {
+    let mut positions = positions.collect::<Vec<_>>();
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let gap = positions.windows(2).map(|pair| pair[1] - pair[0]).filter(|gap| *gap > 0.001).fold(f32::MAX, f32::min);
+    if gap == f32::MAX { 1.0 } else { gap }
+}
+
+/// Checks that the squares' neighbours are exactly the squares at these offsets on the lattice.
+fn check_neighbors(puzzle: &Puzzle, cells: &[(i64, i64)], offsets: &[(i64, i64)]) -> bool {
+    let index = cells.iter().enumerate().map(|(i, &cell)| (cell, i)).collect::<HashMap<_, _>>();
+    index.len() == cells.len() && cells.iter().enumerate().all(|(i, &(x, y))| {
+        let expected = offsets.iter().filter_map(|(dx, dy)| index.get(&(x + dx, y + dy))).fold(Bits::zeroed(), |mut bits, &n| {
+            bits.set(n, true);
+            bits
+        });
+        expected == puzzle.neighbors[i]
+    })
+}
+
+fn square_points() -> Vec<(f32, f32)> {
+    let half = CELL_RADIUS * 0.9;
+    vec![(-half, -half), (half, -half), (half, half), (-half, half)]
+}
+
+fn read_text(contents: &str) -> Result<PuzzleFile, String> {
+    let mut comments = Vec::new();
+    let mut columns = Vec::new();
+    let mut rows = Vec::new();
+    for line in contents.lines() {
+        if let Some(comment) = line.strip_prefix('#') {
+            comments.push(comment.trim().to_string());
+        } else if let Some(list) = line.strip_prefix("columns:") {
+            for column in list.split_whitespace() {
+                columns.push(column.parse::<usize>().map_err(|_| format!("Not a column: {}", column))?);
+            }
+        } else {
+            rows.push(line);
+        }
+    }
+    let mut comments = comments.into_iter();
+    let title = comments.next().unwrap_or_else(|| String::from("Untitled"));
+    let author = comments.next().map(|author| author.strip_prefix("by ").map(String::from).unwrap_or(author));
+
+    let mut index = HashMap::new();
+    let mut squares = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let (mine, secret, revealed, flagged) = grid_square(c).ok_or(format!("Unexpected {} on line {}", c, y + 1))?;
+            index.insert((x as i64, y as i64), squares.len());
+            let size = CELL_RADIUS * 2.0;
+            squares.push(Square { id: format!("{}_{}", x, y), x: x as f32 * size, y: y as f32 * size, points: square_points(), neighbors: Vec::new(), mine, secret, revealed, flagged });
+        }
+    }
+    for (&(x, y), &i) in &index {
+        for (dx, dy) in KING_NEIGHBORS {
+            if let Some(&n) = index.get(&(x + dx, y + dy)) {
+                squares[i].neighbors.push(n);
+            }
+        }
+        squares[i].neighbors.sort_unstable();
+    }
+    let hints = columns.iter().map(|&column| {
+        let mut hint = index.iter().filter(|((x, _), _)| *x == column as i64).map(|(_, &i)| i).collect::<Vec<_>>();
+        hint.sort_unstable();
+        hint
+    }).collect();
+
+    assemble(title, author, squares, hints)
+}
+
+/// Whether a character in a text grid is a mine, has a secret number, is revealed and is flagged.
+fn grid_square(c: char) -> Option<(bool, bool, bool, bool)> {
+    match c {
+        '.' => Some((false, false, false, false)),
+        '*' => Some((true, false, false, false)),
+        'x' => Some((true, false, false, true)),
+        'o' => Some((false, false, true, false)),
+        '?' => Some((false, true, false, false)),
+        '!' => Some((false, true, true, false)),
+        _ => None,
+    }
+}
+
+/// The rows of a text grid, and the columns with hints.
+fn text_grid(file: &PuzzleFile) -> Result<(Vec<String>, Vec<i64>), String> {
+    let gui = &file.gui;
+    let puzzle = &file.puzzle;
+    let not_a_grid = || String::from("Only square grids where squares touch their eight neighbours can be written as a grid");
+    let spacings = (spacing(gui.squares.iter().map(|a| a.x)), spacing(gui.squares.iter().map(|a| a.y)));
+    let cells = lattice(gui, spacings).ok_or_else(not_a_grid)?;
+    if !check_neighbors(puzzle, &cells, &KING_NEIGHBORS) {
+        return Err(not_a_grid());
+    }
+
+    let mut columns = Vec::new();
+    for hint in &puzzle.hints {
+        let column = hint.iter_ones().next().map(|i| cells[i].0);
+        let whole_column = column.is_some_and(|column| cells.iter().enumerate().all(|(i, cell)| hint[i] == (cell.0 == column)));
+        match column {
+            Some(column) if whole_column => columns.push(column),
+            _ => return Err(String::from("Only hints on whole columns can be written as a grid")),
+        }
+    }
+
+    let width = cells.iter().map(|cell| cell.0).max().unwrap_or(0) as usize + 1;
+    let height = cells.iter().map(|cell| cell.1).max().unwrap_or(0) as usize + 1;
+    let mut grid = vec![vec![' '; width]; height];
+    for (i, &(x, y)) in cells.iter().enumerate() {
+        grid[y as usize][x as usize] = match (puzzle.mines[i], puzzle.unknowns[i], puzzle.revealed[i]) {
+            (true, _, _) if puzzle.flagged[i] => 'x',
+            (true, _, _) => '*',
+            (false, true, true) => '!',
+            (false, true, false) => '?',
+            (false, false, true) => 'o',
+            (false, false, false) => '.',
+        };
+    }
+
+    let rows = grid.into_iter().map(|row| row.into_iter().collect::<String>().trim_end().to_string()).collect();
+    Ok((rows, columns))
+}
+
+fn write_text(file: &PuzzleFile) -> Result<String, String> {
+    let (rows, columns) = text_grid(file)?;
+    let mut text = format!("# {}\n", file.title);
+    if let Some(author) = &file.author {
+        writeln!(text, "# by {}", author).unwrap();
+    }
+    for row in rows {
+        writeln!(text, "{}", row).unwrap();
+    }
+    if !columns.is_empty() {
+        writeln!(text, "columns: {}", columns.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" ")).unwrap();
+    }
+    Ok(text)
+}
+
+/// How cells on the grid of a TOML puzzle find their neighbours.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Adjacency {
+    /// Squares touching on their sides and corners.
+    #[default]
+    King,
+    /// Squares touching on their sides.
+    Rook,
+    /// Hexagons in columns, with odd columns half a cell lower.
+    Hex,
+    /// Only the neighbours each cell lists.
+    None,
+}
+
+impl Adjacency {
+    pub fn offsets(self, (x, _): (i64, i64)) -> Vec<(i64, i64)> {
+        match self {
+            Adjacency::King => KING_NEIGHBORS.to_vec(),
+            Adjacency::Rook => vec![(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Adjacency::Hex if x.rem_euclid(2) == 0 => vec![(0, -1), (0, 1), (-1, -1), (-1, 0), (1, -1), (1, 0)],
+            Adjacency::Hex => vec![(0, -1), (0, 1), (-1, 0), (-1, 1), (1, 0), (1, 1)],
+            Adjacency::None => Vec::new(),
+        }
+    }
+
+    pub fn position(self, (x, y): (i64, i64)) -> (f32, f32) {
+        match self {
+            Adjacency::Hex => {
+                let offset = if x.rem_euclid(2) == 1 { 0.5 } else { 0.0 };
+                (x as f32 * CELL_RADIUS * 1.5, (y as f32 + offset) * CELL_RADIUS * 3f32.sqrt())
+            }
+            _ => (x as f32 * CELL_RADIUS * 2.0, y as f32 * CELL_RADIUS * 2.0),
+        }
+    }
+
+    pub fn points(self) -> Vec<(f32, f32)> {
+        match self {
+            Adjacency::Hex => hexagon_points(),
+            _ => square_points(),
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlPuzzle {
+    title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(default)]
+    adjacency: Adjacency,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    grid: Option<String>,
+    /// IDs of cells to make mines, revealed, flagged or secret, besides the ones the grid and cells say.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mines: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    revealed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    flagged: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    secret: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hints: Vec<Vec<String>>,
+    /// Hints on every cell in a column or row of the grid.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    columns: Vec<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rows: Vec<i64>,
+    #[serde(default, rename = "cell", skip_serializing_if = "Vec::is_empty")]
+    cells: Vec<TomlCell>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlCell {
+    /// Defaults to `x_y` for a cell on the grid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// The cell's place on the grid, which decides its neighbours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    at: Option<(i64, i64)>,
+    /// Where the cell is drawn, if not at its place on the grid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pos: Option<(f32, f32)>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    mine: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    revealed: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    flagged: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    secret: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    neighbors: Option<Vec<String>>,
+}
+
+fn read_toml(contents: &str) -> Result<PuzzleFile, String> {
+    let toml: TomlPuzzle = toml::from_str(contents).map_err(|e| format!("Unable to parse TOML: {}", e))?;
+    let adjacency = toml.adjacency;
+
+    // Every cell, with its place on the grid and the neighbours it lists
+    let mut squares = Vec::new();
+    let mut places = Vec::new();
+    let mut listed_neighbors = Vec::new();
+    let mut add = |id: String, at: Option<(i64, i64)>, (x, y): (f32, f32), (mine, secret, revealed, flagged), neighbors: Option<Vec<String>>| {
+        squares.push(Square { id, x, y, points: adjacency.points(), neighbors: Vec::new(), mine, secret, revealed, flagged });
+        places.push(at);
+        listed_neighbors.push(neighbors);
+    };
+    for (y, row) in toml.grid.as_deref().unwrap_or_default().lines().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let flags = grid_square(c).ok_or(format!("Unexpected {} on line {} of the grid", c, y + 1))?;
+            let at = (x as i64, y as i64);
+            add(format!("{}_{}", x, y), Some(at), adjacency.position(at), flags, None);
+        }
+    }
+    for cell in toml.cells {
+        let at = cell.at;
+        let id = cell.id.or_else(|| at.map(|(x, y)| format!("{}_{}", x, y))).ok_or("A cell without at needs an id")?;
+        let pos = cell.pos.or_else(|| at.map(|at| adjacency.position(at))).ok_or(format!("Cell {} needs at or pos", id))?;
+        add(id, at, pos, (cell.mine, cell.secret, cell.revealed, cell.flagged), cell.neighbors);
+    }
+
+    let mut index = HashMap::new();
+    for (i, square) in squares.iter().enumerate() {
+        if index.insert(square.id.clone(), i).is_some() {
+            return Err(format!("Two cells are called {}", square.id));
+        }
+    }
+    let lookup = |id: &String| index.get(id).copied().ok_or(format!("Unknown cell: {}", id));
+    let mut grid = HashMap::new();
+    for (i, at) in places.iter().enumerate() {
+        if let Some((x, y)) = at {
+            if grid.insert((*x, *y), i).is_some() {
+                return Err(format!("Two cells are at {},{}", x, y));
+            }
+        }
+    }
+
+    for id in &toml.mines {
+        squares[lookup(id)?].mine = true;
+    }
+    for id in &toml.secret {
+        squares[lookup(id)?].secret = true;
+    }
+    for id in &toml.revealed {
+        squares[lookup(id)?].revealed = true;
+    }
+    for id in &toml.flagged {
+        let square = &mut squares[lookup(id)?];
+        square.mine = true;
+        square.flagged = true;
+    }
+    for (i, neighbors) in listed_neighbors.iter().enumerate() {
+        squares[i].neighbors = match (neighbors, places[i]) {
+            (Some(neighbors), _) => neighbors.iter().map(lookup).collect::<Result<_, _>>()?,
+            (None, Some((x, y))) => adjacency.offsets((x, y)).iter().filter_map(|(dx, dy)| grid.get(&(x + dx, y + dy)).copied()).collect(),
+            (None, None) => Vec::new(),
+        };
+        squares[i].neighbors.sort_unstable();
+    }
+
+    let mut hints = toml.hints.iter().map(|hint| hint.iter().map(lookup).collect()).collect::<Result<Vec<Vec<usize>>, _>>()?;
+    let lines = toml.columns.iter().map(|&column| ("column", column, 0)).chain(toml.rows.iter().map(|&row| ("row", row, 1)));
+    for (name, line, axis) in lines {
+        let mut hint = grid.iter().filter(|(at, _)| [at.0, at.1][axis] == line).map(|(_, &i)| i).collect::<Vec<_>>();
+        if hint.is_empty() {
+            return Err(format!("There are no cells in {} {}", name, line));
+        }
+        hint.sort_unstable();
+        hints.push(hint);
+    }
+
+    assemble(toml.title, toml.author, squares, hints)
+}
+
+/// Uses a grid where the puzzle has one, and otherwise lists every cell.
+fn write_toml(file: &PuzzleFile) -> Result<String, String> {
+    let puzzle = &file.puzzle;
+    let mut toml = TomlPuzzle {
+        title: file.title.clone(),
+        author: file.author.clone(),
+        adjacency: Adjacency::King,
+        grid: None,
+        mines: Vec::new(),
+        revealed: Vec::new(),
+        flagged: Vec::new(),
+        secret: Vec::new(),
+        hints: Vec::new(),
+        columns: Vec::new(),
+        rows: Vec::new(),
+        cells: Vec::new(),
+    };
+    match text_grid(file) {
+        Ok((rows, columns)) => {
+            toml.grid = Some(rows.join("\n") + "\n");
+            toml.columns = columns;
+        }
+        Err(_) => {
+            toml.adjacency = Adjacency::None;
+            toml.hints = puzzle.hints.iter().map(|hint| ids(file, hint)).collect();
+            toml.cells = file.gui.squares.iter().enumerate().map(|(i, square)| TomlCell {
+                id: Some(puzzle.id_labels[i].clone()),
+                at: None,
+                pos: Some((square.x, square.y)),
+                mine: puzzle.mines[i],
+                revealed: puzzle.revealed[i],
+                flagged: puzzle.flagged[i],
+                secret: puzzle.unknowns[i],
+                neighbors: Some(ids(file, &puzzle.neighbors[i])),
+            }).collect();
+        }
+    }
+    // Multiline strings for the grid, but lists on one line
+    let mut text = String::new();
+    let mut serializer = toml::Serializer::pretty(&mut text);
+    serializer.pretty_array(false);
+    toml.serialize(&mut serializer).map_err(|e| format!("Unable to write TOML: {}", e))?;
+    Ok(text)
+}
+
+/// Hexcells grids put each cell two rows above and below its vertical neighbours, and one row and
+/// column from its diagonal ones.
+const HEX_NEIGHBORS: [(i64, i64); 6] = [(0, -2), (0, 2), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// How far apart two cells on a Hexcells grid are.
+fn hex_distance(a: (i64, i64), b: (i64, i64)) -> i64 {
+    let dx = (a.0 - b.0).abs();
+    let dy = (a.1 - b.1).abs();
+    dx + (dy - dx).max(0) / 2
+}
+
+fn hexagon_points() -> Vec<(f32, f32)> {
+    (0..6).map(|corner| {
+        let angle = std::f32::consts::PI / 3.0 * corner as f32;
+        (CELL_RADIUS * 0.95 * angle.cos(), CELL_RADIUS * 0.95 * angle.sin())
+    }).collect()
+}
+
+/// Reads a level in the format Hexcells Infinite and SixCells share.  Blue cells are mines.  A
+/// revealed blue cell starts flagged, and its number, if shown, becomes a hint over the cells around
+/// it.  Columns become hints over the cells in their line.
+fn read_hexcells(contents: &str) -> Result<(PuzzleFile, Vec<String>), String> {
+    let mut lines = contents.lines();
+    if lines.next().map(str::trim) != Some(HEXCELLS_HEADER) {
+        return Err(format!("A Hexcells level starts with \"{}\"", HEXCELLS_HEADER));
+    }
+    let title = lines.next().unwrap_or_default().trim().to_string();
+    let author = Some(lines.next().unwrap_or_default().trim().to_string()).filter(|author| !author.is_empty());
+    let mut lost = Vec::new();
+    let custom_text = [lines.next(), lines.next()].iter().flatten().map(|line| line.trim()).filter(|line| !line.is_empty()).collect::<Vec<_>>();
+    if !custom_text.is_empty() {
+        lost.push(format!("The level's text: {}", custom_text.join(" ")));
+    }
+
+    let mut index = HashMap::new();
+    let mut squares = Vec::new();
+    let mut blue_numbers = Vec::new();
+    let mut columns = Vec::new();
+    for (y, line) in lines.take(HEXCELLS_SIZE).enumerate() {
+        let chars = line.chars().collect::<Vec<_>>();
+        for (x, pair) in chars.chunks(2).enumerate() {
+            let (kind, info) = (pair[0], pair.get(1).copied().unwrap_or('.'));
+            let cell = (x as i64, y as i64);
+            let (mine, revealed) = match kind {
+                '.' => continue,
+                'o' => (false, false),
+                'O' => (false, true),
+                'x' => (true, false),
+                'X' => (true, true),
+                '|' | '/' | '\\' => {
+                    columns.push((cell, kind));
+                    if matches!(info, 'c' | 'n') {
+                        lost.push(format!("Whether the mines under the column at {},{} are together", x, y));
+                    }
+                    continue;
+                }
+                _ => return Err(format!("Unexpected {} on line {}", kind, y + 6)),
+            };
+            match (mine, revealed, info) {
+                (false, _, 'c') | (false, _, 'n') => lost.push(format!("Whether the mines around {},{} are together", x, y)),
+                (true, true, '+') => blue_numbers.push(squares.len()),
+                (true, false, '+') => lost.push(format!("The number on {},{}, which only shows once it's marked", x, y)),
+                _ => {}
+            }
+            index.insert(cell, squares.len());
+            squares.push(Square {
+                id: format!("{}_{}", x, y),
+                x: x as f32 * CELL_RADIUS * 1.5,
+                y: y as f32 * CELL_RADIUS * 3f32.sqrt() / 2.0,
+                points: hexagon_points(),
+                neighbors: Vec::new(),
+                mine,
+                // A black cell without a number shows a question mark
+                secret: !mine && info == '.',
+                revealed: revealed && !mine,
+                flagged: revealed && mine,
+            });
+        }
+    }
+
+    let cells = index.iter().map(|(&cell, &i)| (i, cell)).collect::<HashMap<_, _>>();
+    for (&(x, y), &i) in &index {
+        squares[i].neighbors = HEX_NEIGHBORS.iter().filter_map(|(dx, dy)| index.get(&(x + dx, y + dy)).copied()).collect();
+        squares[i].neighbors.sort_unstable();
+    }
+
+    let mut hints = Vec::new();
+    for &i in &blue_numbers {
+        let mut hint = index.iter().filter(|(&cell, _)| (1..=2).contains(&hex_distance(cell, cells[&i]))).map(|(_, &n)| n).collect::<Vec<_>>();
+        hint.sort_unstable();
+        hints.push(hint);
+    }
+    for ((x, y), direction) in columns {
+        let step = match direction {
+            '|' => (0, 2),
+            '\\' => (1, 1),
+            _ => (-1, 1),
+        };
+        let mut hint = (1..HEXCELLS_SIZE as i64).filter_map(|k| index.get(&(x + step.0 * k, y + step.1 * k)).copied()).collect::<Vec<_>>();
+        if hint.is_empty() {
+            lost.push(format!("The column at {},{}, which has no cells under it", x, y));
+            continue;
+        }
+        hint.sort_unstable();
+        hints.push(hint);
+    }
+
+    assemble(title, author, squares, hints).map(|file| (file, lost))
+}
+
+fn write_hexcells(file: &PuzzleFile) -> Result<String, String> {
+    let gui = &file.gui;
+    let puzzle = &file.puzzle;
+    let not_a_grid = || String::from("Only hexagonal grids where cells touch their six neighbours can be written for Hexcells");
+    let spacings = (spacing(gui.squares.iter().map(|a| a.x)), spacing(gui.squares.iter().map(|a| a.y)));
+    let mut cells = lattice(gui, spacings).ok_or_else(not_a_grid)?;
+    // Leave room above and beside the cells for columns
+    cells.iter_mut().for_each(|cell| *cell = (cell.0 + 1, cell.1 + 2));
+    // Every cell's row and column have to add up to an even number
+    let parity = cells[0].0 + cells[0].1;
+    if cells.iter().any(|cell| (cell.0 + cell.1 - parity) % 2 != 0) {
+        return Err(not_a_grid());
+    }
+    if parity % 2 != 0 {
+        cells.iter_mut().for_each(|cell| cell.1 += 1);
+    }
+    if !check_neighbors(puzzle, &cells, &HEX_NEIGHBORS) {
+        return Err(not_a_grid());
+    }
+
+    let size = HEXCELLS_SIZE as i64;
+    if cells.iter().any(|&(x, y)| x >= size || y >= size) {
+        return Err(format!("Hexcells levels are at most {} cells across", HEXCELLS_SIZE));
+    }
+    let mut grid = vec![vec![['.', '.']; HEXCELLS_SIZE]; HEXCELLS_SIZE];
+    for (i, &(x, y)) in cells.iter().enumerate() {
+        grid[y as usize][x as usize] = match (puzzle.mines[i], puzzle.revealed[i]) {
+            (true, _) if puzzle.flagged[i] => ['X', '.'],
+            (true, _) => ['x', '.'],
+            (false, true) => ['O', if puzzle.unknowns[i] { '.' } else { '+' }],
+            (false, false) => ['o', if puzzle.unknowns[i] { '.' } else { '+' }],
+        };
+    }
+
+    let index = cells.iter().enumerate().map(|(i, &cell)| (cell, i)).collect::<HashMap<_, _>>();
+    for hint in &puzzle.hints {
+        // A hint on a single mine is a revealed blue cell too
+        if hint.count_ones() == 1 && hint.iter_ones().all(|i| puzzle.mines[i]) {
+            let (x, y) = cells[hint.iter_ones().next().unwrap()];
+            grid[y as usize][x as usize][0] = 'X';
+            continue;
+        }
+
+        // Otherwise it has to be a column, starting from an empty spot just before its first cell
+        let column = [('|', (0, 2)), ('\\', (1, 1)), ('/', (-1, 1))].iter().find_map(|&(direction, step)| {
+            let first = hint.iter_ones().map(|i| cells[i]).min_by_key(|&(_, y)| y)?;
+            let start = (first.0 - step.0, first.1 - step.1);
+            let in_grid = |(x, y): (i64, i64)| (0..size).contains(&x) && (0..size).contains(&y);
+            if !in_grid(start) || grid[start.1 as usize][start.0 as usize][0] != '.' {
+                return None;
+            }
+            let line = (1..size).map(|k| (start.0 + step.0 * k, start.1 + step.1 * k)).take_while(|&cell| in_grid(cell)).filter_map(|cell| index.get(&cell));
+            let covered = line.fold(Bits::zeroed(), |mut bits, &i| {
+                bits.set(i, true);
+                bits
+            });
+            (covered == *hint).then_some((start, direction))
+        });
+        match column {
+            Some(((x, y), direction)) => grid[y as usize][x as usize] = [direction, '.'],
+            None => return Err(String::from("A hint isn't a line of cells, so it can't be written as a Hexcells column")),
+        }
+    }
+
+    let mut text = format!("{}\n{}\n{}\n\n\n", HEXCELLS_HEADER, file.title, file.author.as_deref().unwrap_or_default());
+    for row in grid {
+        writeln!(text, "{}", row.iter().flatten().collect::<String>()).unwrap();
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::{BTreeMap, BTreeSet}, fs, path::Path, process};
+
+    use crate::core::Bits;
+
+    use super::{Format, PuzzleFile, lattice, parse, read, spacing, write};
+
+    /// Where a square is on its grid, counting from the top left square.
+    type Cell = (i64, i64);
+
+    /// What a puzzle says about each square and hint, by where the squares are rather than what
+    /// they're called, since the grid formats name squares after their place.
+    #[derive(PartialEq, Debug)]
+    struct Board {
+        /// Whether each square is a mine, secret, revealed and flagged, and the squares it counts.
+        squares: BTreeMap<Cell, (bool, bool, bool, bool, BTreeSet<Cell>)>,
+        hints: BTreeSet<BTreeSet<Cell>>,
+    }
+
+    fn cells(file: &PuzzleFile) -> Vec<Cell> {
+        let gui = &file.gui;
+        lattice(gui, (spacing(gui.squares.iter().map(|a| a.x)), spacing(gui.squares.iter().map(|a| a.y)))).expect("The squares aren't on a grid")
+    }
+
+    fn board(file: &PuzzleFile) -> Board {
+        let puzzle = &file.puzzle;
+        let cells = cells(file);
+        let on_cells = |bits: &Bits| bits.iter_ones().map(|i| cells[i]).collect::<BTreeSet<_>>();
+        Board {
+            squares: cells.iter().enumerate().map(|(i, &cell)| (cell, (puzzle.mines[i], puzzle.unknowns[i], puzzle.revealed[i], puzzle.flagged[i], on_cells(&puzzle.neighbors[i])))).collect(),
+            hints: puzzle.hints.iter().map(on_cells).collect(),
+        }
+    }
+
+    /// Reads XML the way the parser does, from a file of its own.
+    fn read_xml(xml: &str, name: &str) -> PuzzleFile {
+        let path = std::env::temp_dir().join(format!("tametsi-convert-{}-{}.xml", process::id(), name));
+        fs::write(&path, xml).unwrap();
+        let read = read(&path, Format::Xml);
+        fs::remove_file(&path).unwrap();
+        let (file, lost) = read.unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert!(lost.is_empty(), "{}: {:?}", name, lost);
+        file
+    }
+
+    /// The samples, each with its first mine flagged, since none of them start with flags.
+    fn samples() -> Vec<PuzzleFile> {
+        let folder = Path::new(env!("CARGO_MANIFEST_DIR")).join("samples");
+        let mut paths = fs::read_dir(folder).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+        paths.sort();
+        paths.iter().map(|path| {
+            let mut file = read(path, Format::Xml).unwrap().0;
+            let mine = file.puzzle.mines.first_one().expect("A sample has no mines");
+            file.puzzle.flagged.set(mine, true);
+            file
+        }).collect()
+    }
+
+    /// Writes each sample in `format`, reads it back and writes it as XML again, and checks the
+    /// squares and hints match the sample's after each.  Returns the sample and what came back.
+    fn round_trip(format: Format) -> Vec<(PuzzleFile, PuzzleFile)> {
+        samples().into_iter().map(|sample| {
+            let written = write(&sample, format).unwrap_or_else(|e| panic!("{}: {}", sample.title, e));
+            let (converted, lost) = parse(&written, format).unwrap_or_else(|e| panic!("{}: {}", sample.title, e));
+            assert!(lost.is_empty(), "{}: {:?}", sample.title, lost);
+            let back = read_xml(&write(&converted, Format::Xml).unwrap(), &format!("{}-{}", format.extension(), sample.title.replace(' ', "_")));
+            for file in [&converted, &back] {
+                assert_eq!(file.title, sample.title);
+                assert_eq!(file.author, sample.author);
+                assert_eq!(board(file), board(&sample), "{} through {}", sample.title, format.extension());
+            }
+            (sample, back)
+        }).collect()
+    }
+
+    /// Grids name each square after its column and row.
+    fn assert_named_by_place(file: &PuzzleFile) {
+        let names = cells(file).iter().map(|(x, y)| format!("{}_{}", x, y)).collect::<Vec<_>>();
+        assert_eq!(file.puzzle.id_labels, names, "{}", file.title);
+    }
+
+    #[test]
+    fn json_keeps_everything() {
+        for (sample, back) in round_trip(Format::Json) {
+            assert_eq!(back.puzzle.id_labels, sample.puzzle.id_labels);
+        }
+    }
+
+    #[test]
+    fn text_keeps_the_board() {
+        for (_, back) in round_trip(Format::Text) {
+            assert_named_by_place(&back);
+        }
+    }
+
+    #[test]
+    fn toml_keeps_the_board() {
+        for (_, back) in round_trip(Format::Toml) {
+            assert_named_by_place(&back);
+        }
+    }
+
+    #[test]
+    fn hexcells_keeps_the_board_and_reports_what_it_loses() {
+        // A column over the middle cells, a revealed cell saying its mines are together, a flagged
+        // mine and a mine whose number only shows once it's marked
+        let level = "Hexcells level v1\nHoneycomb\nSomeone\nA custom line\n\n..|.\nx.\n..Oc\no.\n..x+\n";
+        let (file, lost) = parse(level, Format::Hexcells).unwrap();
+        assert_eq!((file.title.as_str(), file.author.as_deref()), ("Honeycomb", Some("Someone")));
+        assert_eq!(lost.len(), 3, "{:?}", lost);
+        assert!(lost[0].contains("A custom line"), "{:?}", lost);
+        assert_eq!(file.puzzle.hints.len(), 1);
+
+        let back = read_xml(&write(&file, Format::Xml).unwrap(), "hexcells");
+        assert_eq!(back.puzzle.id_labels, file.puzzle.id_labels);
+        assert_eq!(board(&back), board(&file));
+        let (again, lost) = parse(&write(&back, Format::Hexcells).unwrap(), Format::Hexcells).unwrap();
+        assert!(lost.is_empty(), "{:?}", lost);
+        assert_eq!(board(&again), board(&file));
+
+        // The samples are square grids
+        assert!(write(&samples()[0], Format::Hexcells).is_err());
+    }
+}
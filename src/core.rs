@@ -1,6 +1,10 @@
 
+use std::{fmt, hash::{Hash, Hasher}};
+
 use bitvec::prelude::*;
 
+use crate::ascii::Layout;
+
 pub type Bits = BitArray<Lsb0, [usize; 7]>;
 
 #[derive(Clone)]
@@ -9,45 +13,212 @@ pub struct Puzzle {
     pub mines: Bits,
     pub unknowns: Bits,
     pub revealed: Bits,
+    /// Mines already flagged at the start, as in a saved game.
+    pub flagged: Bits,
     pub hints: Vec<Bits>,
+    /// The ID each square has in the puzzle file, so output can name squares the way the author did.
+    pub id_labels: Vec<String>,
+    /// Where each square goes when the board is drawn as text.
+    pub layout: Layout,
 }
 
+#[derive(Clone)]
 pub struct PuzzleGui {
     pub min_x: f32,
     pub min_y: f32,
     pub max_x: f32,
     pub max_y: f32,
-    pub squares: Vec<SquareDimensions>
+    pub squares: Vec<SquareDimensions>,
+    /// Problems with squares in the file.  Those squares are still loaded, with made up shapes and
+    /// positions where theirs couldn't be read.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The hints, by index in the puzzle's, that the game shows as colored groups of squares rather
+    /// than as counts at the end of a line.
+    pub color_hints: Vec<usize>,
 }
 
+#[derive(Clone)]
 pub struct SquareDimensions {
     pub x: f32,
     pub y: f32,
     pub points: Vec<(f32, f32)>,
 }
 
+/// Something wrong with one node of a puzzle file.
+#[derive(Clone)]
+pub struct Diagnostic {
+    /// The ID of the node, or of the missing square a hint refers to.
+    pub node: String,
+    pub problem: String,
+    /// Where the node starts in the file's text.
+    pub offset: usize,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at {}): {}", self.node, self.offset, self.problem)
+    }
+}
+
 impl Puzzle {
+    /// A puzzle with no hints, no hidden numbers and squares named by their index, for building one
+    /// without a puzzle file.  `neighbors` holds the squares each square's number counts, and there
+    /// can be as many squares as `Bits` holds.
+    pub fn new(neighbors: Vec<Bits>, mines: Bits, revealed: Bits) -> Puzzle {
+        let id_labels = (0..neighbors.len()).map(|square| square.to_string()).collect();
+        Puzzle { neighbors, mines, unknowns: Bits::zeroed(), revealed, flagged: Bits::zeroed(), hints: Vec::new(), id_labels, layout: Layout::default() }
+    }
+
     #[inline]
     pub fn size(&self) -> usize {
         self.neighbors.len()
     }
+
+    /// The number of mines a player is told are in a group of squares: a hint, the neighborhood
+    /// of a revealed square, or the whole board.
+    #[inline]
+    pub fn displayed_count(&self, bits: Bits) -> usize {
+        (bits & self.mines).count_ones()
+    }
+
+    /// The board drawn as text, with `X` on the squares in `bits`.
+    pub fn draw(&self, bits: Bits) -> String {
+        self.layout.draw(self.size(), |i| if bits[i] { 'X' } else { '.' })
+    }
+
+    /// The IDs of some squares, comma separated.
+    pub fn labels(&self, bits: Bits) -> String {
+        bits.iter_ones().map(|i| self.id_labels[i].as_str()).collect::<Vec<_>>().join(", ")
+    }
+
+    /// The squares in `cells` as a puzzle of their own, to cut a small case out of a big one.
+    /// Squares keep their IDs and lose their neighbors outside.  A number that counted squares
+    /// outside is hidden, and if it was showing it becomes a hint on the neighbors left, so what
+    /// crossed the edge is still told.  Hints only keep their squares inside.
+    pub fn extract(&self, cells: Bits) -> Puzzle {
+        let kept = cells.iter_ones().take_while(|&square| square < self.size()).collect::<Vec<_>>();
+        let crop = |bits: Bits| {
+            let mut cropped = Bits::zeroed();
+            for (square, &old) in kept.iter().enumerate() {
+                cropped.set(square, bits[old]);
+            }
+            cropped
+        };
+
+        let mut hints = self.hints.iter().map(|&hint| crop(hint)).filter(|hint| hint.any()).collect::<Vec<_>>();
+        let mut unknowns = crop(self.unknowns);
+        for (square, &old) in kept.iter().enumerate() {
+            let neighbors = self.neighbors[old];
+            if self.unknowns[old] || (neighbors & !cells).not_any() {
+                continue;
+            }
+            unknowns.set(square, true);
+            if self.revealed[old] && (neighbors & cells).any() {
+                hints.push(crop(neighbors));
+            }
+        }
+
+        Puzzle {
+            neighbors: kept.iter().map(|&old| crop(self.neighbors[old])).collect(),
+            mines: crop(self.mines),
+            unknowns,
+            revealed: crop(self.revealed),
+            flagged: crop(self.flagged),
+            hints,
+            id_labels: kept.iter().map(|&old| self.id_labels[old].clone()).collect(),
+            layout: self.layout.extract(&kept),
+        }
+    }
+
+    /// This puzzle and `other` as one board, with `other`'s squares after these and none of them
+    /// neighbors, so pieces checked on their own can be put together into a bigger puzzle.
+    /// `offset` is where `other` goes in text drawings, in columns and rows.  A square of `other`
+    /// whose ID is already taken gets a number after it.  Fails if there are too many squares
+    /// together for one board.
+    pub fn compose(&self, other: &Puzzle, offset: (usize, usize)) -> Result<Puzzle, String> {
+        let size = self.size();
+        if size + other.size() > Bits::zeroed().len() {
+            return Err(format!("The puzzles have {} squares together, but at most {} are supported", size + other.size(), Bits::zeroed().len()));
+        }
+        let shift = |bits: Bits| {
+            let mut shifted = Bits::zeroed();
+            for square in bits.iter_ones() {
+                shifted.set(square + size, true);
+            }
+            shifted
+        };
+        let join = |mine: Bits, theirs: Bits| mine | shift(theirs);
+
+        let mut id_labels = self.id_labels.clone();
+        for label in &other.id_labels {
+            let mut unique = label.clone();
+            let mut n = 2;
+            while id_labels.contains(&unique) {
+                unique = format!("{}_{}", label, n);
+                n += 1;
+            }
+            id_labels.push(unique);
+        }
+
+        Ok(Puzzle {
+            neighbors: self.neighbors.iter().copied().chain(other.neighbors.iter().map(|&neighbors| shift(neighbors))).collect(),
+            mines: join(self.mines, other.mines),
+            unknowns: join(self.unknowns, other.unknowns),
+            revealed: join(self.revealed, other.revealed),
+            flagged: join(self.flagged, other.flagged),
+            hints: self.hints.iter().copied().chain(other.hints.iter().map(|&hint| shift(hint))).collect(),
+            id_labels,
+            layout: self.layout.compose(size, &other.layout, other.size(), offset),
+        })
+    }
+}
+
+impl PuzzleGui {
+    /// The shapes of the squares in `cells`, to go with `puzzle.extract(cells)`.
+    pub fn extract(&self, puzzle: &Puzzle, cells: Bits) -> PuzzleGui {
+        let squares = cells.iter_ones().take_while(|&square| square < self.squares.len()).map(|square| self.squares[square].clone()).collect::<Vec<SquareDimensions>>();
+        // Hints left with no squares are dropped, so the rest move down
+        let color_hints = puzzle.hints.iter().enumerate().filter(|&(_, &hint)| (hint & cells).any()).enumerate()
+            .filter(|(_, (old, _))| self.color_hints.contains(old))
+            .map(|(index, _)| index)
+            .collect();
+        PuzzleGui {
+            min_x: squares.iter().map(|a| a.x).fold(f32::MAX, f32::min),
+            min_y: squares.iter().map(|a| a.y).fold(f32::MAX, f32::min),
+            max_x: squares.iter().map(|a| a.x).fold(f32::MIN, f32::max),
+            max_y: squares.iter().map(|a| a.y).fold(f32::MIN, f32::max),
+            squares,
+            diagnostics: Vec::new(),
+            color_hints,
+        }
+    }
+
+    /// The shapes of both boards, with `other`'s moved by `offset`, to go with
+    /// `puzzle.compose(..)`.
+    pub fn compose(&self, puzzle: &Puzzle, other: &PuzzleGui, offset: (f32, f32)) -> PuzzleGui {
+        let moved = other.squares.iter().map(|square| SquareDimensions { x: square.x + offset.0, y: square.y + offset.1, points: square.points.clone() });
+        let squares = self.squares.iter().cloned().chain(moved).collect::<Vec<_>>();
+        PuzzleGui {
+            min_x: squares.iter().map(|a| a.x).fold(f32::MAX, f32::min),
+            min_y: squares.iter().map(|a| a.y).fold(f32::MAX, f32::min),
+            max_x: squares.iter().map(|a| a.x).fold(f32::MIN, f32::max),
+            max_y: squares.iter().map(|a| a.y).fold(f32::MIN, f32::max),
+            squares,
+            diagnostics: Vec::new(),
+            color_hints: self.color_hints.iter().copied().chain(other.color_hints.iter().map(|&hint| hint + puzzle.hints.len())).collect(),
+        }
+    }
 }
 
 impl ToString for Puzzle {
     fn to_string(&self) -> String {
-        let mut line = String::new();
-        line.push('[');
-        for i in 0..self.size() {
-            line.push(if self.mines[i] {
-                '*'
-            } else if self.unknowns[i] {
-                '?'
-            } else {
-                ' '
-            });
-        }
-        line.push(']');
-        line
+        self.layout.draw(self.size(), |i| if self.mines[i] {
+            '*'
+        } else if self.unknowns[i] {
+            '?'
+        } else {
+            '.'
+        })
     }
 }
 
@@ -63,4 +234,52 @@ pub fn bits_to_string(bits: Bits, len: usize) -> String {
     }
     line.push(']');
     line
-}
\ No newline at end of file
+}
+
+/// A 64 bit FNV-1a hasher.  Unlike `DefaultHasher`, its output is stable across Rust versions, so
+/// it can be used for hashes that are saved to disk.
+pub struct Fingerprint(u64);
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Fingerprint(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fingerprint {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// The words of `bits` folded into one.  `Bits` hashes itself a bit at a time, 448 writes into the
+/// hasher, so maps in hot loops hash this instead.
+pub fn bits_hash(bits: Bits) -> u64 {
+    bits.as_raw_slice().iter().fold(0, |hash: u64, &word| (hash.rotate_left(5) ^ word as u64).wrapping_mul(0x517c_c1b7_2722_0a95))
+}
+
+/// A set of squares as a map key, hashed by its [`bits_hash`], which is worked out once.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BitsKey {
+    hash: u64,
+    pub bits: Bits,
+}
+
+impl From<Bits> for BitsKey {
+    fn from(bits: Bits) -> BitsKey {
+        BitsKey { hash: bits_hash(bits), bits }
+    }
+}
+
+impl Hash for BitsKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
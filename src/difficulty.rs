@@ -0,0 +1,140 @@
+use crate::solver::SolverStats;
+
+/// The calibration set shipped with the crate.
+pub const CALIBRATION: &str = include_str!("../fixtures/difficulty_calibration.csv");
+
+/// Estimates how hard a puzzle is for a person from the work the solver did on it.  Each weight is
+/// the cost of one unit of effort of that kind.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DifficultyModel {
+    /// Per square looked at while comparing constraints.
+    pub scan_cost: f64,
+    /// Per constraint that had to be kept in mind at the busiest point.
+    pub memory_cost: f64,
+    /// Per crossing needed to reach a deduction, summed over all deductions.
+    pub depth_cost: f64,
+    /// Per step of the deepest single deduction.
+    pub max_depth_cost: f64,
+    /// Per guess.
+    pub guess_cost: f64,
+}
+
+impl Default for DifficultyModel {
+    fn default() -> Self {
+        DifficultyModel {
+            scan_cost: 0.01,
+            memory_cost: 0.5,
+            depth_cost: 1.0,
+            max_depth_cost: 5.0,
+            guess_cost: 25.0,
+        }
+    }
+}
+
+impl DifficultyModel {
+    pub fn score(&self, stats: &SolverStats) -> f64 {
+        self.breakdown(stats).iter().map(|(_, cost)| cost).sum()
+    }
+
+    /// What each kind of effort adds to the score.
+    pub fn breakdown(&self, stats: &SolverStats) -> [(&'static str, f64); 5] {
+        [
+            ("Scanning", self.scan_cost * stats.cells_scanned as f64),
+            ("Memory", self.memory_cost * stats.peak_unsolved_constraints as f64),
+            ("Depth", self.depth_cost * stats.total_depth as f64),
+            ("Deepest deduction", self.max_depth_cost * stats.max_depth as f64),
+            ("Guessing", self.guess_cost * stats.guesses as f64),
+        ]
+    }
+
+    fn weights_mut(&mut self) -> [&mut f64; 5] {
+        [&mut self.scan_cost, &mut self.memory_cost, &mut self.depth_cost, &mut self.max_depth_cost, &mut self.guess_cost]
+    }
+
+    /// Adjusts the weights so that scores rank `samples` (solver stats and community rank) as
+    /// closely as possible, by repeatedly scaling one weight at a time while that improves the
+    /// rank correlation.
+    pub fn calibrate(&self, samples: &[(SolverStats, usize)]) -> DifficultyModel {
+        let ranks = samples.iter().map(|(_, rank)| *rank as f64).collect::<Vec<_>>();
+        let fitness = |model: &DifficultyModel| {
+            let scores = samples.iter().map(|(stats, _)| model.score(stats)).collect::<Vec<_>>();
+            rank_correlation(&scores, &ranks)
+        };
+
+        let mut best = *self;
+        let mut best_fitness = fitness(&best);
+        for _ in 0..20 {
+            let mut improved = false;
+            for weight in 0..5 {
+                for &factor in &[0.5, 2.0] {
+                    let mut candidate = best;
+                    *candidate.weights_mut()[weight] *= factor;
+                    let candidate_fitness = fitness(&candidate);
+                    if candidate_fitness > best_fitness {
+                        best = candidate;
+                        best_fitness = candidate_fitness;
+                        improved = true;
+                    }
+                }
+            }
+
+            if !improved {
+                break;
+            }
+        }
+
+        best
+    }
+}
+
+/// Reads a calibration set: `title,rank` rows, with `#` comments and a header row.
+pub fn parse_calibration(contents: &str) -> Result<Vec<(String, usize)>, String> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && *line != "title,rank")
+        .map(|line| {
+            let (title, rank) = line.rsplit_once(',').ok_or(format!("Expected title,rank: {}", line))?;
+            let rank = rank.trim().parse().map_err(|_| format!("Invalid rank: {}", line))?;
+            Ok((title.trim().to_string(), rank))
+        })
+        .collect()
+}
+
+/// Spearman's rank correlation: 1 when `a` and `b` are in the same order, -1 when reversed.
+pub fn rank_correlation(a: &[f64], b: &[f64]) -> f64 {
+    let (a, b) = (ranks(a), ranks(b));
+    let n = a.len() as f64;
+    if a.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = (n - 1.0) / 2.0;
+    let covariance = a.iter().zip(&b).map(|(x, y)| (x - mean) * (y - mean)).sum::<f64>();
+    let variance_a = a.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+    let variance_b = b.iter().map(|y| (y - mean).powi(2)).sum::<f64>();
+    if variance_a == 0.0 || variance_b == 0.0 {
+        0.0
+    } else {
+        covariance / (variance_a * variance_b).sqrt()
+    }
+}
+
+/// The position of each value when sorted, with ties sharing their average position.
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| values[i].partial_cmp(&values[j]).unwrap());
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end + 1 < order.len() && values[order[end + 1]] == values[order[start]] {
+            end += 1;
+        }
+        for &i in &order[start..=end] {
+            ranks[i] = (start + end) as f64 / 2.0;
+        }
+        start = end + 1;
+    }
+    ranks
+}
@@ -0,0 +1,961 @@
+use std::{any::Any, collections::{HashSet, VecDeque}, panic::{self, AssertUnwindSafe}, path::PathBuf, sync::{Arc, Mutex, mpsc::{self, Receiver, Sender, SyncSender, TryRecvError}}, thread, time::{Duration, Instant}};
+
+use crate::{cache::SolveCache, convert::PuzzleFile, history::{History, HistoryEntry}, core::{Bits, PuzzleGui}, export::{self, GraphFormat}, filter::ConstraintFilter, generator::{self, Suggestion}, parser::{BUNDLE_EXTENSION, Bundle, ParseMode, Parser, PuzzleListing}, race::{PlayerMove, SolverRun}, report::{Badge, SolveReport}, solver::{Action, Alternatives, CancelToken, Constraint, MemoryReport, Provable, PuzzleState, SolveOutcome, Solver, SolverConfig, SolverStats, StepResult, Uniqueness}, trace::Trace};
+
+
+/// What the GUI asks of the engine.  Commands are handled in the order they're sent, and most
+/// answer with one or more updates.  Anything but `Run` stops a running puzzle.
+#[derive(PartialEq, Eq)]
+pub enum Command {
+    /// Step the shown puzzle until it finishes, gets stuck or is stopped.
+    Run,
+    /// Show a puzzle, picking up where it was left if it's been loaded with these settings before.
+    Load(PuzzleListing, SolverConfig, ParseMode),
+    /// Take one step of the shown puzzle.
+    Step,
+    /// Stop running.  Sent through `EngineHandle::send`, this also cuts short the step under way.
+    Stop,
+    /// Check whether the shown puzzle has only one layout of mines.
+    VerifyUnique,
+    /// List other layouts of mines that fit the shown puzzle.
+    FindAlternatives,
+    /// Reveal or flag a square by hand, as a player would.  Refused if it isn't safe or a mine.
+    Play(usize, Action),
+    /// Start racing the solver on the shown puzzle from where it is, or stop.
+    Race(bool),
+    /// Follow the constraints on a square, or stop following them.
+    Inspect(Option<usize>),
+    /// Start or stop sending every unsolved constraint after each step.
+    WatchConstraints(bool),
+    /// Start or stop sending the unsolved constraints that match a filter after each step.
+    Filter(Option<String>),
+    /// Solve these puzzles in the background, spread over the queue's workers.
+    Queue(Vec<PuzzleListing>, SolverConfig),
+    /// Stop a queued solve, or skip it if it hasn't started.
+    CancelJob(usize),
+    /// Move up to this many of the shown puzzle's mines, picked with this seed, then add hints until
+    /// it can be solved without guessing.
+    Repair(usize, u64),
+    /// Solve the shown puzzle again with these settings, from the start if true or else from where
+    /// it is.
+    Reconfigure(SolverConfig, bool),
+    /// Look for single hints or revealed squares that would let the shown puzzle be solved.
+    SuggestFixes,
+    /// Find every square that can be proven from the latest step, without stepping.
+    ProveCurrent,
+    /// Write the unsolved constraints after the latest step to a file in the current folder.
+    ExportGraph(GraphFormat),
+    /// Write the steps taken so far to a JSON file in the current folder.
+    ExportSteps,
+    /// Save the shown puzzle with the steps taken so far as a bundle in the current folder.
+    SaveBundle,
+    /// Send every solve in the history.
+    History,
+    /// Pause running puzzles loaded from now on, and the shown one, when their unsolved constraints
+    /// pass this many, or never.
+    WarnAt(Option<usize>),
+    /// Carry on running the shown puzzle after a warning, warning it again at this many unsolved
+    /// constraints or never.
+    Resume(Option<usize>),
+    /// Reveal and flag every square of the shown puzzle a search of the layouts can prove, as one
+    /// step.
+    ProveRest,
+}
+
+/// What the engine tells the GUI.
+pub enum Update {
+    PuzzleListing(Vec<PuzzleListing>),
+    NewPuzzle(PuzzleListing, PuzzleState, PuzzleGui),
+    /// The state after a step, what the step did and how long it took.
+    Step(PuzzleState, StepResult, Duration),
+    Stats(SolverStats),
+    Uniqueness(Uniqueness),
+    Alternatives(Alternatives),
+    /// The report of the last complete solve of this puzzle with these settings.
+    Report(SolveReport),
+    /// The solver's current constraints on the inspected square.
+    Constraints(Vec<Constraint>),
+    Unsolved(Vec<Constraint>),
+    /// The unsolved constraints matching the filter, or why it couldn't be parsed.  `None` once the
+    /// filter is cleared.
+    Filtered(Option<Result<Vec<Constraint>, String>>),
+    /// The badge of the puzzle at this index in the listing.
+    Badge(usize, Badge),
+    /// A puzzle was queued as the job with this number, counting from 0.
+    JobQueued(usize, String),
+    /// How a queued job is getting on.
+    Job(usize, JobStatus),
+    /// A queued puzzle has been solved.
+    Queued(PuzzleListing, SolveReport),
+    /// Single changes that would let the solver finish the shown puzzle, or `None` if it already
+    /// can.
+    Suggestions(Option<Vec<Suggestion>>),
+    /// The shown puzzle was repaired with these changes, and is sent again as a new puzzle.
+    Repaired(Vec<String>),
+    /// Every solve in the history, oldest first.
+    History(Vec<HistoryEntry>),
+    /// The squares that can be proven after the latest step.
+    Provable(Provable),
+    /// The squares whose unsolved constraints changed in the step just sent.
+    Ripple(Bits),
+    /// The player made a move in a race.
+    RaceMove(PlayerMove),
+    /// The solver has finished its side of a race.
+    RaceSolver(SolverRun),
+    /// The constraint graph was written to this file.
+    Exported(String),
+    /// Something went wrong in the engine.  Names the puzzle involved.
+    Error(String),
+    /// The shown puzzle was paused so the user can decide how to go on.
+    Warning(String),
+    /// How hard the engine is working on the shown puzzle.
+    Resources(EngineResources),
+    /// The engine panicked, with why.  It does nothing more until it's restarted.
+    EngineCrashed(String),
+}
+
+/// Where a queued solve is up to.
+#[derive(Clone, PartialEq, Debug)]
+pub enum JobStatus {
+    Waiting,
+    /// Squares resolved so far, out of all of them.
+    Running(usize, usize),
+    Finished(SolveOutcome),
+    /// The solve panicked, with why.
+    Failed(String),
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_done(&self) -> bool {
+        matches!(self, JobStatus::Finished(_) | JobStatus::Failed(_) | JobStatus::Cancelled)
+    }
+}
+
+/// A queued solve waiting for a worker.
+struct QueuedJob {
+    id: usize,
+    listing: PuzzleListing,
+    config: SolverConfig,
+    cancelled: CancelToken,
+}
+
+/// How hard the engine is working, for the resource overlay.
+#[derive(Clone, Copy, Debug)]
+pub struct EngineResources {
+    pub steps_per_second: f64,
+    pub memory: MemoryReport,
+}
+
+/// How often the engine reports its resources while running.
+const RESOURCE_INTERVAL: f64 = 0.5;
+
+/// How often a queued solve reports its progress, in seconds.
+const JOB_PROGRESS_INTERVAL: f64 = 0.1;
+
+/// How many alternative layouts are listed when a puzzle is ambiguous.
+const MAX_ALTERNATIVES: usize = 16;
+
+/// How many updates can wait for the GUI before the engine waits for it to catch up.  Also the most
+/// the GUI takes in a frame.
+const UPDATE_BOUND: usize = 256;
+
+/// How many puzzles the engine keeps in progress, to pick up where they were when returned to.
+const MAX_SESSIONS: usize = 8;
+
+/// A loaded puzzle and everything done to it so far.
+struct Session {
+    listing: PuzzleListing,
+    config: SolverConfig,
+    mode: ParseMode,
+    solver: Solver,
+    gui: PuzzleGui,
+    start: PuzzleState,
+    /// Each step, with the solver's stats after it, the squares whose constraints it changed and how
+    /// long it took.
+    history: Vec<(PuzzleState, StepResult, SolverStats, Bits, Duration)>,
+    /// The unsolved constraints after the latest step, to compare the next step's with.
+    constraints: HashSet<Constraint>,
+    /// Where the puzzle's report is saved.
+    key: Option<String>,
+    /// When a race against the solver started, while one is on.
+    race: Option<Instant>,
+    /// How many unsolved constraints running can reach before it's paused with a warning.
+    warn_at: Option<usize>,
+}
+
+/// Where the engine's updates go: over a bounded channel to another thread, or onto a queue the
+/// GUI empties itself.
+#[derive(Clone)]
+enum UpdateSender {
+    Channel(SyncSender<Update>),
+    Local(Arc<Mutex<VecDeque<Update>>>),
+}
+
+impl UpdateSender {
+    /// Waits while the channel is full.  Fails once the GUI has closed.
+    fn send(&self, update: Update) -> Result<(), ()> {
+        match self {
+            UpdateSender::Channel(send) => send.send(update).map_err(|_| ()),
+            UpdateSender::Local(queue) => {
+                queue.lock().unwrap().push_back(update);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Loads, steps and analyses puzzles for the GUI, one command at a time.
+struct Engine {
+    send: UpdateSender,
+    /// Where queued solves go for the workers, or `None` if they're solved as they're queued.
+    queue: Option<Sender<QueuedJob>>,
+    /// Cancels each queued job, by its number.
+    jobs: Vec<CancelToken>,
+    cache: Option<SolveCache>,
+    history: Option<History>,
+    /// Puzzles that have been loaded, most recent first.  The first is the one being shown.
+    sessions: VecDeque<Session>,
+    inspected: Option<usize>,
+    watching: bool,
+    filter: Option<ConstraintFilter>,
+    running: bool,
+    warn_at: Option<usize>,
+    /// Steps since the resources were last reported, and when that was.
+    resource_steps: usize,
+    resource_time: Instant,
+    /// Shared with the shown puzzles' solvers, and cancelled by the handle to stop one mid-step.
+    cancel: CancelToken,
+    /// Whether races and queued solves get threads of their own.
+    threaded: bool,
+    /// Puzzles whose badges are still to be worked out, when no thread is doing it.
+    badges: VecDeque<(usize, PuzzleListing)>,
+}
+
+impl Engine {
+    /// Lists the puzzles in `folders`, or in the Steam install if there are none, and starts
+    /// working out their badges.
+    fn new(send: UpdateSender, folders: Vec<PathBuf>, cancel: CancelToken, threaded: bool) -> Engine {
+        let parser = Parser::from_folders(folders);
+        let puzzles = parser.read_all_puzzles();
+        send.send(Update::PuzzleListing(puzzles.clone())).unwrap();
+        let history = History::open();
+        let mut badges = VecDeque::new();
+        let mut queue = None;
+        if threaded {
+            let badge_send = send.clone();
+            thread::spawn(move || find_badges(puzzles, badge_send));
+            let (queue_send, queued) = mpsc::channel();
+            let queued = Arc::new(Mutex::new(queued));
+            // One core is left for the GUI and the shown puzzle
+            let workers = thread::available_parallelism().map_or(1, |cores| cores.get().saturating_sub(1).max(1));
+            for _ in 0..workers {
+                let queued = Arc::clone(&queued);
+                let queue_send = send.clone();
+                let queue_history = history.clone();
+                thread::spawn(move || solve_queue(queued, queue_send, queue_history));
+            }
+            queue = Some(queue_send);
+        } else {
+            badges.extend(puzzles.into_iter().enumerate());
+        }
+
+        Engine {
+            send,
+            queue,
+            jobs: Vec::new(),
+            cache: SolveCache::open(),
+            history,
+            sessions: VecDeque::new(),
+            inspected: None,
+            watching: false,
+            filter: None,
+            running: false,
+            warn_at: None,
+            resource_steps: 0,
+            resource_time: Instant::now(),
+            cancel,
+            threaded,
+            badges,
+        }
+    }
+
+    /// Handles commands until the GUI closes.
+    fn run(mut self, recieve: Receiver<Command>) {
+        loop {
+            let command = if self.running {
+                match recieve.try_recv() {
+                    Ok(command) => command,
+                    Err(TryRecvError::Empty) => Command::Run,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            } else {
+                match recieve.recv() {
+                    Ok(command) => command,
+                    Err(_) => return,
+                }
+            };
+            self.handle(command);
+        }
+    }
+
+    /// Does what there is to do when no command is waiting: a step if running, or else one of the
+    /// badges left.
+    fn idle(&mut self) {
+        if self.running {
+            self.handle(Command::Run);
+        } else if let Some((index, listing)) = self.badges.pop_front() {
+            self.send.send(Update::Badge(index, find_badge(&listing, self.cache.as_ref()))).unwrap();
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        self.running = command == Command::Run;
+        let Engine { send, queue, jobs, cache, history, sessions, inspected, watching, filter, running, warn_at, resource_steps, resource_time, cancel, threaded, .. } = self;
+
+        match command {
+            Command::Load(listing, config, mode) => {
+                // A bundle is solved the way it was saved, up to where it was saved
+                let bundle = listing.bundle();
+                let config = bundle.as_ref().and_then(|bundle| bundle.config).unwrap_or(config);
+                let mut session = match sessions.iter().position(|session| session.listing == listing && session.config == config && session.mode == mode) {
+                    Some(index) => sessions.remove(index).unwrap(),
+                    None => {
+                        // The parser and solver panic on bad puzzles
+                        let loaded = panic::catch_unwind(|| {
+                            listing.read_checked(mode).map(|(puzzle, gui)| (Solver::new(puzzle, config), gui))
+                        });
+                        let (solver, gui) = match loaded {
+                            Ok(Ok(loaded)) => loaded,
+                            Ok(Err(problems)) if mode == ParseMode::Strict => {
+                                send.send(Update::Error(format!("{}: rejected by the strict parser:\n{}", listing.name, problems))).unwrap();
+                                return;
+                            }
+                            Ok(Err(problem)) => {
+                                send.send(Update::Error(format!("{}: unable to load: {}", listing.name, problem))).unwrap();
+                                return;
+                            }
+                            Err(payload) => {
+                                send.send(Update::Error(format!("{}: unable to load: {}", listing.name, panic_message(payload)))).unwrap();
+                                return;
+                            }
+                        };
+                        Session {
+                            constraints: solver.unsolved_constraints().into_iter().collect(),
+                            key: listing.contents().ok().map(|contents| SolveCache::key(&contents, &config, false)),
+                            start: solver.puzzle.clone(),
+                            listing: listing.clone(),
+                            config,
+                            mode,
+                            solver,
+                            gui,
+                            history: Vec::new(),
+                            race: None,
+                            warn_at: *warn_at,
+                        }
+                    }
+                };
+                session.solver.set_cancel(cancel.clone());
+
+                // Replay the session so the GUI has its steps again
+                send.send(Update::NewPuzzle(listing.clone(), session.start.clone(), session.gui.clone())).unwrap();
+                for (state, result, stats, ripple, took) in &session.history {
+                    send.send(Update::Step(state.clone(), result.clone(), *took)).unwrap();
+                    send.send(Update::Stats(stats.clone())).unwrap();
+                    send.send(Update::Ripple(*ripple)).unwrap();
+                }
+                if let Some(replay) = bundle.and_then(|bundle| bundle.replay).filter(|_| session.history.is_empty()) {
+                    let mut resolving = 0;
+                    while resolving < replay.steps.len() {
+                        let before = session.solver.puzzle.revealed | session.solver.puzzle.flagged;
+                        let started = Instant::now();
+                        let response = session.solver.step();
+                        if ((session.solver.puzzle.revealed | session.solver.puzzle.flagged) & !before).any() {
+                            resolving += 1;
+                        }
+                        let done = SolveOutcome::from_step(&response).is_some();
+                        publish_step(send, &mut session, response, started.elapsed(), None, false, None);
+                        if done {
+                            break;
+                        }
+                    }
+                }
+                let solver = &session.solver;
+                send.send(Update::Stats(solver.stats.clone())).unwrap();
+                if *watching {
+                    send.send(Update::Unsolved(solver.unsolved_constraints())).unwrap();
+                }
+                if let Some(filter) = filter.as_ref() {
+                    send.send(Update::Filtered(Some(Ok(solver.query(filter))))).unwrap();
+                }
+                if let Some(report) = cache.as_ref().zip(session.key.as_ref()).and_then(|(cache, key)| cache.get(key)) {
+                    send.send(Update::Report(report)).unwrap();
+                }
+                *inspected = None;
+
+                sessions.push_front(session);
+                sessions.truncate(MAX_SESSIONS);
+            }
+            Command::Run | Command::Step => {
+                if let Some(session) = sessions.front_mut() {
+                    let title = &session.listing.name;
+                    let s = &mut session.solver;
+                    let started = Instant::now();
+                    let response = match panic::catch_unwind(AssertUnwindSafe(|| s.step())) {
+                        Ok(response) => response,
+                        Err(payload) => {
+                            // The solver is in no state to continue
+                            send.send(Update::Error(format!("{}: the solver failed: {}", title, panic_message(payload)))).unwrap();
+                            sessions.pop_front();
+                            *running = false;
+                            return;
+                        }
+                    };
+                    let took = started.elapsed();
+                    // Stopped partway through a step, which is taken again from the start next time
+                    if cancel.is_cancelled() && matches!(response, StepResult::UnexpectedStop(_)) {
+                        *running = false;
+                        return;
+                    }
+                    // Point at the clues that conflict rather than what the solver derived from them
+                    let response = match response {
+                        StepResult::Contradiction(constraints) => match s.unsat_core() {
+                            Some(core) => {
+                                send.send(Update::Error(format!("{}: {} of the puzzle's clues can't all hold", title, core.len()))).unwrap();
+                                StepResult::Contradiction(core)
+                            }
+                            None => {
+                                send.send(Update::Error(format!("{}: contradiction between {} constraints, though the puzzle's clues fit together", title, constraints.len()))).unwrap();
+                                StepResult::Contradiction(constraints)
+                            }
+                        },
+                        response => response,
+                    };
+                    if let StepResult::UnexpectedStop(why) = &response {
+                        send.send(Update::Error(format!("{}: stopped: {}", title, why))).unwrap();
+                    }
+                    let finished = match response {
+                        StepResult::Finished | StepResult::Stuck | StepResult::UnexpectedStop(_) | StepResult::Contradiction(_) => {
+                            *running = false;
+                            true
+                        }
+                        _ => false,
+                    };
+                    if finished {
+                        let report = SolveReport::from_solver(title, s, &response);
+                        if let Some((cache, key)) = cache.as_ref().zip(session.key.as_ref()) {
+                            cache.put(key, &report);
+                        }
+                        // Only solves of the file as it is, with one set of settings, go in the history
+                        if let Some((history, contents)) = history.as_ref().filter(|_| session.key.is_some()).zip(session.listing.contents().ok()) {
+                            history.record(&HistoryEntry::new(&contents, session.config, report.clone()));
+                        }
+                        send.send(Update::Report(report)).unwrap();
+                    }
+                    publish_step(send, session, response, took, *inspected, *watching, filter.as_ref());
+                    // Stopped once the step was done, so it's kept
+                    if cancel.is_cancelled() {
+                        *running = false;
+                    }
+                    let s = &session.solver;
+                    if let Some(limit) = session.warn_at.filter(|&limit| *running && s.stats.unsolved_constraints > limit) {
+                        *running = false;
+                        send.send(Update::Warning(format!("{}: the solver has {} unsolved constraints, more than the {} it can have before asking, and may take a long time from here", session.listing.name, s.stats.unsolved_constraints, limit))).unwrap();
+                    }
+
+                    *resource_steps += 1;
+                    let elapsed = resource_time.elapsed().as_secs_f64();
+                    if elapsed >= RESOURCE_INTERVAL || !*running {
+                        send.send(Update::Resources(EngineResources { steps_per_second: *resource_steps as f64 / elapsed.max(RESOURCE_INTERVAL), memory: s.memory_report() })).unwrap();
+                        *resource_steps = 0;
+                        *resource_time = Instant::now();
+                    }
+                }
+            }
+            Command::Play(square, action) => {
+                if let Some(session) = sessions.front_mut() {
+                    let s = &mut session.solver;
+                    let (label, mine) = (s.puzzle.base.id_labels[square].clone(), s.puzzle.base.mines[square]);
+                    if s.puzzle.revealed[square] || s.puzzle.flagged[square] {
+                        return;
+                    }
+                    if let Some(start) = session.race {
+                        let at = start.elapsed();
+                        let provable = s.prove_all_current();
+                        let (justified, correct) = match action {
+                            Action::Reveal => (provable.safe[square], !mine),
+                            Action::Flag => (provable.mines[square], mine),
+                        };
+                        send.send(Update::RaceMove(PlayerMove { square, action, at, justified, correct })).unwrap();
+                    }
+                    let mut square_bits = Bits::zeroed();
+                    square_bits.set(square, true);
+                    let started = Instant::now();
+                    let response = match (action, mine) {
+                        (Action::Reveal, false) => {
+                            s.reveal(square);
+                            StepResult::Progress { revealed: square_bits, flagged: Bits::zeroed(), deductions: Vec::new() }
+                        }
+                        (Action::Flag, true) => {
+                            s.flag(square);
+                            StepResult::Progress { revealed: Bits::zeroed(), flagged: square_bits, deductions: Vec::new() }
+                        }
+                        (Action::Reveal, true) => {
+                            send.send(Update::Error(format!("{}: {} is a mine, so it wasn't revealed", session.listing.name, label))).unwrap();
+                            return;
+                        }
+                        (Action::Flag, false) => {
+                            send.send(Update::Error(format!("{}: {} is safe, so it wasn't flagged", session.listing.name, label))).unwrap();
+                            return;
+                        }
+                    };
+                    publish_step(send, session, response, started.elapsed(), *inspected, *watching, filter.as_ref());
+                }
+            }
+            Command::VerifyUnique => {
+                if let Some(session) = sessions.front() {
+                    send.send(Update::Uniqueness(session.solver.verify_unique())).unwrap();
+                }
+            }
+            Command::FindAlternatives => {
+                if let Some(session) = sessions.front() {
+                    send.send(Update::Alternatives(session.solver.alternative_layouts(MAX_ALTERNATIVES))).unwrap();
+                }
+            }
+            Command::Race(start) => {
+                if let Some(session) = sessions.front_mut() {
+                    session.race = None;
+                    if start {
+                        session.race = Some(Instant::now());
+                        // Stopping the shown puzzle shouldn't stop its race
+                        let mut solver = session.solver.clone();
+                        solver.set_cancel(CancelToken::default());
+                        if *threaded {
+                            let send = send.clone();
+                            thread::spawn(move || {
+                                // Nobody is listening if the window was closed meanwhile
+                                send.send(Update::RaceSolver(SolverRun::solve(solver))).ok();
+                            });
+                        } else {
+                            send.send(Update::RaceSolver(SolverRun::solve(solver))).unwrap();
+                        }
+                    }
+                }
+            }
+            Command::Inspect(square) => {
+                *inspected = square;
+                if let Some((session, square)) = sessions.front().zip(square) {
+                    send.send(Update::Constraints(session.solver.constraints_on(square))).unwrap();
+                }
+            }
+            Command::WatchConstraints(watch) => {
+                *watching = watch;
+                if let Some(session) = sessions.front().filter(|_| watch) {
+                    send.send(Update::Unsolved(session.solver.unsolved_constraints())).unwrap();
+                }
+            }
+            Command::Filter(source) => {
+                *filter = None;
+                match source.map(|source| ConstraintFilter::parse(&source)) {
+                    Some(Ok(parsed)) => {
+                        if let Some(session) = sessions.front() {
+                            send.send(Update::Filtered(Some(Ok(session.solver.query(&parsed))))).unwrap();
+                        }
+                        *filter = Some(parsed);
+                    }
+                    Some(Err(why)) => send.send(Update::Filtered(Some(Err(why)))).unwrap(),
+                    None => send.send(Update::Filtered(None)).unwrap(),
+                }
+            }
+            Command::Queue(listings, config) => {
+                for listing in listings {
+                    let job = QueuedJob { id: jobs.len(), listing, config, cancelled: CancelToken::default() };
+                    send.send(Update::JobQueued(job.id, job.listing.name.clone())).unwrap();
+                    jobs.push(job.cancelled.clone());
+                    match queue {
+                        Some(queue) => queue.send(job).unwrap(),
+                        None => send.send(Update::Job(job.id, solve_job(&job, cache.as_ref(), history.as_ref(), send))).unwrap(),
+                    }
+                }
+            }
+            Command::CancelJob(id) => {
+                if let Some(cancelled) = jobs.get(id) {
+                    cancelled.cancel();
+                }
+            }
+            Command::SuggestFixes => {
+                if let Some(session) = sessions.front() {
+                    send.send(Update::Suggestions(generator::suggest_fixes(&session.start.base, &session.gui, session.config))).unwrap();
+                }
+            }
+            Command::Repair(moves, seed) => {
+                if let Some(session) = sessions.pop_front() {
+                    let title = session.listing.name.clone();
+                    let (base, gui) = (&session.start.base, &session.gui);
+                    let repaired = panic::catch_unwind(|| generator::repair(base, gui, moves, seed));
+                    let (puzzle, changes) = match repaired {
+                        Ok(Ok(repaired)) => repaired,
+                        Ok(Err(why)) => {
+                            send.send(Update::Error(format!("{}: unable to fix: {}", title, why))).unwrap();
+                            sessions.push_front(session);
+                            return;
+                        }
+                        Err(payload) => {
+                            send.send(Update::Error(format!("{}: unable to fix: {}", title, panic_message(payload)))).unwrap();
+                            sessions.push_front(session);
+                            return;
+                        }
+                    };
+
+                    // No longer what the file says, so there's no saved report for it
+                    let mut solver = Solver::new(puzzle, session.config);
+                    solver.set_cancel(cancel.clone());
+                    let constraints = solver.unsolved_constraints().into_iter().collect();
+                    let session = Session { key: None, start: solver.puzzle.clone(), solver, history: Vec::new(), constraints, race: None, warn_at: *warn_at, ..session };
+                    send.send(Update::NewPuzzle(session.listing.clone(), session.start.clone(), session.gui.clone())).unwrap();
+                    send.send(Update::Stats(session.solver.stats.clone())).unwrap();
+                    send.send(Update::Repaired(changes)).unwrap();
+                    *inspected = None;
+                    sessions.push_front(session);
+                }
+            }
+            Command::Reconfigure(config, restart) => {
+                if let Some(mut session) = sessions.pop_front() {
+                    if restart {
+                        let mut solver = Solver::new(session.start.base.clone(), config);
+                        solver.set_cancel(cancel.clone());
+                        // A repaired puzzle has no saved report whatever the settings
+                        let key = session.key.as_ref().and(session.listing.contents().ok()).map(|contents| SolveCache::key(&contents, &config, false));
+                        session = Session {
+                            constraints: solver.unsolved_constraints().into_iter().collect(),
+                            key,
+                            start: solver.puzzle.clone(),
+                            config,
+                            solver,
+                            history: Vec::new(),
+                            race: None,
+                            warn_at: *warn_at,
+                            ..session
+                        };
+                        send.send(Update::NewPuzzle(session.listing.clone(), session.start.clone(), session.gui.clone())).unwrap();
+                        if *watching {
+                            send.send(Update::Unsolved(session.solver.unsolved_constraints())).unwrap();
+                        }
+                        if let Some(filter) = filter.as_ref() {
+                            send.send(Update::Filtered(Some(Ok(session.solver.query(filter))))).unwrap();
+                        }
+                        if let Some(report) = cache.as_ref().zip(session.key.as_ref()).and_then(|(cache, key)| cache.get(key)) {
+                            send.send(Update::Report(report)).unwrap();
+                        }
+                        *inspected = None;
+                    } else {
+                        // Solved partly with other settings, so it isn't the saved report's solve
+                        session.solver.reconfigure(config);
+                        session.config = config;
+                        session.key = None;
+                    }
+                    send.send(Update::Stats(session.solver.stats.clone())).unwrap();
+                    sessions.push_front(session);
+                }
+            }
+            Command::ProveCurrent => {
+                if let Some(session) = sessions.front() {
+                    send.send(Update::Provable(session.solver.prove_all_current())).unwrap();
+                }
+            }
+            Command::ExportGraph(format) => {
+                if let Some(session) = sessions.front() {
+                    let title = &session.listing.name;
+                    let path = format!("{}.{}", export::file_stem(title), format.extension());
+                    let graph = export::constraint_graph(title, &session.solver.puzzle.base, &session.solver.unsolved_constraints(), format);
+                    match std::fs::write(&path, graph) {
+                        Ok(()) => send.send(Update::Exported(path)).unwrap(),
+                        Err(e) => send.send(Update::Error(format!("{}: unable to write {}: {}", title, path, e))).unwrap(),
+                    }
+                }
+            }
+            Command::History => match history.as_ref() {
+                Some(history) => send.send(Update::History(history.entries())).unwrap(),
+                None => send.send(Update::Error(String::from("Unable to open the history, which another tametsi may be using"))).unwrap(),
+            },
+            Command::ExportSteps => {
+                if let Some(session) = sessions.front() {
+                    let title = &session.listing.name;
+                    let path = format!("{}.steps.json", export::file_stem(title));
+                    let steps = session.history.iter().map(|(state, result, _, _, _)| (state.clone(), result.clone())).collect::<Vec<_>>();
+                    let durations = session.history.iter().map(|&(_, _, _, _, took)| took).collect::<Vec<_>>();
+                    match std::fs::write(&path, export::steps_json(title, &session.gui, &session.start, &steps, &durations)) {
+                        Ok(()) => send.send(Update::Exported(path)).unwrap(),
+                        Err(e) => send.send(Update::Error(format!("{}: unable to write {}: {}", title, path, e))).unwrap(),
+                    }
+                }
+            }
+            Command::SaveBundle => {
+                if let Some(session) = sessions.front() {
+                    let title = &session.listing.name;
+                    let path = PathBuf::from(format!("{}.{}", export::file_stem(title), BUNDLE_EXTENSION));
+                    let file = PuzzleFile { title: title.clone(), author: session.listing.author.clone(), puzzle: session.start.base.clone(), gui: session.gui.clone() };
+                    let saved = Bundle::new(&file).and_then(|mut bundle| {
+                        bundle.config = Some(session.config);
+                        bundle.replay = Some(Trace::from_steps(title, &session.start, session.config, session.history.iter().map(|(state, result, _, _, _)| (state, result))));
+                        bundle.write(&path)
+                    });
+                    match saved {
+                        Ok(()) => send.send(Update::Exported(path.display().to_string())).unwrap(),
+                        Err(e) => send.send(Update::Error(format!("{}: {}", title, e))).unwrap(),
+                    }
+                }
+            }
+            Command::WarnAt(limit) => {
+                *warn_at = limit;
+                if let Some(session) = sessions.front_mut() {
+                    session.warn_at = limit;
+                }
+            }
+            Command::Resume(limit) => {
+                if let Some(session) = sessions.front_mut() {
+                    session.warn_at = limit;
+                    *running = true;
+                }
+            }
+            Command::ProveRest => {
+                if let Some(session) = sessions.front_mut() {
+                    let started = Instant::now();
+                    let provable = session.solver.prove_all_current();
+                    if (provable.safe | provable.mines).not_any() {
+                        send.send(Update::Error(format!("{}: searching the layouts proves nothing more", session.listing.name))).unwrap();
+                        return;
+                    }
+                    let response = session.solver.apply_provable(&provable);
+                    publish_step(send, session, response, started.elapsed(), *inspected, *watching, filter.as_ref());
+                }
+            }
+            // The handle cancelled the step under way, if there was one, before sending this
+            Command::Stop => cancel.reset(),
+        }
+    }
+}
+
+/// The GUI's side of the engine.  Commands never wait, while updates are taken a frame's worth at
+/// a time.
+pub struct EngineHandle {
+    send: Sender<Command>,
+    backend: Backend,
+    /// Cancelled when `Command::Stop` is sent, so a long step doesn't hold it up.
+    cancel: CancelToken,
+    /// Where the puzzles come from, for a restart.
+    folders: Vec<PathBuf>,
+}
+
+enum Backend {
+    /// The engine has a thread of its own, and threads for badges, races and queued solves.
+    Threaded(Receiver<Update>),
+    /// The engine runs on the GUI's thread, a command or a step at a time, for platforms without
+    /// threads.
+    /// The engine is `None` once it has panicked.
+    Local { engine: Option<Box<Engine>>, recieve: Receiver<Command>, updates: Arc<Mutex<VecDeque<Update>>> },
+}
+
+impl EngineHandle {
+    /// Starts the engine on a thread of its own, on puzzles from `folders`.
+    pub fn spawn(folders: Vec<PathBuf>) -> EngineHandle {
+        let (send, recieve) = mpsc::channel();
+        let (update_send, updates) = mpsc::sync_channel(UPDATE_BOUND);
+        let cancel = CancelToken::default();
+        let (engine_cancel, engine_folders) = (cancel.clone(), folders.clone());
+        thread::spawn(move || {
+            let crash_send = update_send.clone();
+            let ran = panic::catch_unwind(AssertUnwindSafe(|| Engine::new(UpdateSender::Channel(update_send), engine_folders, engine_cancel, true).run(recieve)));
+            if let Err(payload) = ran {
+                // Nobody is listening if the GUI has closed, which may be why it panicked
+                crash_send.send(Update::EngineCrashed(panic_message(payload))).ok();
+            }
+        });
+        EngineHandle { send, backend: Backend::Threaded(updates), cancel, folders }
+    }
+
+    /// Makes an engine that only works while updates are being taken, on puzzles from `folders`.
+    /// Races and queued solves hold up the GUI until they're done.
+    pub fn local(folders: Vec<PathBuf>) -> EngineHandle {
+        let (send, recieve) = mpsc::channel();
+        let updates = Arc::new(Mutex::new(VecDeque::new()));
+        let cancel = CancelToken::default();
+        let engine = Engine::new(UpdateSender::Local(Arc::clone(&updates)), folders.clone(), cancel.clone(), false);
+        EngineHandle { send, backend: Backend::Local { engine: Some(Box::new(engine)), recieve, updates }, cancel, folders }
+    }
+
+    /// Replaces the engine with a new one, started the same way, after it has crashed.  Everything
+    /// the old one was doing is lost.
+    pub fn restart(&mut self) {
+        let folders = self.folders.clone();
+        *self = match self.backend {
+            Backend::Threaded(_) => EngineHandle::spawn(folders),
+            Backend::Local { .. } => EngineHandle::local(folders),
+        };
+    }
+
+    pub fn send(&self, command: Command) {
+        if command == Command::Stop {
+            self.cancel.cancel();
+        }
+        // The engine only stops if the GUI has gone
+        self.send.send(command).ok();
+    }
+
+    /// The updates waiting, oldest first and at most `UPDATE_BOUND` of them.
+    pub fn updates(&mut self) -> Vec<Update> {
+        match &mut self.backend {
+            Backend::Threaded(recieve) => recieve.try_iter().take(UPDATE_BOUND).collect(),
+            Backend::Local { engine, recieve, updates } => {
+                if let Some(working) = engine {
+                    let worked = panic::catch_unwind(AssertUnwindSafe(|| {
+                        let mut handled = false;
+                        while let Ok(command) = recieve.try_recv() {
+                            working.handle(command);
+                            handled = true;
+                        }
+                        if !handled {
+                            working.idle();
+                        }
+                    }));
+                    if let Err(payload) = worked {
+                        *engine = None;
+                        updates.lock().unwrap().push_back(Update::EngineCrashed(panic_message(payload)));
+                    }
+                }
+                let mut updates = updates.lock().unwrap();
+                let taken = updates.len().min(UPDATE_BOUND);
+                updates.drain(..taken).collect()
+            }
+        }
+    }
+}
+
+/// Works out the badge of every puzzle in turn.
+fn find_badges(puzzles: Vec<PuzzleListing>, send: UpdateSender) {
+    let cache = SolveCache::open();
+    for (index, listing) in puzzles.iter().enumerate() {
+        // The GUI has closed
+        if send.send(Update::Badge(index, find_badge(listing, cache.as_ref()))).is_err() {
+            return;
+        }
+    }
+}
+
+/// The badge of a puzzle, using the saved one if there is one.
+fn find_badge(listing: &PuzzleListing, cache: Option<&SolveCache>) -> Badge {
+    let key = listing.contents().ok().map(|contents| SolveCache::badge_key(&contents));
+    let saved = cache.zip(key.as_ref()).and_then(|(cache, key)| cache.get(key));
+    saved.unwrap_or_else(|| {
+        let badge = Badge::of(listing);
+        if let Some((cache, key)) = cache.zip(key.as_ref()) {
+            cache.put(key, &badge);
+        }
+        badge
+    })
+}
+
+/// Records a step of the shown puzzle and sends it to the GUI, with whatever it is following.
+fn publish_step(send: &UpdateSender, session: &mut Session, response: StepResult, took: Duration, inspected: Option<usize>, watching: bool, filter: Option<&ConstraintFilter>) {
+    let s = &session.solver;
+    // Constraints that came, went or had their bounds changed, by their squares
+    let constraints = s.unsolved_constraints().into_iter().collect::<HashSet<_>>();
+    let ripple = session.constraints.symmetric_difference(&constraints).fold(Bits::zeroed(), |ripple, c| ripple | c.bits);
+    session.constraints = constraints;
+    session.history.push((s.puzzle.clone(), response.clone(), s.stats.clone(), ripple, took));
+    send.send(Update::Step(s.puzzle.clone(), response, took)).unwrap();
+    send.send(Update::Stats(s.stats.clone())).unwrap();
+    send.send(Update::Ripple(ripple)).unwrap();
+    if let Some(square) = inspected {
+        send.send(Update::Constraints(s.constraints_on(square))).unwrap();
+    }
+    if watching {
+        send.send(Update::Unsolved(s.unsolved_constraints())).unwrap();
+    }
+    if let Some(filter) = filter {
+        send.send(Update::Filtered(Some(Ok(s.query(filter))))).unwrap();
+    }
+}
+
+/// Takes queued puzzles one at a time until the queue closes, using saved reports where there are
+/// any.  Several of these share the queue.
+fn solve_queue(queued: Arc<Mutex<Receiver<QueuedJob>>>, send: UpdateSender, history: Option<History>) {
+    let cache = SolveCache::open();
+    loop {
+        // The lock is only held while waiting for the next job
+        let job = match queued.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        if send.send(Update::Job(job.id, solve_job(&job, cache.as_ref(), history.as_ref(), &send))).is_err() {
+            return;
+        }
+    }
+}
+
+/// Solves a queued puzzle, reporting progress as it goes, and sends its report if it finishes.
+/// Returns where the job ended up.
+fn solve_job(job: &QueuedJob, cache: Option<&SolveCache>, history: Option<&History>, send: &UpdateSender) -> JobStatus {
+    let QueuedJob { id, listing, config, cancelled } = job;
+    if cancelled.is_cancelled() {
+        return JobStatus::Cancelled;
+    }
+    let contents = listing.contents().ok();
+    let key = contents.as_ref().map(|contents| SolveCache::key(contents, config, false));
+    let report = match cache.zip(key.as_ref()).and_then(|(cache, key)| cache.get(key)) {
+        Some(report) => report,
+        None => {
+            let solved = panic::catch_unwind(AssertUnwindSafe(|| {
+                let (puzzle, _) = listing.read();
+                let mut solver = Solver::new(puzzle, *config);
+                solver.set_cancel(cancelled.clone());
+                let mut reported = Instant::now();
+                loop {
+                    let result = solver.step();
+                    // A cancelled solve isn't saved, as it didn't finish
+                    if cancelled.is_cancelled() {
+                        return None;
+                    }
+                    if SolveOutcome::from_step(&result).is_some() {
+                        return Some(SolveReport::from_solver(&listing.name, &solver, &result));
+                    }
+                    if reported.elapsed().as_secs_f64() >= JOB_PROGRESS_INTERVAL {
+                        let state = &solver.puzzle;
+                        let _ = send.send(Update::Job(*id, JobStatus::Running((state.revealed | state.flagged).count_ones(), state.base.size())));
+                        reported = Instant::now();
+                    }
+                }
+            }));
+            match solved {
+                Ok(Some(report)) => {
+                    if let Some((cache, key)) = cache.zip(key.as_ref()) {
+                        cache.put(key, &report);
+                    }
+                    if let Some((history, contents)) = history.zip(contents) {
+                        history.record(&HistoryEntry::new(&contents, *config, report.clone()));
+                    }
+                    report
+                }
+                Ok(None) => return JobStatus::Cancelled,
+                Err(payload) => {
+                    let why = panic_message(payload);
+                    let _ = send.send(Update::Error(format!("{}: unable to solve: {}", listing.name, why)));
+                    return JobStatus::Failed(why);
+                }
+            }
+        }
+    };
+
+    let outcome = report.outcome.clone();
+    let _ = send.send(Update::Queued(listing.clone(), report));
+    JobStatus::Finished(outcome)
+}
+
+/// The text of a caught panic.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(|| String::from("unknown error"), |message| message.to_string()),
+    }
+}
+
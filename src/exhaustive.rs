@@ -0,0 +1,148 @@
+use crate::{core::Bits, solver::Constraint};
+
+/// The result of searching for a mine layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// A layout satisfying every constraint.  Holds the mines.
+    Found(Bits),
+    Impossible,
+    /// The search was abandoned after visiting too many positions.
+    GaveUp,
+}
+
+/// Layouts found by `Exhaustive::enumerate`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Layouts {
+    /// The mines of each layout.
+    pub layouts: Vec<Bits>,
+    /// Whether these are all the layouts, rather than the first few.
+    pub complete: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Continue,
+    Stop,
+    GaveUp,
+}
+
+/// Backtracking search for mine layouts that satisfy a set of constraints exactly, rather than the
+/// pairwise reasoning the solver does.  Used where the solver's answer needs to be checked.
+pub struct Exhaustive<'a> {
+    constraints: &'a [Constraint],
+    cells: Bits,
+    node_limit: usize,
+}
+
+impl<'a> Exhaustive<'a> {
+    /// `cells` are the squares whose contents are unknown.  Squares outside of it are treated as safe.
+    pub fn new(constraints: &'a [Constraint], cells: Bits) -> Exhaustive<'a> {
+        Exhaustive {
+            constraints,
+            cells,
+            node_limit: 100_000,
+        }
+    }
+
+    /// Finds a layout with every square in `mines` a mine and every square in `safe` safe.
+    pub fn find(&self, mines: Bits, safe: Bits) -> Outcome {
+        let mut found = None;
+        let visit = self.visit(mines, safe | !self.cells, &mut 0, &mut |layout| {
+            found = Some(layout);
+            false
+        });
+
+        match (found, visit) {
+            (Some(layout), _) => Outcome::Found(layout),
+            (None, Visit::GaveUp) => Outcome::GaveUp,
+            (None, _) => Outcome::Impossible,
+        }
+    }
+
+    /// Lists up to `limit` layouts with every square in `mines` a mine and every square in `safe` safe.
+    pub fn enumerate(&self, mines: Bits, safe: Bits, limit: usize) -> Layouts {
+        let mut layouts = Vec::new();
+        let visit = self.visit(mines, safe | !self.cells, &mut 0, &mut |layout| {
+            layouts.push(layout);
+            layouts.len() < limit
+        });
+
+        Layouts {
+            layouts,
+            complete: visit == Visit::Continue,
+        }
+    }
+
+    /// Calls `visitor` with each layout found until it returns false.
+    fn visit(&self, mut mines: Bits, mut safe: Bits, nodes: &mut usize, visitor: &mut dyn FnMut(Bits) -> bool) -> Visit {
+        if !self.propagate(&mut mines, &mut safe) {
+            return Visit::Continue;
+        }
+
+        // Branch on a square in the constraint with the fewest open squares, where the choice matters most
+        let open = self.cells & !mines & !safe;
+        let branch = self.constraints.iter()
+            .map(|c| c.bits & open)
+            .filter(|open| open.any())
+            .min_by_key(|open| open.count_ones())
+            .and_then(|open| open.first_one())
+            .or_else(|| open.first_one());
+
+        let square = match branch {
+            Some(square) => square,
+            None => return if visitor(mines & self.cells) { Visit::Continue } else { Visit::Stop },
+        };
+
+        *nodes += 1;
+        if *nodes > self.node_limit {
+            return Visit::GaveUp;
+        }
+
+        let mut with_mine = mines;
+        with_mine.set(square, true);
+        match self.visit(with_mine, safe, nodes, visitor) {
+            Visit::Continue => {}
+            other => return other,
+        }
+
+        let mut with_safe = safe;
+        with_safe.set(square, true);
+        self.visit(mines, with_safe, nodes, visitor)
+    }
+
+    /// Fills in every square a single constraint forces, until nothing changes.  Returns false if
+    /// some constraint can no longer be satisfied.
+    fn propagate(&self, mines: &mut Bits, safe: &mut Bits) -> bool {
+        if (*mines & *safe).any() {
+            return false;
+        }
+
+        loop {
+            let mut changed = false;
+            for constraint in self.constraints {
+                let placed = (constraint.bits & *mines).count_ones();
+                let open = constraint.bits & !*mines & !*safe;
+                let open_count = open.count_ones();
+                if placed > constraint.max_mines || placed + open_count < constraint.min_mines {
+                    return false;
+                }
+
+                if open_count == 0 {
+                    continue;
+                }
+
+                if placed == constraint.max_mines {
+                    *safe |= open;
+                    changed = true;
+                } else if placed + open_count == constraint.min_mines {
+                    *mines |= open;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return true;
+            }
+        }
+    }
+}
@@ -0,0 +1,577 @@
+use std::{collections::BTreeMap, fmt::Write, time::{Duration, Instant}};
+
+use eframe::egui::Color32;
+use serde::Serialize;
+
+use crate::{app::{describe_step, highlighted_squares, square_appearance}, core::{Bits, Puzzle, PuzzleGui}, difficulty::DifficultyModel, history::HistoryEntry, narration::narrate, report::SolveReport, solver::{Action, Constraint, PuzzleState, SolveOutcome, Solver, SolverStats, StepResult}, technique::Technique};
+
+const PAGE_STYLE: &str = "body { background: #202020; color: #e0e0e0; font-family: sans-serif; margin: 2em auto; max-width: 60em; }
+nav { display: flex; gap: 1em; align-items: center; margin-bottom: 1em; }
+button { font-size: 1em; padding: 0.3em 1em; }
+svg { width: 100%; max-height: 75vh; background: #000; }
+p { white-space: pre-line; }
+.narration { color: #b0d0ff; }
+td { padding: 0.1em 1em 0.1em 0; }";
+
+const PAGE_SCRIPT: &str = "let current = 0;
+const steps = document.querySelectorAll('.step');
+function show(index) {
+    current = Math.max(0, Math.min(steps.length - 1, index));
+    steps.forEach((step, i) => step.hidden = i !== current);
+    document.getElementById('position').textContent = 'Step ' + current + ' of ' + (steps.length - 1);
+}
+document.getElementById('prev').onclick = () => show(current - 1);
+document.getElementById('next').onclick = () => show(current + 1);
+document.addEventListener('keydown', e => {
+    if (e.key === 'ArrowLeft') show(current - 1);
+    if (e.key === 'ArrowRight') show(current + 1);
+});
+show(0);";
+
+/// A file format for the constraint graph.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GraphFormat {
+    /// For Graphviz.
+    Dot,
+    /// For Gephi, yEd and most other graph tools.
+    GraphMl,
+}
+
+impl GraphFormat {
+    pub const ALL: [GraphFormat; 2] = [GraphFormat::Dot, GraphFormat::GraphMl];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::GraphMl => "graphml",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GraphFormat> {
+        GraphFormat::ALL.iter().copied().find(|format| format.name() == name)
+    }
+
+    pub fn extension(self) -> &'static str {
+        self.name()
+    }
+}
+
+/// How a table with a row per puzzle is written.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TableFormat {
+    /// For spreadsheets.
+    Csv,
+    Json,
+}
+
+impl TableFormat {
+    pub const ALL: [TableFormat; 2] = [TableFormat::Csv, TableFormat::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TableFormat::Csv => "csv",
+            TableFormat::Json => "json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TableFormat> {
+        TableFormat::ALL.iter().copied().find(|format| format.name() == name)
+    }
+
+    pub fn extension(self) -> &'static str {
+        self.name()
+    }
+}
+
+/// How exported pictures of the board look.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Theme {
+    /// The GUI's colors: blue, red and gray squares with white numbers.
+    Solver,
+    /// Close to the game's own: pale unrevealed squares, dark revealed ones with thin light outlines,
+    /// larger numbers and a dimmer '?' on secret squares, so pictures match what players see.
+    Tametsi,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Solver, Theme::Tametsi];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Solver => "solver",
+            Theme::Tametsi => "tametsi",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Theme> {
+        Theme::ALL.iter().copied().find(|theme| theme.name() == name)
+    }
+
+    pub fn background(self) -> Color32 {
+        match self {
+            Theme::Solver => Color32::from_rgb(32, 32, 32),
+            Theme::Tametsi => Color32::from_rgb(14, 17, 22),
+        }
+    }
+
+    /// A square's fill, the text on it and the text's color.
+    pub fn appearance(self, state: &PuzzleState, square: usize) -> (Color32, String, Color32) {
+        let (color, text) = square_appearance(state, square);
+        match self {
+            Theme::Solver => (color, text, Color32::WHITE),
+            Theme::Tametsi if state.revealed[square] => {
+                let text_color = if state.base.unknowns[square] { Color32::from_rgb(120, 140, 160) } else { Color32::from_rgb(235, 240, 245) };
+                (Color32::from_rgb(40, 46, 56), text, text_color)
+            }
+            Theme::Tametsi if state.flagged[square] => (Color32::from_rgb(70, 140, 230), text, Color32::WHITE),
+            Theme::Tametsi => (Color32::from_rgb(170, 182, 196), text, Color32::WHITE),
+        }
+    }
+
+    /// The color of square outlines, and their width as a fraction of a square's size.
+    pub fn outline(self) -> (Color32, f32) {
+        match self {
+            Theme::Solver => (Color32::BLACK, 0.05),
+            Theme::Tametsi => (Color32::from_rgb(200, 208, 218), 0.025),
+        }
+    }
+
+    /// How tall numbers are, as a fraction of a square's height.
+    pub fn text_size(self) -> f32 {
+        match self {
+            Theme::Solver => 0.5,
+            Theme::Tametsi => 0.6,
+        }
+    }
+}
+
+/// Steps `solver` until it stops, keeping the state after each step with its result.
+pub fn solve_steps(solver: &mut Solver) -> Vec<(PuzzleState, StepResult)> {
+    timed_steps(solver).0
+}
+
+/// Like [`solve_steps`], and also how long each step took.
+pub fn timed_steps(solver: &mut Solver) -> (Vec<(PuzzleState, StepResult)>, Vec<Duration>) {
+    let (mut steps, mut durations) = (Vec::new(), Vec::new());
+    loop {
+        let started = Instant::now();
+        let result = solver.step();
+        durations.push(started.elapsed());
+        let done = matches!(result, StepResult::Finished | StepResult::Stuck | StepResult::UnexpectedStop(_) | StepResult::Contradiction(_));
+        steps.push((solver.puzzle.clone(), result));
+        if done {
+            return (steps, durations);
+        }
+    }
+}
+
+/// A standalone web page walking through a solve, with a picture of the board and the explanation
+/// for every step told as a tutorial would, then where the difficulty score comes from.  `steps` are the states after each
+/// step, as the GUI keeps them, and `stats` the solver's at the end.
+pub fn html_walkthrough(title: &str, gui: &PuzzleGui, start: &PuzzleState, steps: &[(PuzzleState, StepResult)], stats: &SolverStats, theme: Theme) -> String {
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>", escape(title), PAGE_STYLE).unwrap();
+    writeln!(html, "<h1>{}</h1>", escape(title)).unwrap();
+    writeln!(html, "<nav><button id=\"prev\">Previous</button><button id=\"next\">Next</button><span id=\"position\"></span></nav>").unwrap();
+
+    writeln!(html, "<section class=\"step\">\n{}\n<p>The starting board.</p>\n</section>", board_svg(gui, start, None, theme)).unwrap();
+    let mut before = start;
+    for (state, result) in steps {
+        writeln!(html, "<section class=\"step\" hidden>\n{}\n<p>{}</p>", board_svg(gui, state, Some(result), theme), escape(&describe_step(result, &start.base))).unwrap();
+        if let StepResult::Progress{..} | StepResult::CrossConstraint(_) = result {
+            for sentence in narrate(result, before) {
+                writeln!(html, "<p class=\"narration\">{}</p>", escape(&sentence)).unwrap();
+            }
+        }
+        writeln!(html, "</section>").unwrap();
+        before = state;
+    }
+
+    let model = DifficultyModel::default();
+    writeln!(html, "<h2>Difficulty {:.1}</h2>\n<table>", model.score(stats)).unwrap();
+    for (effort, cost) in model.breakdown(stats).iter() {
+        writeln!(html, "<tr><td>{}</td><td>{:.1}</td></tr>", effort, cost).unwrap();
+    }
+    writeln!(html, "</table>\n<p>Techniques used: {}</p>", stats.techniques).unwrap();
+
+    writeln!(html, "<script>\n{}\n</script>\n</body>\n</html>", PAGE_SCRIPT).unwrap();
+    html
+}
+
+/// Whether a step gets its own section and picture in the Markdown log: it resolved squares or
+/// ended the solve.  Crossings are told in the section of the step that uses them.
+pub fn significant(result: &StepResult) -> bool {
+    !matches!(result, StepResult::CrossConstraint(_) | StepResult::CliqueConstraint(_))
+}
+
+/// How many crossings leading up to a step the Markdown log tells.
+const MARKDOWN_CROSSINGS: usize = 10;
+
+/// The file name of the picture of the board after step `number`, 0 being the start.
+pub fn step_image(number: usize) -> String {
+    format!("step_{:04}.png", number)
+}
+
+/// A Markdown document of a solve for issues and wikis: the starting board, then for each
+/// [`significant`] step a picture of the board with the step told as a tutorial would, then where
+/// the difficulty score comes from.  Pictures are named by [`step_image`] in `images`, a folder
+/// next to the document.
+pub fn markdown_log(title: &str, start: &PuzzleState, steps: &[(PuzzleState, StepResult)], stats: &SolverStats, images: &str) -> String {
+    let mut markdown = String::new();
+    writeln!(markdown, "# {}
+", markdown_escape(title)).unwrap();
+    writeln!(markdown, "![The starting board]({}/{})
+", images, step_image(0)).unwrap();
+
+    let mut before = start;
+    let mut crossings = Vec::new();
+    for (number, (state, result)) in steps.iter().enumerate().map(|(i, step)| (i + 1, step)) {
+        let narration = match result {
+            StepResult::Progress{..} | StepResult::CrossConstraint(_) => narrate(result, before),
+            _ => Vec::new(),
+        };
+        before = state;
+        if !significant(result) {
+            crossings.extend(narration);
+            continue;
+        }
+
+        writeln!(markdown, "## Step {}
+
+{}
+", number, markdown_escape(&describe_step(result, &start.base))).unwrap();
+        // Long searches cross many constraints, and the latest are the ones the step is likeliest to use
+        let skipped = crossings.len().saturating_sub(MARKDOWN_CROSSINGS);
+        if skipped > 0 {
+            writeln!(markdown, "- ({} earlier crossings left out)", skipped).unwrap();
+        }
+        for sentence in crossings.drain(..).skip(skipped).chain(narration) {
+            writeln!(markdown, "- {}", markdown_escape(&sentence)).unwrap();
+        }
+        writeln!(markdown, "
+![The board after step {}]({}/{})
+", number, images, step_image(number)).unwrap();
+    }
+
+    let model = DifficultyModel::default();
+    writeln!(markdown, "## Difficulty {:.1}
+
+| Effort | Cost |
+| --- | --- |", model.score(stats)).unwrap();
+    for (effort, cost) in model.breakdown(stats).iter() {
+        writeln!(markdown, "| {} | {:.1} |", effort, cost).unwrap();
+    }
+    writeln!(markdown, "
+Techniques used: {}", stats.techniques).unwrap();
+    markdown
+}
+
+/// The constraints as a graph: a node for each constraint and each square they cover, an edge from
+/// every constraint to its squares, and an edge between constraints that overlap, labelled with how
+/// many squares they share.
+pub fn constraint_graph(title: &str, puzzle: &Puzzle, constraints: &[Constraint], format: GraphFormat) -> String {
+    let squares = constraints.iter().fold(Bits::zeroed(), |squares, c| squares | c.bits);
+    let mut overlaps = Vec::new();
+    for (i, left) in constraints.iter().enumerate() {
+        for (j, right) in constraints.iter().enumerate().skip(i + 1) {
+            let shared = (left.bits & right.bits).count_ones();
+            if shared > 0 {
+                overlaps.push((i, j, shared));
+            }
+        }
+    }
+
+    let mut text = String::new();
+    match format {
+        GraphFormat::Dot => {
+            let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+            writeln!(text, "graph {} {{", quote(title)).unwrap();
+            writeln!(text, "    node [shape=circle];").unwrap();
+            for square in squares.iter_ones() {
+                writeln!(text, "    s{} [label={}];", square, quote(&puzzle.id_labels[square])).unwrap();
+            }
+            for (i, c) in constraints.iter().enumerate() {
+                writeln!(text, "    c{} [shape=box, label=\"{}->{}/{}\\n{}, depth {}\"];", i, c.min_mines, c.max_mines, c.size, c.technique, c.depth).unwrap();
+                for square in c.bits.iter_ones() {
+                    writeln!(text, "    c{} -- s{};", i, square).unwrap();
+                }
+            }
+            for (i, j, shared) in overlaps {
+                writeln!(text, "    c{} -- c{} [style=dashed, label=\"{}\"];", i, j, shared).unwrap();
+            }
+            text.push_str("}\n");
+        }
+        GraphFormat::GraphMl => {
+            writeln!(text, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">").unwrap();
+            for (key, domain, kind) in [("label", "node", "string"), ("kind", "node", "string"), ("min_mines", "node", "int"), ("max_mines", "node", "int"), ("size", "node", "int"), ("technique", "node", "string"), ("depth", "node", "int"), ("shared", "edge", "int")].iter() {
+                writeln!(text, "  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>", key, domain, kind).unwrap();
+            }
+            writeln!(text, "  <graph id=\"{}\" edgedefault=\"undirected\">", escape(title)).unwrap();
+            for square in squares.iter_ones() {
+                writeln!(text, "    <node id=\"s{}\"><data key=\"kind\">square</data><data key=\"label\">{}</data></node>", square, escape(&puzzle.id_labels[square])).unwrap();
+            }
+            for (i, c) in constraints.iter().enumerate() {
+                writeln!(text, "    <node id=\"c{}\"><data key=\"kind\">constraint</data><data key=\"label\">{}->{}/{}</data><data key=\"min_mines\">{}</data><data key=\"max_mines\">{}</data><data key=\"size\">{}</data><data key=\"technique\">{}</data><data key=\"depth\">{}</data></node>",
+                    i, c.min_mines, c.max_mines, c.size, c.min_mines, c.max_mines, c.size, c.technique, c.depth).unwrap();
+                for square in c.bits.iter_ones() {
+                    writeln!(text, "    <edge source=\"c{}\" target=\"s{}\"/>", i, square).unwrap();
+                }
+            }
+            for (i, j, shared) in overlaps {
+                writeln!(text, "    <edge source=\"c{}\" target=\"c{}\"><data key=\"shared\">{}</data></edge>", i, j, shared).unwrap();
+            }
+            text.push_str("  </graph>\n</graphml>\n");
+        }
+    }
+    text
+}
+
+/// A puzzle's name with anything that might not be allowed in a file name replaced.
+pub fn file_stem(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+/// The board drawn in `theme`, with the squares a step is about at full brightness.  It has its
+/// own background, so it can be shown on its own.
+pub fn board_svg(gui: &PuzzleGui, state: &PuzzleState, result: Option<&StepResult>, theme: Theme) -> String {
+    let corners = gui.squares.iter().flat_map(|object| object.points.iter().map(move |a| (object.x + a.0, object.y + a.1)));
+    let (min_x, min_y, max_x, max_y) = corners.fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(min_x, min_y, max_x, max_y), (x, y)| {
+        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+    });
+    // Sizes in the puzzle's own units, which vary between puzzles
+    let unit = gui.squares.first().map_or(1.0, |object| square_height(&object.points));
+    let margin = unit / 2.0;
+    let (outline, outline_width) = theme.outline();
+
+    let mut svg = String::new();
+    let (left, top, width, height) = (min_x - margin, min_y - margin, max_x - min_x + margin * 2.0, max_y - min_y + margin * 2.0);
+    write!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">", left, top, width, height).unwrap();
+    write!(svg, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", left, top, width, height, hex(theme.background())).unwrap();
+    let highlighted = result.map(highlighted_squares);
+    for (i, object) in gui.squares.iter().enumerate() {
+        let (color, text, text_color) = theme.appearance(state, i);
+        let opacity = if highlighted.is_some_and(|highlighted| highlighted[i]) { 1.0 } else { 0.5 };
+        let points = object.points.iter().map(|a| format!("{},{}", object.x + a.0, object.y + a.1)).collect::<Vec<_>>().join(" ");
+        // The title shows the square's ID when hovering over it
+        write!(svg, "<polygon points=\"{}\" fill=\"{}\" fill-opacity=\"{}\" stroke=\"{}\" stroke-width=\"{}\"><title>{}</title></polygon>", points, hex(color), opacity, hex(outline), unit * outline_width, escape(&state.base.id_labels[i])).unwrap();
+        if !text.is_empty() {
+            write!(svg, "<text x=\"{}\" y=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\" font-size=\"{}\">{}</text>", object.x, object.y, hex(text_color), square_height(&object.points) * theme.text_size(), escape(&text)).unwrap();
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// The height of a square, so numbers and outlines fit whatever units the puzzle uses.
+fn square_height(points: &[(f32, f32)]) -> f32 {
+    let top = points.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+    let bottom = points.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+    (bottom - top).max(2.0)
+}
+
+fn hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+/// Text with anything Markdown would read as formatting escaped.
+fn markdown_escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        if "\\`*_[]<>#|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The version of the steps document, bumped whenever a field changes meaning or goes away.
+pub const STEPS_SCHEMA_VERSION: u32 = 1;
+
+/// A JSON Schema describing the steps document.
+pub const STEPS_SCHEMA: &str = include_str!("../schema/steps-1.schema.json");
+
+#[derive(Serialize)]
+struct StepsDocument {
+    schema: &'static str,
+    version: u32,
+    title: String,
+    squares: Vec<StepsSquare>,
+    start: StepsBoard,
+    steps: Vec<StepsStep>,
+    outcome: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct StepsSquare {
+    id: String,
+    x: f32,
+    y: f32,
+    mine: bool,
+    secret: bool,
+}
+
+#[derive(Serialize)]
+struct StepsBoard {
+    revealed: Vec<String>,
+    flagged: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct StepsStep {
+    kind: &'static str,
+    explanation: String,
+    narration: Vec<String>,
+    revealed: Vec<String>,
+    flagged: Vec<String>,
+    constraints: Vec<StepsConstraint>,
+    deductions: Vec<StepsDeduction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    milliseconds: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct StepsConstraint {
+    squares: Vec<String>,
+    min_mines: usize,
+    max_mines: usize,
+    technique: String,
+    depth: usize,
+}
+
+#[derive(Serialize)]
+struct StepsDeduction {
+    action: &'static str,
+    squares: Vec<String>,
+    constraint: usize,
+}
+
+/// Every step of a solve as a JSON document following [`STEPS_SCHEMA`], for tools that want to
+/// replay or study a solve.  `steps` are the states after each step, as for [`html_walkthrough`],
+/// and may stop before the solve does.  `durations` are how long the steps took, if they were timed.
+pub fn steps_json(title: &str, gui: &PuzzleGui, start: &PuzzleState, steps: &[(PuzzleState, StepResult)], durations: &[Duration]) -> String {
+    let base = &start.base;
+    let ids = |bits: Bits| bits.iter_ones().map(|square| base.id_labels[square].clone()).collect::<Vec<_>>();
+    let constraint = |c: &Constraint| StepsConstraint {
+        squares: ids(c.bits),
+        min_mines: c.min_mines,
+        max_mines: c.max_mines,
+        technique: c.technique.name().replace(' ', "-"),
+        depth: c.depth,
+    };
+
+    let document = StepsDocument {
+        schema: "tametsi-steps",
+        version: STEPS_SCHEMA_VERSION,
+        title: title.to_string(),
+        squares: gui.squares.iter().enumerate().map(|(i, object)| StepsSquare {
+            id: base.id_labels[i].clone(),
+            x: object.x,
+            y: object.y,
+            mine: base.mines[i],
+            secret: base.unknowns[i],
+        }).collect(),
+        start: StepsBoard { revealed: ids(start.revealed), flagged: ids(start.flagged) },
+        steps: steps.iter().enumerate().map(|(i, (_, result))| {
+            let (kind, revealed, flagged, constraints, deductions) = match result {
+                StepResult::Progress{revealed, flagged, deductions} => {
+                    let steps = deductions.iter().enumerate().map(|(i, deduction)| StepsDeduction {
+                        action: match deduction.action {
+                            Action::Reveal => "reveal",
+                            Action::Flag => "flag",
+                        },
+                        squares: ids(deduction.cells),
+                        constraint: i,
+                    }).collect();
+                    ("progress", *revealed, *flagged, deductions.iter().map(|deduction| constraint(&deduction.constraint)).collect(), steps)
+                }
+                StepResult::CrossConstraint(c) => ("cross", Bits::zeroed(), Bits::zeroed(), vec![constraint(c)], Vec::new()),
+                StepResult::CliqueConstraint(c) => ("clique", Bits::zeroed(), Bits::zeroed(), vec![constraint(c)], Vec::new()),
+                StepResult::Stuck => ("stuck", Bits::zeroed(), Bits::zeroed(), Vec::new(), Vec::new()),
+                StepResult::UnexpectedStop(_) => ("stopped", Bits::zeroed(), Bits::zeroed(), Vec::new(), Vec::new()),
+                StepResult::Contradiction(cs) => ("contradiction", Bits::zeroed(), Bits::zeroed(), cs.iter().map(constraint).collect(), Vec::new()),
+                StepResult::Finished => ("finished", Bits::zeroed(), Bits::zeroed(), Vec::new(), Vec::new()),
+            };
+            let before = if i == 0 { start } else { &steps[i - 1].0 };
+            let milliseconds = durations.get(i).map(|took| took.as_secs_f64() * 1000.0);
+            StepsStep { kind, explanation: describe_step(result, base), narration: narrate(result, before), revealed: ids(revealed), flagged: ids(flagged), constraints, deductions, milliseconds }
+        }).collect(),
+        outcome: steps.last().and_then(|(_, result)| SolveOutcome::from_step(result)).map(|outcome| match outcome {
+            SolveOutcome::Solved => "solved",
+            SolveOutcome::Stuck => "stuck",
+            SolveOutcome::Stopped(_) => "stopped",
+            SolveOutcome::Contradiction(_) => "contradiction",
+        }),
+    };
+    serde_json::to_string_pretty(&document).expect("Unable to write JSON!") + "\n"
+}
+
+#[derive(Serialize)]
+struct TechniqueRow<'a> {
+    puzzle: &'a str,
+    outcome: String,
+    steps: usize,
+    difficulty: f64,
+    /// Deductions made with each technique, by its hyphenated name.
+    techniques: BTreeMap<String, usize>,
+}
+
+/// A row per report with how many deductions each technique made in the solve, so a puzzle's
+/// difficulty can be described by what it takes rather than by a score alone.
+pub fn technique_table(reports: &[SolveReport], format: TableFormat) -> String {
+    let column = |technique: Technique| technique.name().replace(' ', "-");
+    match format {
+        TableFormat::Csv => {
+            let mut csv = String::from("puzzle,outcome,steps,difficulty");
+            for &technique in &Technique::ALL {
+                write!(csv, ",{}", column(technique)).unwrap();
+            }
+            for report in reports {
+                write!(csv, "\n{},{},{},{:.2}", csv_field(&report.title), csv_field(&report.outcome.to_string()), report.stats.steps, report.difficulty).unwrap();
+                for &technique in &Technique::ALL {
+                    write!(csv, ",{}", report.stats.techniques.get(technique)).unwrap();
+                }
+            }
+            csv.push('\n');
+            csv
+        }
+        TableFormat::Json => {
+            let rows = reports.iter().map(|report| TechniqueRow {
+                puzzle: &report.title,
+                outcome: report.outcome.to_string(),
+                steps: report.stats.steps,
+                difficulty: report.difficulty,
+                techniques: Technique::ALL.iter().map(|&technique| (column(technique), report.stats.techniques.get(technique))).collect(),
+            }).collect::<Vec<_>>();
+            serde_json::to_string_pretty(&rows).expect("Unable to write JSON!") + "\n"
+        }
+    }
+}
+
+/// Solves from the history as a table with a row per solve, in the order given.
+pub fn history_table(entries: &[HistoryEntry], format: TableFormat) -> String {
+    match format {
+        TableFormat::Csv => {
+            let mut csv = String::from("date,puzzle,fingerprint,tier,max-cells,max-mines,outcome,steps,crossings,difficulty");
+            for entry in entries {
+                let (config, report) = (&entry.config, &entry.report);
+                write!(csv, "\n{},{},{},{},{},{},{},{},{},{:.2}", entry.date(), csv_field(&report.title), entry.puzzle, config.tier.name(), config.max_cells, config.max_mines, csv_field(&report.outcome.to_string()), report.stats.steps, report.stats.crosses, report.difficulty).unwrap();
+            }
+            csv.push('\n');
+            csv
+        }
+        TableFormat::Json => serde_json::to_string_pretty(entries).expect("Unable to write JSON!") + "\n",
+    }
+}
+
+/// Quotes a CSV field if it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+
+use crate::solver::Constraint;
+
+/// A predicate over constraints, such as `size<=3 && max-min==1`.
+///
+/// Numbers can be combined with `+` and `-` and compared with `<`, `<=`, `>`, `>=`, `==` and `!=`.
+/// Comparisons can be combined with `&&`, `||` and `!`, and grouped with parentheses.  The values of a
+/// constraint are `size`, `min`, `max` and `depth`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ConstraintFilter {
+    expr: Expr,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Expr {
+    Value(Value),
+    Number(i64),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Comparison, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Value {
+    Size,
+    Min,
+    Max,
+    Depth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Word(String),
+    Number(i64),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => f.write_str(word),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 13] = ["<=", ">=", "==", "!=", "&&", "||", "<", ">", "!", "+", "-", "(", ")"];
+
+impl ConstraintFilter {
+    pub fn parse(source: &str) -> Result<ConstraintFilter, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = ExprParser { tokens, position: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {}", token));
+        }
+        if !expr.is_condition() {
+            return Err(String::from("The filter has to be a comparison, like size<=3"));
+        }
+
+        Ok(ConstraintFilter { expr })
+    }
+
+    pub fn matches(&self, constraint: &Constraint) -> bool {
+        self.expr.eval(constraint) != 0
+    }
+}
+
+impl Expr {
+    /// Whether this is true or false, rather than a number.
+    fn is_condition(&self) -> bool {
+        matches!(self, Expr::Compare(..) | Expr::And(..) | Expr::Or(..) | Expr::Not(..))
+    }
+
+    /// Conditions evaluate to 1 when true and 0 when false.
+    fn eval(&self, c: &Constraint) -> i64 {
+        match self {
+            Expr::Value(Value::Size) => c.size as i64,
+            Expr::Value(Value::Min) => c.min_mines as i64,
+            Expr::Value(Value::Max) => c.max_mines as i64,
+            Expr::Value(Value::Depth) => c.depth as i64,
+            Expr::Number(n) => *n,
+            Expr::Add(a, b) => a.eval(c).saturating_add(b.eval(c)),
+            Expr::Sub(a, b) => a.eval(c).saturating_sub(b.eval(c)),
+            Expr::Compare(a, comparison, b) => {
+                let (a, b) = (a.eval(c), b.eval(c));
+                let result = match comparison {
+                    Comparison::Less => a < b,
+                    Comparison::LessOrEqual => a <= b,
+                    Comparison::Greater => a > b,
+                    Comparison::GreaterOrEqual => a >= b,
+                    Comparison::Equal => a == b,
+                    Comparison::NotEqual => a != b,
+                };
+                result as i64
+            }
+            Expr::And(a, b) => (a.eval(c) != 0 && b.eval(c) != 0) as i64,
+            Expr::Or(a, b) => (a.eval(c) != 0 || b.eval(c) != 0) as i64,
+            Expr::Not(a) => (a.eval(c) == 0) as i64,
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(next) = rest.chars().next() {
+        let length = if next.is_ascii_alphabetic() {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..length].to_ascii_lowercase()));
+            length
+        } else if next.is_ascii_digit() {
+            let length = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..length].parse().map_err(|_| format!("Number too large: {}", &rest[..length]))?));
+            length
+        } else {
+            let symbol = SYMBOLS.iter().find(|&&symbol| rest.starts_with(symbol)).ok_or(format!("Unexpected character: {}", next))?;
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        };
+        rest = rest[length..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent, from loosest to tightest binding: `||`, `&&`, `!`, comparisons, `+` and `-`.
+struct ExprParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            let expr = self.not()?;
+            if !expr.is_condition() {
+                return Err(String::from("! needs a comparison after it"));
+            }
+            Ok(Expr::Not(Box::new(expr)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        let comparison = [
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ].iter().find(|(symbol, _)| self.eat(symbol)).map(|&(_, comparison)| comparison);
+
+        match comparison {
+            Some(comparison) => Ok(Expr::Compare(Box::new(left), comparison, Box::new(self.sum()?))),
+            None => Ok(left),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.atom()?;
+        loop {
+            if self.eat("+") {
+                expr = Expr::Add(Box::new(expr), Box::new(self.atom()?));
+            } else if self.eat("-") {
+                expr = Expr::Sub(Box::new(expr), Box::new(self.atom()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.peek().cloned().ok_or("The filter ends too soon")?;
+        self.position += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Word(word) => match word.as_str() {
+                "size" => Ok(Expr::Value(Value::Size)),
+                "min" => Ok(Expr::Value(Value::Min)),
+                "max" => Ok(Expr::Value(Value::Max)),
+                "depth" => Ok(Expr::Value(Value::Depth)),
+                _ => Err(format!("Unknown value: {}.  Use size, min, max or depth", word)),
+            },
+            Token::Symbol("(") => {
+                let expr = self.or()?;
+                if self.eat(")") {
+                    Ok(expr)
+                } else {
+                    Err(String::from("Missing )"))
+                }
+            }
+            Token::Symbol(symbol) => Err(format!("Unexpected {}", symbol)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::Bits, solver::Constraint, technique::Technique};
+
+    use super::ConstraintFilter;
+
+    fn constraint(size: usize, min_mines: usize, max_mines: usize, depth: usize) -> Constraint {
+        Constraint { bits: Bits::zeroed(), min_mines, max_mines, size, technique: Technique::NakedSingle, depth }
+    }
+
+    fn matches(filter: &str, constraint: &Constraint) -> bool {
+        ConstraintFilter::parse(filter).unwrap_or_else(|e| panic!("{}: {}", filter, e)).matches(constraint)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let c = constraint(3, 1, 2, 0);
+        assert!(matches("size==1 && min==5 || max==2", &c));
+        assert!(!matches("size==1 && (min==5 || max==2)", &c));
+        assert!(matches("max-min==1 && size<=3", &c));
+        assert!(matches("size - 1 - 1 == 1", &c));
+        assert!(matches("!size==1 && depth==0", &c));
+    }
+
+    #[test]
+    fn not_needs_a_comparison() {
+        assert!(ConstraintFilter::parse("!size").is_err());
+        assert!(ConstraintFilter::parse("!(size + 1)").is_err());
+        assert!(ConstraintFilter::parse("size").is_err());
+    }
+
+    #[test]
+    fn unclosed_parenthesis_is_an_error() {
+        assert!(ConstraintFilter::parse("(size<=3").is_err());
+        assert!(ConstraintFilter::parse("(size<=3 && (min==1)").is_err());
+        assert!(ConstraintFilter::parse("size<=3)").is_err());
+    }
+
+    #[test]
+    fn sums_saturate_instead_of_overflowing() {
+        let c = constraint(3, 0, 1, 0);
+        assert!(matches("size + 9223372036854775807 > 0", &c));
+        assert!(matches("0 - 9223372036854775807 - size < 0", &c));
+        assert!(ConstraintFilter::parse("size < 9223372036854775808").is_err());
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::HashMap;
+
+use crate::{convert::{self, Adjacency, PuzzleFile, Square}, core::{Bits, Puzzle, PuzzleGui}, difficulty::DifficultyModel, export, report::SolveReport, solver::{Preset, Solver, SolverConfig, StepResult}, technique::TechniqueCounts};
+
+/// How many mine layouts are tried before giving up on a mix of settings.
+const ATTEMPTS: usize = 50;
+/// How many puzzles that fit a place in a pack are found, to choose the easiest from.
+const CANDIDATES: usize = 8;
+/// The least of a cell that has to be inside an outline for the cell to be kept.
+const MIN_COVER: f32 = 0.4;
+
+/// The cells a generated board is made of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shape {
+    Square,
+    Hex,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 2] = [Shape::Square, Shape::Hex];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Square => "square",
+            Shape::Hex => "hex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Shape> {
+        Shape::ALL.iter().copied().find(|shape| shape.name() == name)
+    }
+
+    fn adjacency(self) -> Adjacency {
+        match self {
+            Shape::Square => Adjacency::King,
+            Shape::Hex => Adjacency::Hex,
+        }
+    }
+
+    /// The lines a hint can run along, as a key shared by every cell on the same line in each
+    /// direction.  Squares have columns, rows and both diagonals, and hexagons their three axes.
+    fn lines(self, (x, y): (i64, i64)) -> Vec<(usize, i64)> {
+        match self {
+            Shape::Square => vec![(0, x), (1, y), (2, x + y), (3, x - y)],
+            Shape::Hex => {
+                // Axial coordinates, from the columns with odd ones half a cell lower
+                let r = y - (x - x.rem_euclid(2)) / 2;
+                vec![(0, x), (1, r), (2, -x - r)]
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GeneratorConfig {
+    pub shape: Shape,
+    /// The board is `width` cells across and `height` cells down, or fits an outline in that space.
+    pub width: usize,
+    pub height: usize,
+    /// The silhouette of the board.  Cells mostly outside it are left out and those on its edge are
+    /// cut to it.
+    pub outline: Option<Vec<(f32, f32)>>,
+    /// The fraction of squares that are mines.
+    pub density: f64,
+    /// The number of safe squares that don't show a number once revealed.
+    pub secrets: usize,
+    /// The number of squares revealed at the start.
+    pub starters: usize,
+    /// The same seed and settings always make the same puzzle.
+    pub seed: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig { shape: Shape::Square, width: 8, height: 8, outline: None, density: 0.2, secrets: 0, starters: 1, seed: 0 }
+    }
+}
+
+/// SplitMix64, which is plenty for laying out puzzles and keeps seeds stable between versions.
+pub struct Rng(pub u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// The solver settings a generated puzzle has to be finished with: only what a player can see,
+/// and no guessing.  Leaving out the mine count and keeping to a step budget stops boards that
+/// would need a long search from stalling generation, and those just count as unsolved.
+fn solver_config() -> SolverConfig {
+    SolverConfig { player_knowledge_only: true, known_mine_count: false, ..SolverConfig::default().with_preset(Preset::Fast) }
+}
+
+/// How many squares the solver leaves unresolved, which is 0 when the puzzle can be finished.
+fn unresolved(puzzle: &Puzzle) -> usize {
+    let mut solver = Solver::new(puzzle.clone(), solver_config());
+    match solver.solve() {
+        StepResult::Finished => 0,
+        _ => solver.stats.cells_remaining,
+    }
+}
+
+/// A random puzzle the solver can finish without guessing.  Where the solver gets stuck, hints
+/// along lines of the board are added, choosing the line that resolves the most squares.  If no
+/// line helps, the mines, secret squares and starting squares are laid out again.  Hints that turn
+/// out not to be needed are then taken away.
+pub fn generate(config: &GeneratorConfig) -> Result<PuzzleFile, String> {
+    let adjacency = config.shape.adjacency();
+    let (cells, shapes): (Vec<_>, Vec<_>) = board(config).into_iter().unzip();
+    let size = cells.len();
+    if size < 2 || size > Bits::zeroed().len() {
+        return Err(format!("Boards need between 2 and {} squares", Bits::zeroed().len()));
+    }
+    if !(0.0..1.0).contains(&config.density) {
+        return Err(String::from("The mine density has to be at least 0 and below 1"));
+    }
+    let mine_count = ((size as f64 * config.density).round() as usize).clamp(1, size - 1);
+    if mine_count + config.secrets + config.starters > size {
+        return Err(format!("{} mines, {} secret squares and {} starting squares don't fit in {} squares", mine_count, config.secrets, config.starters, size));
+    }
+
+    let mut rng = Rng(config.seed);
+    let index = cells.iter().enumerate().map(|(i, &cell)| (cell, i)).collect::<HashMap<_, _>>();
+
+    let mut lines: HashMap<(usize, i64), Vec<usize>> = HashMap::new();
+    for (i, &cell) in cells.iter().enumerate() {
+        for key in config.shape.lines(cell) {
+            lines.entry(key).or_default().push(i);
+        }
+    }
+    let mut lines = lines.into_iter().filter(|(_, line)| line.len() > 1).collect::<Vec<_>>();
+    // HashMap order isn't stable, and the seed has to decide everything
+    lines.sort();
+    let mut lines = lines.into_iter().map(|(_, line)| {
+        line.iter().fold(Bits::zeroed(), |mut bits, &square| {
+            bits.set(square, true);
+            bits
+        })
+    }).collect::<Vec<_>>();
+    rng.shuffle(&mut lines);
+
+    for _ in 0..ATTEMPTS {
+        let mut order = (0..size).collect::<Vec<_>>();
+        rng.shuffle(&mut order);
+        let (mines, safe) = order.split_at(mine_count);
+        let (secrets, safe) = safe.split_at(config.secrets);
+
+        let mut squares = cells.iter().enumerate().map(|(i, &(x, y))| {
+            let (px, py) = adjacency.position((x, y));
+            let mut neighbors = adjacency.offsets((x, y)).iter().filter_map(|(dx, dy)| index.get(&(x + dx, y + dy)).copied()).collect::<Vec<_>>();
+            neighbors.sort_unstable();
+            Square { id: format!("{}_{}", x, y), x: px, y: py, points: shapes[i].clone(), neighbors, mine: mines.contains(&i), secret: secrets.contains(&i), revealed: false, flagged: false }
+        }).collect::<Vec<_>>();
+
+        // Start from the squares with the fewest mines around them
+        let mut starters = safe.to_vec();
+        starters.sort_by_key(|&i| squares[i].neighbors.iter().filter(|&&n| squares[n].mine).count());
+        for &i in &starters[..config.starters] {
+            squares[i].revealed = true;
+        }
+
+        let title = format!("Generated {} {}x{} #{}", config.shape.name(), config.width, config.height, config.seed);
+        let mut file = convert::assemble(title, None, squares, Vec::new())?;
+        if add_hints(&mut file.puzzle, &lines) {
+            return Ok(file);
+        }
+    }
+
+    Err(format!("No layout out of {} could be solved without guessing.  Try fewer secret squares, more starting squares or a lower mine density", ATTEMPTS))
+}
+
+/// A cell's lattice coordinates, and its shape around its center.
+type Cell = ((i64, i64), Vec<(f32, f32)>);
+
+/// The cells of the board.  With an outline, it is scaled to fill the board, and only cells mostly
+/// inside it are kept, cut to fit.
+fn board(config: &GeneratorConfig) -> Vec<Cell> {
+    let adjacency = config.shape.adjacency();
+    let cells = (0..config.height as i64).flat_map(|y| (0..config.width as i64).map(move |x| (x, y)));
+    let outline = match &config.outline {
+        Some(outline) if outline.len() > 2 => outline,
+        _ => return cells.map(|cell| (cell, adjacency.points())).collect(),
+    };
+
+    let corners = cells.clone().flat_map(|cell| {
+        let (x, y) = adjacency.position(cell);
+        adjacency.points().into_iter().map(move |(px, py)| (x + px, y + py))
+    }).collect::<Vec<_>>();
+    let (board_min, board_max) = bounds(&corners);
+    let (outline_min, outline_max) = bounds(outline);
+    let scale = ((board_max.0 - board_min.0) / (outline_max.0 - outline_min.0)).min((board_max.1 - board_min.1) / (outline_max.1 - outline_min.1));
+    // Centered on the board, keeping the outline's proportions
+    let offset = (
+        (board_min.0 + board_max.0 - (outline_min.0 + outline_max.0) * scale) / 2.0,
+        (board_min.1 + board_max.1 - (outline_min.1 + outline_max.1) * scale) / 2.0,
+    );
+    let outline = outline.iter().map(|&(x, y)| (x * scale + offset.0, y * scale + offset.1)).collect::<Vec<_>>();
+
+    cells.filter_map(|cell| {
+        let (x, y) = adjacency.position(cell);
+        let shape = adjacency.points().into_iter().map(|(px, py)| (x + px, y + py)).collect::<Vec<_>>();
+        let cut = clip(&outline, &shape);
+        let cover = area(&cut) / area(&shape);
+        if cover < MIN_COVER {
+            None
+        } else if cover > 0.99 {
+            Some((cell, adjacency.points()))
+        } else {
+            Some((cell, cut.into_iter().map(|(px, py)| (px - x, py - y)).collect()))
+        }
+    }).collect()
+}
+
+fn bounds(points: &[(f32, f32)]) -> ((f32, f32), (f32, f32)) {
+    points.iter().fold(((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)), |(min, max), &(x, y)| ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))))
+}
+
+fn area(polygon: &[(f32, f32)]) -> f32 {
+    let twice = (0..polygon.len()).map(|i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        a.0 * b.1 - b.0 * a.1
+    }).sum::<f32>();
+    twice.abs() / 2.0
+}
+
+/// The part of `polygon` inside `convex`, by Sutherland-Hodgman clipping against each of its edges.
+fn clip(polygon: &[(f32, f32)], convex: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let count = convex.len() as f32;
+    let center = convex.iter().fold((0.0, 0.0), |sum, &(x, y)| (sum.0 + x / count, sum.1 + y / count));
+    let side = |a: (f32, f32), b: (f32, f32), p: (f32, f32)| (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+
+    let mut result = polygon.to_vec();
+    for i in 0..convex.len() {
+        let (a, b) = (convex[i], convex[(i + 1) % convex.len()]);
+        let inward = side(a, b, center).signum();
+        let input = std::mem::take(&mut result);
+        for j in 0..input.len() {
+            let (p, q) = (input[j], input[(j + 1) % input.len()]);
+            let (sp, sq) = (side(a, b, p) * inward, side(a, b, q) * inward);
+            if sp >= 0.0 {
+                result.push(p);
+            }
+            if (sp >= 0.0) != (sq >= 0.0) {
+                let t = sp / (sp - sq);
+                result.push((p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t));
+            }
+        }
+    }
+    result
+}
+
+/// The points of the first path in SVG, or of bare path data.  Only straight lines are understood:
+/// the move, line, horizontal, vertical and close commands, in absolute and relative forms.
+pub fn parse_svg_path(text: &str) -> Result<Vec<(f32, f32)>, String> {
+    let data = match text.find(" d=\"") {
+        Some(start) => {
+            let rest = &text[start + 4..];
+            &rest[..rest.find('"').ok_or("The path's d attribute isn't closed")?]
+        }
+        None => text,
+    };
+
+    // Split into commands and numbers, where a sign can start a number with no space before it
+    let mut tokens = Vec::new();
+    let mut number = String::new();
+    for c in data.chars() {
+        let sign = c == '-' || c == '+';
+        if c.is_ascii_digit() || c == '.' || (c == 'e' && !number.is_empty()) || (sign && number.ends_with('e')) {
+            number.push(c);
+            continue;
+        }
+        if !number.is_empty() {
+            tokens.push(std::mem::take(&mut number));
+        }
+        if sign {
+            number.push(c);
+        } else if c.is_ascii_alphabetic() {
+            tokens.push(c.to_string());
+        }
+    }
+    if !number.is_empty() {
+        tokens.push(number);
+    }
+
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    let mut command = 'M';
+    let mut at = (0.0, 0.0);
+    let mut tokens = tokens.iter().peekable();
+    let next = |tokens: &mut std::iter::Peekable<std::slice::Iter<String>>| -> Result<f32, String> {
+        let token = tokens.next().ok_or("The path ends in the middle of a command")?;
+        token.parse::<f32>().map_err(|_| format!("Expected a number in the path, not {}", token))
+    };
+    while let Some(token) = tokens.peek() {
+        if let Some(c) = token.chars().next().filter(char::is_ascii_alphabetic) {
+            command = c;
+            tokens.next();
+            if c == 'Z' || c == 'z' {
+                if points.len() > 2 {
+                    break;
+                }
+                continue;
+            }
+        }
+        let relative = command.is_ascii_lowercase();
+        let base = if relative { at } else { (0.0, 0.0) };
+        at = match command.to_ascii_uppercase() {
+            'M' | 'L' => (base.0 + next(&mut tokens)?, base.1 + next(&mut tokens)?),
+            'H' => (base.0 + next(&mut tokens)?, at.1),
+            'V' => (at.0, base.1 + next(&mut tokens)?),
+            other => return Err(format!("Only straight lines are supported in outlines, not the {} command", other)),
+        };
+        points.push(at);
+        // Numbers after a move are lines to there
+        command = match command {
+            'M' => 'L',
+            'm' => 'l',
+            other => other,
+        };
+    }
+
+    if points.len() < 3 {
+        return Err(String::from("An outline needs at least three points"));
+    }
+    Ok(points)
+}
+
+/// Adds hints from `lines` until the solver can finish the puzzle, then takes away the added ones
+/// it can do without, most recently added first.  Returns false if no line gets the solver unstuck.
+fn add_hints(puzzle: &mut Puzzle, lines: &[Bits]) -> bool {
+    let existing = puzzle.hints.len();
+    let mut left = unresolved(puzzle);
+    while left > 0 {
+        let mut best = None;
+        for &line in lines {
+            if puzzle.hints.contains(&line) {
+                continue;
+            }
+            puzzle.hints.push(line);
+            let remaining = unresolved(puzzle);
+            puzzle.hints.pop();
+            if remaining < best.map_or(left, |(_, remaining)| remaining) {
+                best = Some((line, remaining));
+            }
+        }
+
+        match best {
+            Some((line, remaining)) => {
+                puzzle.hints.push(line);
+                left = remaining;
+            }
+            None => return false,
+        }
+    }
+
+    for i in (existing..puzzle.hints.len()).rev() {
+        let hint = puzzle.hints.remove(i);
+        if unresolved(puzzle) > 0 {
+            puzzle.hints.insert(i, hint);
+        }
+    }
+    true
+}
+
+/// Changes a puzzle a little while keeping it solvable without guessing.  Up to `moves` mines are
+/// moved to other hidden squares, and then hints along the board's rows and columns are added
+/// wherever the solver still gets stuck.  With no moves this repairs a puzzle that needs guessing,
+/// moving one mine after all if hints alone can't do it.  Returns the new puzzle and a description
+/// of each change.
+pub fn repair(puzzle: &Puzzle, gui: &PuzzleGui, moves: usize, seed: u64) -> Result<(Puzzle, Vec<String>), String> {
+    let lines = gui_lines(gui);
+    let mut rng = Rng(seed);
+    for attempt in 0..ATTEMPTS {
+        let mut repaired = puzzle.clone();
+        let mut changes = Vec::new();
+        for _ in 0..if attempt == 0 { moves } else { moves.max(1) } {
+            let mines = (repaired.mines & !repaired.flagged).iter_ones().collect::<Vec<_>>();
+            let free = (0..repaired.size()).filter(|&i| !repaired.mines[i] && !repaired.revealed[i] && !repaired.unknowns[i]).collect::<Vec<_>>();
+            if mines.is_empty() || free.is_empty() {
+                break;
+            }
+            let (from, to) = (mines[rng.below(mines.len())], free[rng.below(free.len())]);
+            repaired.mines.set(from, false);
+            repaired.mines.set(to, true);
+            changes.push(format!("Moved a mine from {} to {}", repaired.id_labels[from], repaired.id_labels[to]));
+        }
+
+        let existing = repaired.hints.len();
+        if add_hints(&mut repaired, &lines) {
+            for &hint in &repaired.hints[existing..] {
+                changes.push(format!("Added a hint on {}", repaired.labels(hint)));
+            }
+            return Ok((repaired, changes));
+        }
+    }
+
+    Err(format!("No hint along a row or column gets the solver unstuck, even after {} tries at moving mines", ATTEMPTS))
+}
+
+/// A change that lets the solver finish a puzzle on its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Suggestion {
+    Hint(Bits),
+    Reveal(usize),
+}
+
+impl Suggestion {
+    pub fn describe(self, puzzle: &Puzzle) -> String {
+        match self {
+            Suggestion::Hint(bits) => format!("Add a hint on {}", puzzle.labels(bits)),
+            Suggestion::Reveal(square) => format!("Reveal {}", puzzle.id_labels[square]),
+        }
+    }
+}
+
+/// The smallest single changes that let the solver finish a puzzle it gets stuck on with `config`:
+/// every safe square that could be revealed, and the hints along rows and columns with the fewest
+/// squares.  `None` if the solver doesn't get stuck, and empty if nothing short of two changes helps.
+/// Changes are tried without the total mine count, which makes stuck solves far slower, so some
+/// that only work with it are missed.  Those found work either way.
+pub fn suggest_fixes(puzzle: &Puzzle, gui: &PuzzleGui, config: SolverConfig) -> Option<Vec<Suggestion>> {
+    if matches!(Solver::new(puzzle.clone(), config).solve(), StepResult::Finished) {
+        return None;
+    }
+    let config = SolverConfig { known_mine_count: false, ..config };
+    // Each change carries on from where the solver got stuck, rather than solving from the start
+    let mut stuck = Solver::new(puzzle.clone(), config);
+    stuck.solve();
+    let unresolved = (0..puzzle.size()).filter(|&i| !stuck.puzzle.revealed[i] && !stuck.puzzle.flagged[i]).fold(Bits::zeroed(), |mut bits, i| {
+        bits.set(i, true);
+        bits
+    });
+    let finishes = |suggestion: Suggestion| {
+        let mut solver = stuck.clone();
+        match suggestion {
+            Suggestion::Hint(bits) => solver.add_hint(bits),
+            Suggestion::Reveal(square) => solver.reveal(square),
+        }
+        matches!(solver.solve(), StepResult::Finished)
+    };
+
+    let mut suggestions = (unresolved & !puzzle.mines).iter_ones().map(Suggestion::Reveal).filter(|&suggestion| finishes(suggestion)).collect::<Vec<_>>();
+
+    let mut lines = gui_lines(gui);
+    lines.retain(|line| (*line & unresolved).any() && !puzzle.hints.contains(line));
+    lines.sort_by_key(|line| line.count_ones());
+    let mut smallest = None;
+    for line in lines {
+        if smallest.is_some_and(|smallest| line.count_ones() > smallest) {
+            break;
+        }
+        if finishes(Suggestion::Hint(line)) {
+            smallest = Some(line.count_ones());
+            suggestions.push(Suggestion::Hint(line));
+        }
+    }
+    Some(suggestions)
+}
+
+/// The rows and columns of squares in a puzzle's layout: squares whose centers line up.
+fn gui_lines(gui: &PuzzleGui) -> Vec<Bits> {
+    let mut lines: HashMap<(bool, i64), Bits> = HashMap::new();
+    for (i, square) in gui.squares.iter().enumerate() {
+        lines.entry((false, square.x.round() as i64)).or_insert_with(Bits::zeroed).set(i, true);
+        lines.entry((true, square.y.round() as i64)).or_insert_with(Bits::zeroed).set(i, true);
+    }
+    let mut lines = lines.into_iter().filter(|(_, line)| line.count_ones() > 1).collect::<Vec<_>>();
+    lines.sort_by_key(|&(key, _)| key);
+    lines.into_iter().map(|(_, line)| line).collect()
+}
+
+/// A web page of the solve that shows a generated puzzle needs no guessing, step by step, with
+/// where its difficulty score comes from.
+pub fn walkthrough(file: &PuzzleFile) -> String {
+    let mut solver = Solver::new(file.puzzle.clone(), solver_config());
+    let start = solver.puzzle.clone();
+    let steps = export::solve_steps(&mut solver);
+    export::html_walkthrough(&file.title, &file.gui, &start, &steps, &solver.stats, export::Theme::Solver)
+}
+
+/// One puzzle of a pack, with how hard the solver found it.
+pub struct PackPuzzle {
+    pub file: PuzzleFile,
+    pub score: f64,
+    pub techniques: TechniqueCounts,
+}
+
+/// A puzzle from a seed sweep, with the seed that made it and how the solver fared.
+pub struct SweepPuzzle {
+    pub seed: u64,
+    pub file: PuzzleFile,
+    pub report: SolveReport,
+}
+
+/// Makes a puzzle from each of `count` seeds, starting at `config.seed`, and keeps the `keep` the
+/// solver scores hardest, hardest first.  Seeds that don't make a puzzle are skipped.
+pub fn sweep(config: &GeneratorConfig, count: u64, keep: usize) -> Vec<SweepPuzzle> {
+    let mut kept: Vec<SweepPuzzle> = Vec::new();
+    for seed in config.seed..config.seed.saturating_add(count) {
+        let mut file = match generate(&GeneratorConfig { seed, ..config.clone() }) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        file.title = format!("Seed {}", seed);
+        let report = SolveReport::generate(&file.title, &file.puzzle, solver_config(), &[], false);
+        if kept.len() == keep && kept.last().is_none_or(|easiest| report.difficulty <= easiest.report.difficulty) {
+            continue;
+        }
+        let place = kept.iter().position(|puzzle| puzzle.report.difficulty < report.difficulty).unwrap_or(kept.len());
+        kept.insert(place, SweepPuzzle { seed, file, report });
+        kept.truncate(keep);
+    }
+    kept
+}
+
+/// `count` puzzles that get harder one after another, building up to the mine density and secret
+/// squares `config` asks for.  Each puzzle scores higher than the one before and uses every
+/// technique it did, so techniques are introduced one by one and then kept.  Of the puzzles that
+/// fit a place, the easiest is kept, which leaves room for the ones after it.
+pub fn generate_pack(config: &GeneratorConfig, count: usize) -> Result<Vec<PackPuzzle>, String> {
+    let model = DifficultyModel::default();
+    let mut rng = Rng(config.seed);
+    let mut pack: Vec<PackPuzzle> = Vec::new();
+    for level in 0..count {
+        let progress = if count > 1 { level as f64 / (count - 1) as f64 } else { 1.0 };
+        let mut settings = config.clone();
+        settings.density = config.density * (0.5 + 0.5 * progress);
+        settings.secrets = (config.secrets as f64 * progress).round() as usize;
+
+        let mut best: Option<PackPuzzle> = None;
+        let mut found = 0;
+        for _ in 0..ATTEMPTS {
+            if found == CANDIDATES {
+                break;
+            }
+            settings.seed = rng.next();
+            let file = match generate(&settings) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            let mut solver = Solver::new(file.puzzle.clone(), solver_config());
+            solver.solve();
+            let score = model.score(&solver.stats);
+            let techniques = solver.stats.techniques;
+
+            let fits = pack.last().is_none_or(|previous| score > previous.score && previous.techniques.used().all(|(technique, _)| techniques.get(technique) > 0));
+            if !fits {
+                continue;
+            }
+            found += 1;
+            if best.as_ref().is_none_or(|best| score < best.score) {
+                best = Some(PackPuzzle { file, score, techniques });
+            }
+        }
+
+        let mut puzzle = best.ok_or(format!("Couldn't make puzzle {} harder than the one before it.  Try a smaller pack or a larger board", level + 1))?;
+        puzzle.file.title = format!("Level {}", level + 1);
+        pack.push(puzzle);
+    }
+    Ok(pack)
+}
@@ -0,0 +1,71 @@
+use std::{hash::{Hash, Hasher}, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{core::Fingerprint, report::SolveReport, solver::SolverConfig};
+
+/// A completed solve, as the history keeps it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    /// A fingerprint of the puzzle file, which stays the same when it's renamed or moved.
+    pub puzzle: String,
+    pub config: SolverConfig,
+    pub report: SolveReport,
+}
+
+impl HistoryEntry {
+    /// An entry for a solve of the puzzle with these file contents that just finished.
+    pub fn new(contents: &[u8], config: SolverConfig, report: SolveReport) -> HistoryEntry {
+        let mut hasher = Fingerprint::default();
+        contents.hash(&mut hasher);
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        HistoryEntry { at, puzzle: format!("{:016x}", hasher.finish()), config, report }
+    }
+
+    /// When the solve finished, as `YYYY-MM-DD HH:MM` in UTC.
+    pub fn date(&self) -> String {
+        // Days to a civil date, from Howard Hinnant's algorithm
+        let days = (self.at / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, self.at / 3600 % 24, self.at / 60 % 60)
+    }
+}
+
+/// Every completed solve, kept in a database in the local data directory.  Only one program can
+/// have it open at a time.
+#[derive(Clone)]
+pub struct History {
+    db: sled::Db,
+}
+
+impl History {
+    /// The history in the user's data directory, or `None` if there isn't one or it's in use.
+    pub fn open() -> Option<History> {
+        let mut path = dirs::data_local_dir()?;
+        path.push("tametsi");
+        path.push("history");
+        sled::open(path).ok().map(|db| History { db })
+    }
+
+    /// Adds a solve.  Failing to save it only loses it from the history, so errors are ignored.
+    pub fn record(&self, entry: &HistoryEntry) {
+        if let (Ok(id), Ok(json)) = (self.db.generate_id(), serde_json::to_vec(entry)) {
+            let _ = self.db.insert(id.to_be_bytes(), json);
+            let _ = self.db.flush();
+        }
+    }
+
+    /// Every solve recorded, oldest first.  Entries that can't be read are left out.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.db.iter().values().filter_map(|value| serde_json::from_slice(&value.ok()?).ok()).collect()
+    }
+}
@@ -0,0 +1,132 @@
+use eframe::egui::{Align2, Color32, Painter, Pos2, Rect, Shape, Stroke, TextStyle, Vec2, color::Hsva};
+
+use crate::{core::{Bits, PuzzleGui}, solver::Constraint};
+
+/// Constraints covering more squares than this aren't drawn, since they would tie most of the
+/// graph together (the total mine count, for one).
+const MAX_EDGE_SIZE: usize = 24;
+
+const REPULSION: f32 = 0.02;
+const ATTRACTION: f32 = 0.05;
+const DAMPING: f32 = 0.8;
+/// Below this much total movement the layout is considered settled.
+const SETTLED: f32 = 0.001;
+
+/// A force directed layout of the unsolved constraints, drawn with squares as nodes and each
+/// constraint as a hyperedge joining its squares.  Positions carry over between updates, so when
+/// crossing splits a constraint the pieces can be seen drifting apart.
+pub struct Hypergraph {
+    constraints: Vec<Constraint>,
+    /// Constraints left out for being too large.
+    pub hidden: usize,
+    /// Positions in the unit square, for squares covered by a drawn constraint.
+    positions: Vec<Option<Pos2>>,
+    velocities: Vec<Vec2>,
+    settled: bool,
+}
+
+impl Hypergraph {
+    pub fn new(gui: &PuzzleGui) -> Hypergraph {
+        Hypergraph {
+            constraints: Vec::new(),
+            hidden: 0,
+            positions: vec![None; gui.squares.len()],
+            velocities: vec![Vec2::ZERO; gui.squares.len()],
+            settled: true,
+        }
+    }
+
+    /// Replaces the constraints.  Squares new to the graph start where they are on the board.
+    pub fn set_constraints(&mut self, constraints: Vec<Constraint>, gui: &PuzzleGui) {
+        let total = constraints.len();
+        self.constraints = constraints.into_iter().filter(|c| c.size <= MAX_EDGE_SIZE).collect();
+        self.hidden = total - self.constraints.len();
+        let covered = self.constraints.iter().fold(Bits::zeroed(), |covered, c| covered | c.bits);
+        let width = (gui.max_x - gui.min_x).max(1.0);
+        let height = (gui.max_y - gui.min_y).max(1.0);
+        for (square, position) in self.positions.iter_mut().enumerate() {
+            if !covered[square] {
+                *position = None;
+            } else if position.is_none() {
+                let object = &gui.squares[square];
+                *position = Some(Pos2::new((object.x - gui.min_x) / width, (object.y - gui.min_y) / height));
+                self.velocities[square] = Vec2::ZERO;
+            }
+        }
+        self.settled = false;
+    }
+
+    /// Moves the layout one step towards balance.  Returns whether anything is still moving.
+    pub fn relax(&mut self) -> bool {
+        if self.settled {
+            return false;
+        }
+
+        let nodes = self.positions.iter().enumerate().filter_map(|(i, p)| p.map(|p| (i, p))).collect::<Vec<_>>();
+        let mut forces = vec![Vec2::ZERO; self.positions.len()];
+
+        // Every pair of squares pushes apart
+        for (a, &(i, pi)) in nodes.iter().enumerate() {
+            for &(j, pj) in &nodes[a + 1..] {
+                let delta = pi - pj;
+                let distance = delta.length().max(0.01);
+                let push = delta / distance * (REPULSION / (distance * distance)).min(1.0) * REPULSION;
+                forces[i] += push;
+                forces[j] -= push;
+            }
+        }
+
+        // Every constraint pulls its squares towards their middle
+        for constraint in &self.constraints {
+            let members = constraint.bits.iter_ones().filter_map(|square| self.positions[square].map(|p| (square, p))).collect::<Vec<_>>();
+            let center = members.iter().fold(Vec2::ZERO, |sum, &(_, p)| sum + p.to_vec2()) / members.len() as f32;
+            for &(square, p) in &members {
+                forces[square] += (center.to_pos2() - p) * ATTRACTION;
+            }
+        }
+
+        let mut movement = 0.0;
+        for &(i, p) in &nodes {
+            self.velocities[i] = (self.velocities[i] + forces[i]) * DAMPING;
+            self.positions[i] = Some(p + self.velocities[i]);
+            movement += self.velocities[i].length();
+        }
+
+        self.settled = movement < SETTLED;
+        !self.settled
+    }
+
+    /// Draws the graph, scaled to fit `rect`.  Squares are colored like the board.
+    pub fn draw(&self, painter: &Painter, rect: Rect, colors: impl Fn(usize) -> Color32) {
+        let nodes = self.positions.iter().filter_map(|&p| p).collect::<Vec<_>>();
+        if nodes.is_empty() {
+            painter.text(rect.center(), Align2::CENTER_CENTER, "No unsolved constraints", TextStyle::Body, Color32::WHITE);
+            return;
+        }
+
+        let bounds = nodes.iter().fold(Rect::NOTHING, |bounds, &p| bounds.union(Rect::from_min_max(p, p)));
+        let scale = (rect.width() / bounds.width().max(0.01)).min(rect.height() / bounds.height().max(0.01));
+        let to_screen = |p: Pos2| rect.center() + (p - bounds.center()) * scale;
+
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            let members = constraint.bits.iter_ones().filter_map(|square| self.positions[square]).map(to_screen).collect::<Vec<_>>();
+            let center = (members.iter().fold(Vec2::ZERO, |sum, p| sum + p.to_vec2()) / members.len() as f32).to_pos2();
+            let color = edge_color(index);
+            for &member in &members {
+                painter.line_segment([center, member], Stroke::new(1.0, color));
+            }
+            painter.text(center, Align2::CENTER_CENTER, format!("{}->{}/{}", constraint.min_mines, constraint.max_mines, constraint.size), TextStyle::Small, color);
+        }
+
+        for (square, position) in self.positions.iter().enumerate() {
+            if let Some(p) = position {
+                painter.add(Shape::circle_filled(to_screen(*p), 5.0, colors(square)));
+            }
+        }
+    }
+}
+
+/// A color for each constraint, spread around the hue circle so neighbors are easy to tell apart.
+fn edge_color(index: usize) -> Color32 {
+    Hsva::new((index as f32 * 0.618_034).fract(), 0.8, 1.0, 1.0).into()
+}
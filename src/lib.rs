@@ -0,0 +1,18 @@
+#![forbid(unsafe_code)]
+
+//! The deduction engine on its own, without the GUI, the command line or anything that reads files,
+//! so it builds with `default-features = false` for wasm and other projects.
+//!
+//! Build a [`Puzzle`], hand it to [`Solver::new`] and call [`Solver::step`] until the
+//! [`StepResult`] says it has finished or is stuck.
+
+pub mod ascii;
+pub mod core;
+pub mod exhaustive;
+pub mod filter;
+pub mod solver;
+pub mod symmetry;
+pub mod technique;
+pub mod trace;
+
+pub use crate::{core::{Bits, Puzzle}, solver::{Solver, SolverConfig, StepResult}};
@@ -1,25 +1,41 @@
 #![forbid(unsafe_code)]
 
+// The solver and what it needs are the library, so they build without the rest
+use tametsi::{ascii, core, filter, solver, technique, trace};
 
-mod solver;
 mod parser;
-mod core;
 mod app;
+mod engine;
+mod cli;
+mod difficulty;
+mod report;
+mod cache;
+mod config;
+mod hypergraph;
+mod export;
+mod convert;
+mod generator;
+mod history;
+mod video;
+mod narration;
+mod race;
+mod rules;
+mod script;
 
 
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        if let Err(error) = cli::run(args) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let app = app::TemplateApp::default();
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(Box::new(app), native_options);
 }
-
-/*
-fn main() {
-    let parser = parser::Parser::new();
-    for listing in parser.read_all_puzzles() {
-        println!("Solving puzzle {}", listing.name);
-        solver::Solver::new(listing.read(), 9, 3).solve();
-    }
-} */
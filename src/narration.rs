@@ -0,0 +1,156 @@
+use crate::{app::describe_step, core::{Bits, Puzzle}, solver::{Action, Constraint, PuzzleState, StepResult}, technique::Technique};
+
+/// Something a player can see that says how many mines are in a group of squares: the number on a
+/// revealed square, a hint or the board's mine count.
+struct Clue {
+    name: String,
+    /// The squares it covers that aren't revealed or flagged yet.
+    bits: Bits,
+    /// The mines left among them.
+    mines: usize,
+}
+
+impl Clue {
+    /// Every clue on a board where the squares in `revealed` and `flagged` are known, numbers first.
+    fn all(base: &Puzzle, revealed: Bits, flagged: Bits) -> Vec<Clue> {
+        let open = !revealed & !flagged;
+        let clue = |name: String, group: Bits| Clue { name, bits: group & open, mines: base.displayed_count(group).saturating_sub((group & flagged).count_ones()) };
+
+        let mut clues = Vec::new();
+        for square in (revealed & !base.unknowns).iter_ones() {
+            let neighbors = base.neighbors[square];
+            clues.push(clue(format!("the {} on {}", base.displayed_count(neighbors), base.id_labels[square]), neighbors));
+        }
+        for &hint in &base.hints {
+            clues.push(clue(format!("the hint on {}", brief(base, hint)), hint));
+        }
+        let mut board = Bits::zeroed();
+        for square in 0..base.size() {
+            board.set(square, true);
+        }
+        clues.push(clue(String::from("the mine count"), board));
+        clues.retain(|clue| clue.bits.any());
+        clues
+    }
+}
+
+/// The step as a tutorial would put it: a sentence for each thing it found, naming the clues that
+/// show it.  `before` is the board the step was taken on.
+pub fn narrate(result: &StepResult, before: &PuzzleState) -> Vec<String> {
+    let base = &before.base;
+    match result {
+        StepResult::Progress{deductions, ..} => {
+            // Squares resolved by one deduction can show the number the next one comes from
+            let (mut revealed, mut flagged) = (before.revealed, before.flagged);
+            deductions.iter().map(|deduction| {
+                let sentence = format!("{}, so {} {}.", capitalize(&reason(&deduction.constraint, revealed, flagged, base)), list(base, deduction.cells), conclusion(deduction.action, deduction.cells.count_ones()));
+                match deduction.action {
+                    Action::Reveal => revealed |= deduction.cells,
+                    Action::Flag => flagged |= deduction.cells,
+                }
+                sentence
+            }).collect()
+        }
+        StepResult::CrossConstraint(constraint) => vec![format!("{}.", capitalize(&reason(constraint, before.revealed, before.flagged, base)))],
+        _ => vec![describe_step(result, base)],
+    }
+}
+
+fn conclusion(action: Action, count: usize) -> &'static str {
+    match (action, count) {
+        (Action::Reveal, 1) => "is safe",
+        (Action::Reveal, _) => "are safe",
+        (Action::Flag, 1) => "is a mine",
+        (Action::Flag, _) => "are mines",
+    }
+}
+
+/// Why `constraint` holds, from one clue or two overlapping ones if they show it, otherwise from
+/// the constraint alone.  The constraint may be older than what is known, so its own squares are
+/// treated as unknown.
+fn reason(constraint: &Constraint, revealed: Bits, flagged: Bits, base: &Puzzle) -> String {
+    let clues = Clue::all(base, revealed & !constraint.bits, flagged & !constraint.bits);
+    let range = mines(constraint.min_mines, constraint.max_mines);
+    if let Some(clue) = clues.iter().find(|clue| clue.bits == constraint.bits) {
+        return format!("{} leaves {} among {}", clue.name, range, brief(base, clue.bits));
+    }
+
+    // A pair only explains the constraint if what it implies is at least as tight
+    let tight = |(min, max): (usize, usize)| min >= constraint.min_mines && max <= constraint.max_mines;
+    for a in &clues {
+        for b in &clues {
+            let overlap = a.bits & b.bits;
+            if overlap.not_any() || std::ptr::eq(a, b) {
+                continue;
+            }
+            let (overlap_min, overlap_max) = overlap_mines(a, b);
+            if constraint.bits == a.bits & !b.bits && tight((a.mines.saturating_sub(overlap_max), a.mines.saturating_sub(overlap_min))) {
+                let rest = mines(constraint.min_mines, constraint.max_mines);
+                return if (b.bits & !a.bits).not_any() {
+                    format!("{} lies inside {}, which leaves {} for the rest of {} ({})", b.name, a.name, rest, a.name, list(base, constraint.bits))
+                } else {
+                    format!("{} overlaps {} in {}, which leaves {} for the rest of {} ({})", a.name, b.name, list(base, overlap), rest, a.name, list(base, constraint.bits))
+                };
+            }
+            if constraint.bits == overlap && tight((overlap_min, overlap_max)) {
+                return format!("{} and {} overlap in {}, which must hold {}", a.name, b.name, list(base, overlap), range);
+            }
+        }
+    }
+
+    if constraint.technique == Technique::Contradiction {
+        return format!("anything else contradicts the clues, so {} must hold {}", list(base, constraint.bits), range);
+    }
+    let depth = match constraint.depth {
+        0 | 1 => String::new(),
+        depth => format!(" over {} crossings", depth),
+    };
+    format!("working from several clues{}, {} must hold {}", depth, list(base, constraint.bits), range)
+}
+
+/// The fewest and most mines that can be in the squares two clues share.
+fn overlap_mines(a: &Clue, b: &Clue) -> (usize, usize) {
+    let overlap = (a.bits & b.bits).count_ones();
+    let a_rest = a.bits.count_ones() - overlap;
+    let b_rest = b.bits.count_ones() - overlap;
+    let min = a.mines.saturating_sub(a_rest).max(b.mines.saturating_sub(b_rest));
+    let max = overlap.min(a.mines).min(b.mines);
+    (min, max.max(min))
+}
+
+fn mines(min: usize, max: usize) -> String {
+    match (min, max) {
+        (0, 0) => String::from("no mines"),
+        (1, 1) => String::from("exactly 1 mine"),
+        (min, max) if min == max => format!("exactly {} mines", min),
+        (min, max) => format!("between {} and {} mines", min, max),
+    }
+}
+
+/// The IDs of some squares as a list in words, like "A, B and C".
+fn list(base: &Puzzle, bits: Bits) -> String {
+    let labels = bits.iter_ones().map(|square| base.id_labels[square].as_str()).collect::<Vec<_>>();
+    match labels.split_last() {
+        None => String::from("nothing"),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// How many squares [`brief`] names before counting the rest.
+const BRIEF_SQUARES: usize = 6;
+
+/// Like [`list`], but only naming the first few squares of a large group.
+fn brief(base: &Puzzle, bits: Bits) -> String {
+    let count = bits.count_ones();
+    if count <= BRIEF_SQUARES {
+        return list(base, bits);
+    }
+    let named = bits.iter_ones().take(BRIEF_SQUARES - 1).map(|square| base.id_labels[square].as_str()).collect::<Vec<_>>();
+    format!("{} and {} more", named.join(", "), count - named.len())
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
@@ -1,55 +1,384 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{borrow::Cow, cmp::Ordering, collections::{BTreeMap, HashMap}, fs::{self, File}, io::{self, Read, Write}, path::{Path, PathBuf}};
 
-use roxmltree::Document;
+use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
 use steamlocate::SteamDir;
+use zip::ZipArchive;
 
-use crate::core::{Bits, Puzzle, PuzzleGui, SquareDimensions};
+use crate::{ascii::Layout, cache::{FileStamp, ListedFile, ListingCache}, convert::{self, Format, PuzzleFile}, core::{Bits, Diagnostic, Puzzle, PuzzleGui, SquareDimensions}, solver::SolverConfig, trace::Trace};
 
 const TAMETSI_APP_ID: u32 = 709920;
 
+/// Files listing the puzzles in a folder in the order the game shows them, one file name per line.
+const INDEX_FILES: [&str; 2] = ["index.txt", "order.txt"];
+
+/// Puzzles shipped with the program, listed when the game isn't installed.
+const SAMPLES: [(&str, &str); 4] = [
+    ("first_steps.xml", include_str!("../samples/first_steps.xml")),
+    ("crossing.xml", include_str!("../samples/crossing.xml")),
+    ("columns.xml", include_str!("../samples/columns.xml")),
+    ("secrets.xml", include_str!("../samples/secrets.xml")),
+];
+
+/// The extension of bundle files, which are listed alongside puzzles.
+pub const BUNDLE_EXTENSION: &str = "tmb";
+
+/// The first bytes of every bundle.
+const BUNDLE_MAGIC: &[u8; 4] = b"TMTB";
+
+/// Bumped whenever what a bundle holds changes.  Bundles from later versions aren't read.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// A puzzle with everything worked out about it, in one file.  On disk it's [`BUNDLE_MAGIC`], then
+/// the version, length and CRC-32 of the rest as little endian `u32`s, then the rest: this as
+/// zlib compressed JSON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub title: String,
+    pub author: Option<String>,
+    /// The puzzle as XML, the way the game saves it.
+    pub puzzle: String,
+    /// The settings the replay was solved with.
+    pub config: Option<SolverConfig>,
+    /// The squares resolved by each step solved so far.
+    pub replay: Option<Trace>,
+    /// Anything else about the analysis, such as what saved it.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    /// A bundle of `file` with nothing worked out yet, saying which version of the program made it.
+    pub fn new(file: &PuzzleFile) -> Result<Bundle, String> {
+        let mut metadata = BTreeMap::new();
+        metadata.insert(String::from("saved by"), format!("tametsi {}", env!("CARGO_PKG_VERSION")));
+        Ok(Bundle {
+            title: file.title.clone(),
+            author: file.author.clone(),
+            puzzle: convert::write(file, Format::Xml)?,
+            config: None,
+            replay: None,
+            metadata,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let json = serde_json::to_vec(self).expect("Unable to write bundle!");
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).expect("Writing to memory can't fail");
+        let body = encoder.finish().expect("Writing to memory can't fail");
+
+        let mut bytes = BUNDLE_MAGIC.to_vec();
+        bytes.extend_from_slice(&BUNDLE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Bundle, String> {
+        let word = |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+        if bytes.len() < 16 || &bytes[..4] != BUNDLE_MAGIC {
+            return Err(String::from("Not a bundle"));
+        }
+        let version = word(4);
+        if version == 0 || version > BUNDLE_VERSION {
+            return Err(format!("Bundle version {} is newer than this program reads, which is up to {}", version, BUNDLE_VERSION));
+        }
+        let body = &bytes[16..];
+        if body.len() != word(8) as usize {
+            return Err(format!("Bundle is cut short, with {} of {} bytes", body.len(), word(8)));
+        }
+        if crc32fast::hash(body) != word(12) {
+            return Err(String::from("Bundle is damaged, its checksum doesn't match"));
+        }
+
+        let mut json = Vec::new();
+        ZlibDecoder::new(body).read_to_end(&mut json).map_err(|e| format!("Unable to decompress bundle: {}", e))?;
+        serde_json::from_slice(&json).map_err(|e| format!("Unable to read bundle: {}", e))
+    }
+
+    pub fn read(path: &Path) -> Result<Bundle, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        Bundle::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+}
+
+/// How puzzle files with problems are treated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParseMode {
+    /// Any problem is an error, for checking packs before they're released.
+    Strict,
+    /// Load as much as possible and report the problems.
+    Permissive,
+}
+
+impl ParseMode {
+    pub const ALL: [ParseMode; 2] = [ParseMode::Strict, ParseMode::Permissive];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ParseMode::Strict => "Strict",
+            ParseMode::Permissive => "Permissive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ParseMode> {
+        ParseMode::ALL.iter().copied().find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
 #[derive(PartialEq, Eq, Clone)]
 pub struct PuzzleListing {
     pub name: String,
-    path: PathBuf,
+    /// Only set if the file names its author.
+    pub author: Option<String>,
+    /// The pack and chapter the file says it belongs to, if any.
+    pub pack: Option<String>,
+    pub chapter: Option<String>,
+    /// The subfolders of the puzzle directory the file is in.  A zip archive counts as a folder.
+    pub folder: Vec<String>,
+    /// Where the puzzle comes in its folder: first the order the folder's index gives, then the
+    /// rest by file name.
+    pub order: usize,
+    source: Source,
 }
 
-impl PuzzleListing {
-    pub fn new(path: PathBuf) -> Self {
-        let contents = fs::read_to_string(path.clone())
-            .expect(format!("Unable to read file: {}", path.to_string_lossy()).as_str());
-
-        let doc = Document::parse(&contents).expect("Unable to parse XML!");
-        if let Some(title_node) = doc.root().children().flat_map(|f| f.children()).find(|a| a.has_tag_name("TITLE")) {
-            PuzzleListing {
-                name: title_node.text().expect("No title given!").to_string(),
-                path,
-            }
+/// Where a puzzle file is.
+#[derive(PartialEq, Eq, Clone)]
+enum Source {
+    File(PathBuf),
+    /// A file inside a zip archive, which is read without extracting it.
+    Zipped { archive: PathBuf, entry: String },
+    /// One of the sample puzzles built into the program.
+    Bundled(usize),
+    /// A puzzle piped in, read up front since it can only be read once.
+    Stdin { contents: Vec<u8>, format: Format },
+    /// The puzzle in a bundle file, saved with what was worked out about it.
+    Saved(PathBuf),
+}
+
+impl Source {
+    /// The file at `path`, which is read as a bundle if its extension says so.
+    fn at(path: PathBuf) -> Source {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(BUNDLE_EXTENSION)) {
+            Source::Saved(path)
         } else {
-            println!("Length: {}", doc.root().children().count());
-            println!("Tags: {}", doc.root().children().map(|a| a.tag_name().name()).collect::<Vec<_>>().join("\n"));
-            panic!("No title in document! {}", path.to_string_lossy());
+            Source::File(path)
         }
     }
 
+    /// The file's bytes, borrowed where they're already in memory.
+    fn read(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Source::File(path) => fs::read(path).map(Cow::Owned),
+            Source::Zipped { archive, entry } => {
+                let mut archive = ZipArchive::new(File::open(archive)?)?;
+                let mut file = archive.by_name(entry)?;
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                Ok(Cow::Owned(contents))
+            }
+            Source::Bundled(index) => Ok(Cow::Borrowed(SAMPLES[*index].1.as_bytes())),
+            Source::Stdin { contents, .. } => Ok(Cow::Borrowed(contents)),
+            Source::Saved(path) => Bundle::read(path).map(|bundle| Cow::Owned(bundle.puzzle.into_bytes())).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match self {
+            Source::File(path) | Source::Saved(path) => path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            Source::Zipped { archive, entry } => format!("{}/{}", archive.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(), entry),
+            Source::Bundled(index) => format!("samples/{}", SAMPLES[*index].0),
+            Source::Stdin { .. } => String::from("stdin"),
+        }
+    }
+
+    /// Files without an extension another format uses are taken to be XML.
+    fn format(&self) -> Format {
+        match self {
+            Source::Stdin { format, .. } => *format,
+            Source::Saved(_) => Format::Xml,
+            _ => Format::of(Path::new(&self.file_name())).unwrap_or(Format::Xml),
+        }
+    }
+
+    /// The file name without its extension or any folders.
+    fn stem(&self) -> String {
+        let name = self.file_name();
+        let name = name.rsplit('/').next().unwrap_or_default();
+        name.rsplit_once('.').map_or(name, |(stem, _)| stem).to_string()
+    }
+}
+
+impl PuzzleListing {
+    /// Reads the title, author, pack and chapter.  A file that isn't a puzzle is still listed, under
+    /// its file name, and fails when it is read.
+    pub fn new(path: PathBuf, folder: Vec<String>) -> Self {
+        let source = Source::at(path);
+        // Already owned, so this doesn't copy.  A damaged bundle is listed, and fails when read
+        let contents = match source.read() {
+            Ok(contents) => contents.into_owned(),
+            Err(_) if matches!(source, Source::Saved(_)) => Vec::new(),
+            Err(e) => panic!("Unable to read file: {}: {}", source.file_name(), e),
+        };
+        PuzzleListing::from_contents(source, folder, &contents)
+    }
+
+    /// Like [`PuzzleListing::new`], but takes what the file says from `cache` if it hasn't changed
+    /// since it was last listed, and saves it there if it has.
+    fn listed(path: PathBuf, folder: Vec<String>, cache: &mut Option<ListingCache>) -> Self {
+        let stamp = FileStamp::of(&path);
+        if let Some(listed) = cache.as_ref().zip(stamp).and_then(|(cache, stamp)| cache.get(&path, stamp)) {
+            let ListedFile { name, author, pack, chapter, .. } = listed.clone();
+            return PuzzleListing { name, author, pack, chapter, folder, order: 0, source: Source::at(path) };
+        }
+
+        let listing = PuzzleListing::new(path.clone(), folder);
+        if let (Some(cache), Some(stamp)) = (cache.as_mut(), stamp) {
+            cache.put(&path, ListedFile { stamp, name: listing.name.clone(), author: listing.author.clone(), pack: listing.pack.clone(), chapter: listing.chapter.clone() });
+        }
+        listing
+    }
+
+    /// Reads a puzzle in XML or JSON from standard input, telling which by how it starts.
+    pub fn from_stdin() -> Result<Self, String> {
+        let mut contents = Vec::new();
+        io::stdin().read_to_end(&mut contents).map_err(|e| format!("Unable to read stdin: {}", e))?;
+        let format = match decode(&contents).trim_start().chars().next() {
+            Some('<') => Format::Xml,
+            Some('{') => Format::Json,
+            _ => return Err(String::from("Only XML and JSON puzzles can be read from stdin")),
+        };
+        let source = Source::Stdin { contents: contents.clone(), format };
+        Ok(PuzzleListing::from_contents(source, Vec::new(), &contents))
+    }
+
+    fn from_contents(source: Source, folder: Vec<String>, contents: &[u8]) -> Self {
+        let file_name = source.stem();
+        let contents = decode(contents);
+        let format = source.format();
+        if format != Format::Xml {
+            let (name, author) = match convert::parse(&contents, format) {
+                Ok((file, _)) => (file.title, file.author),
+                Err(_) => (file_name, None),
+            };
+            return PuzzleListing { name, author, pack: None, chapter: None, folder, order: 0, source };
+        }
+        let doc = match Document::parse(&contents) {
+            Ok(doc) => doc,
+            Err(_) => return PuzzleListing { name: file_name, author: None, pack: None, chapter: None, folder, order: 0, source },
+        };
+        let tag = |name| doc.root().children().flat_map(|f| f.children()).find(|a| a.has_tag_name(name)).and_then(|a| a.text()).map(String::from);
+
+        PuzzleListing {
+            name: tag("TITLE").unwrap_or(file_name),
+            author: tag("AUTHOR"),
+            pack: tag("PACK"),
+            chapter: tag("CHAPTER"),
+            folder,
+            order: 0,
+            source,
+        }
+    }
+
+    /// The nested sections the puzzle is listed under: its folders, then its pack and chapter.
+    pub fn sections(&self) -> Vec<&str> {
+        self.folder.iter().map(String::as_str).chain(self.pack.as_deref()).chain(self.chapter.as_deref()).collect()
+    }
+
+    /// The puzzle's file name, including the archive it's in if there is one.
+    pub fn file_name(&self) -> String {
+        self.source.file_name()
+    }
+
+    /// The bundle the puzzle is in, if it's in one that can still be read.
+    pub fn bundle(&self) -> Option<Bundle> {
+        match &self.source {
+            Source::Saved(path) => Bundle::read(path).ok(),
+            _ => None,
+        }
+    }
+
+    /// The raw bytes of the puzzle file.
+    pub fn contents(&self) -> io::Result<Cow<'_, [u8]>> {
+        self.source.read()
+    }
+
+    /// Reads the puzzle, failing with its problems if there are any and the mode is strict.  A
+    /// file that can't be read as a puzzle at all fails in either mode.
+    pub fn read_checked(&self, mode: ParseMode) -> Result<(Puzzle, PuzzleGui), String> {
+        let (puzzle, gui) = self.parse()?;
+        if mode == ParseMode::Strict && !gui.diagnostics.is_empty() {
+            return Err(gui.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>().join("\n"));
+        }
+
+        Ok((puzzle, gui))
+    }
+
+    /// Reads the puzzle as well as it can.  Problems with the file are listed in the GUI's
+    /// diagnostics.  Panics if the file can't be read as a puzzle at all.
     pub fn read(&self) -> (Puzzle, PuzzleGui) {
-        let contents = fs::read_to_string(self.path.clone())
-            .expect(format!("Unable to read file: {}", self.path.to_string_lossy()).as_str());
+        self.parse().unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let doc = Document::parse(&contents).expect("Unable to parse XML!");
+    /// Reads the puzzle as well as it can, with problems in single nodes listed in the GUI's
+    /// diagnostics.  Fails if the file can't be read, isn't a puzzle, or has no squares.
+    pub fn parse(&self) -> Result<(Puzzle, PuzzleGui), String> {
+        let contents = self.contents()
+            .map_err(|e| format!("Unable to read file {}: {}", self.file_name(), e))?;
+        let contents = decode(&contents);
+        let format = self.source.format();
+        if format != Format::Xml {
+            let (mut file, lost) = convert::parse(&contents, format).map_err(|e| format!("Unable to read {}: {}", self.file_name(), e))?;
+            // What the format has that a puzzle can't is a problem too
+            file.gui.diagnostics.extend(lost.into_iter().map(|problem| Diagnostic { node: self.file_name(), problem, offset: 0 }));
+            return Ok((file.puzzle, file.gui));
+        }
 
-        let nodes = doc.root().children().flat_map(|f| f.children()).find(|a| a.has_tag_name("GRAPH")).expect("No graph in document!").children();
+        let doc = Document::parse(&contents).map_err(|e| format!("Unable to parse XML in {}: {}", self.file_name(), e))?;
+
+        let nodes = doc.root().children().flat_map(|f| f.children()).find(|a| a.has_tag_name("GRAPH"))
+            .ok_or_else(|| format!("No graph in {}", self.file_name()))?
+            .children().filter(|node| node.is_element()).collect::<Vec<_>>();
+        if nodes.is_empty() {
+            return Err(format!("The graph in {} has no squares", self.file_name()));
+        }
+        if nodes.len() > Bits::zeroed().len() {
+            return Err(format!("The puzzle has {} squares, but at most {} are supported", nodes.len(), Bits::zeroed().len()));
+        }
 
         let mut id_map = HashMap::new();
         let mut revealed = Bits::zeroed();
+        let mut flagged = Bits::zeroed();
         let mut mines = Bits::zeroed();
         let mut unknowns = Bits::zeroed();
         let mut hints = Vec::new();
         let mut neighbors = Vec::new();
         let mut square_dimensions= Vec::new();
+        let mut id_labels = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut diagnose = |node: &str, problem: String, offset: usize| diagnostics.push(Diagnostic { node: node.to_string(), problem, offset });
 
-        for node in nodes.clone() {
-            let id = node.children().find(|a| a.has_tag_name("ID")).and_then(|f|f.text()).expect("No ID in graph!");
-            id_map.insert(id, id_map.len());
+        for (index, node) in nodes.iter().enumerate() {
+            let id = match node.children().find(|a| a.has_tag_name("ID")).and_then(|f|f.text()) {
+                Some(id) => id.to_string(),
+                None => {
+                    // Nothing can refer to it, but it's still a square
+                    let id = format!("#{}", index);
+                    diagnose(&id, String::from("No ID"), node.range().start);
+                    id
+                }
+            };
+            if id_map.insert(id.clone(), index).is_some() {
+                diagnose(&id, String::from("Another node has the same ID"), node.range().start);
+            }
+            id_labels.push(id);
             neighbors.push(Bits::zeroed());
             square_dimensions.push(SquareDimensions {
                 x: 0.0,
@@ -58,82 +387,143 @@ impl PuzzleListing {
             })
         }
 
-        for node in nodes {
-            let id = node.children().find(|a| a.has_tag_name("ID")).and_then(|f|f.text()).expect("No ID in graph!");
-            let index = id_map[&id];
+        // Squares whose position or shape couldn't be read, which get made up ones once the rest are known
+        let mut unplaced = Vec::new();
+        let mut unshaped = Vec::new();
+        for (index, node) in nodes.iter().enumerate() {
+            let id = id_labels[index].clone();
+            let offset = node.range().start;
             let edges = node.children().find(|a| a.has_tag_name("EDGES")).and_then(|f|f.text()).unwrap_or("");
             let has_mine = node.children().any(|a| a.has_tag_name("HAS_MINE"));
-            let secret = node.children().any(|a| a.has_tag_name("SECRET"));
-            let is_revealed = node.children().any(|a| a.has_tag_name("REVEALED"));
-            assert!(!has_mine || !secret, "Both HAS_MINE and SECRET were set!");
-            let pos = node.children().find(|a| a.has_tag_name("POS")).and_then(|f|f.text()).expect("No POS in node!").split(',').map(|a| a.parse::<f32>().expect("Unable to parse float!")).collect::<Vec<_>>();
-            let (x, y) = (pos[0], pos[1]);
-
-            let points = node.children().find(|a| a.has_tag_name("POLY")).expect("No POLY in node!").children().find(|a| a.has_tag_name("POINTS")).and_then(|f|f.text()).expect("No POINTS in node!").split(",").map(|a| a.parse::<f32>().expect("Unable to parse float!")).collect::<Vec<_>>();
-            let mut points_iter = points.into_iter();
-            while let Some(first) = points_iter.next() {
-                let second = points_iter.next().expect("Elements in POINTS are not paired!");
-
-                square_dimensions[index].points.push((first, second));
+            let mut secret = node.children().any(|a| a.has_tag_name("SECRET"));
+            let mut is_revealed = node.children().any(|a| a.has_tag_name("REVEALED"));
+            let mut is_flagged = node.children().any(|a| a.has_tag_name("FLAGGED"));
+            if is_flagged && !has_mine {
+                diagnose(&id, String::from("FLAGGED was set without HAS_MINE"), offset);
+                is_flagged = false;
+            }
+            if is_revealed && is_flagged {
+                diagnose(&id, String::from("Both REVEALED and FLAGGED were set"), offset);
+                is_revealed = false;
+            }
+            if is_revealed && has_mine {
+                diagnose(&id, String::from("Both REVEALED and HAS_MINE were set"), offset);
+                is_revealed = false;
+            }
+            if has_mine && secret {
+                diagnose(&id, String::from("Both HAS_MINE and SECRET were set"), offset);
+                secret = false;
             }
-            square_dimensions[index].x = x;
-            square_dimensions[index].y = y;
 
+            match parse_pos(node) {
+                Ok((x, y)) => {
+                    square_dimensions[index].x = x;
+                    square_dimensions[index].y = y;
+                }
+                Err(problem) => {
+                    diagnose(&id, problem, offset);
+                    unplaced.push(index);
+                }
+            }
+            match parse_points(node) {
+                Ok(points) => square_dimensions[index].points = points,
+                Err(problem) => {
+                    diagnose(&id, problem, offset);
+                    unshaped.push(index);
+                }
+            }
 
-            
             let mut neighbor_map = Bits::zeroed();
 
             if edges != "" {
                 for edge in edges.split(',') {
-                    let neighbor_id = id_map[edge];
-                    neighbor_map.set(neighbor_id, true);
+                    match id_map.get(edge) {
+                        Some(&neighbor_id) => neighbor_map.set(neighbor_id, true),
+                        None => diagnose(&id, format!("EDGES refers to an unknown square: {}", edge), offset),
+                    }
                 }
             }
 
             revealed.set(index, is_revealed);
+            flagged.set(index, is_flagged);
             mines.set(index, has_mine);
             unknowns.set(index, secret);
 
             neighbors[index] = neighbor_map;
         }
         
-        for hint in doc.root().children().flat_map(|f| f.children()).filter(|a| a.has_tag_name("HINT_LIST") || a.has_tag_name("COLUMN_HINT_LIST")).flat_map(|a| a.children()) {
-            let ids = hint.children().find(|a| a.has_tag_name("IDS")).and_then(|f|f.text()).expect("No ids in hint!");
+        // Hints in the plain list are the game's color hints
+        let mut color_hints = Vec::new();
+        for (hint, color) in doc.root().children().flat_map(|f| f.children()).filter(|a| a.has_tag_name("HINT_LIST") || a.has_tag_name("COLUMN_HINT_LIST")).flat_map(|a| a.children().map(move |hint| (hint, a.has_tag_name("HINT_LIST")))) {
+            let ids = match hint.children().find(|a| a.has_tag_name("IDS")).and_then(|f|f.text()) {
+                Some(ids) => ids,
+                None => {
+                    diagnose(&format!("hint #{}", hints.len()), String::from("A hint has no IDS, so it was left out"), hint.range().start);
+                    continue;
+                }
+            };
             let mut bits = Bits::zeroed();
             for id in ids.split(",") {
-                let square_id = id_map[id];
-                bits.set(square_id, true);
+                match id_map.get(id) {
+                    Some(&square_id) => bits.set(square_id, true),
+                    None => diagnose(id, String::from("A hint refers to this square, which isn't in the graph"), hint.range().start),
+                }
             }
 
+            if color {
+                color_hints.push(hints.len());
+            }
             hints.push(bits);
         }
 
-        let min_x = square_dimensions.iter().map(|a| a.x).reduce(f32::min).unwrap();
-        let max_x = square_dimensions.iter().map(|a| a.x).reduce(f32::max).unwrap();
-        let min_y = square_dimensions.iter().map(|a| a.y).reduce(f32::min).unwrap();
-        let max_y = square_dimensions.iter().map(|a| a.y).reduce(f32::max).unwrap();
+        // Broken squares borrow the shape of a good one, and go in a row under the board
+        let shape = square_dimensions.iter().enumerate().find(|(i, _)| !unshaped.contains(i)).map_or_else(|| vec![(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)], |(_, object)| object.points.clone());
+        for &index in &unshaped {
+            square_dimensions[index].points = shape.clone();
+        }
+        let width = shape.iter().map(|p| p.0).fold(f32::MIN, f32::max) - shape.iter().map(|p| p.0).fold(f32::MAX, f32::min);
+        let height = shape.iter().map(|p| p.1).fold(f32::MIN, f32::max) - shape.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let placed = square_dimensions.iter().enumerate().filter(|(i, _)| !unplaced.contains(i)).map(|(_, object)| object);
+        let (left, bottom) = placed.fold((f32::MAX, f32::MIN), |(left, bottom), object| (left.min(object.x), bottom.max(object.y)));
+        let (left, bottom) = if left == f32::MAX { (0.0, 0.0) } else { (left, bottom + height * 2.0) };
+        for (column, &index) in unplaced.iter().enumerate() {
+            square_dimensions[index].x = left + column as f32 * width * 1.5;
+            square_dimensions[index].y = bottom;
+        }
 
-        (
+        let min_x = square_dimensions.iter().map(|a| a.x).fold(f32::MAX, f32::min);
+        let max_x = square_dimensions.iter().map(|a| a.x).fold(f32::MIN, f32::max);
+        let min_y = square_dimensions.iter().map(|a| a.y).fold(f32::MAX, f32::min);
+        let max_y = square_dimensions.iter().map(|a| a.y).fold(f32::MIN, f32::max);
+
+        let gui = PuzzleGui {
+            min_y,
+            min_x,
+            max_y,
+            max_x, 
+            squares: square_dimensions,
+            diagnostics,
+            color_hints,
+        };
+        Ok((
             Puzzle {
                 neighbors,
                 revealed,
+                flagged,
                 hints,
                 mines,
-                unknowns
+                unknowns,
+                id_labels,
+                layout: Layout::new(&gui),
             },
-            PuzzleGui {
-                min_y,
-                min_x,
-                max_y,
-                max_x, 
-                squares: square_dimensions,
-            }
-        )
+            gui
+        ))
     }
 }
 
 pub struct Parser {
-    puzzle_dir: PathBuf,
+    /// Empty when the game isn't installed, in which case only the samples are listed.
+    puzzle_dirs: Vec<PathBuf>,
 }
 
 impl Parser {
@@ -143,31 +533,266 @@ impl Parser {
                 let mut path = app.path.clone();
                 path.push("puzzles");
                 Parser {
-                    puzzle_dir: path
+                    puzzle_dirs: vec![path]
                 }
             } else {
-                panic!("Couldn't locate Tametsi on the computer!")
+                eprintln!("Couldn't locate Tametsi on the computer, showing the sample puzzles instead");
+                Parser { puzzle_dirs: Vec::new() }
             }
         } else {
-            panic!("Couldn't locate Steam on this computer!")
+            eprintln!("Couldn't locate Steam on this computer, showing the sample puzzles instead");
+            Parser { puzzle_dirs: Vec::new() }
         }
     }
 
     pub fn from_folder<T>(path: T) -> Parser 
         where T: Into<PathBuf> + Sized
     {
-        Parser { puzzle_dir: path.into() }
+        Parser { puzzle_dirs: vec![path.into()] }
+    }
+
+    /// The puzzles in `folders`, or in the Steam install if there are none.
+    pub fn from_folders(folders: Vec<PathBuf>) -> Parser {
+        if folders.is_empty() {
+            Parser::new()
+        } else {
+            Parser { puzzle_dirs: folders }
+        }
     }
     
+    /// Every puzzle in the directories and their subfolders, ordered by section and then as the
+    /// game orders them.
     pub fn read_all_puzzles(&self) -> Vec<PuzzleListing> {
         let mut puzzles = Vec::new();
-        for entry in fs::read_dir(self.puzzle_dir.clone()).expect("Unable to read puzzle directory!") {
-            let entry = entry.expect("Unable to read puzzle directory!");
-            let path = entry.path();
-            if path.is_file() {
-                puzzles.push(PuzzleListing::new(path));
+        if self.puzzle_dirs.is_empty() {
+            for (index, (_, contents)) in SAMPLES.iter().enumerate() {
+                let mut listing = PuzzleListing::from_contents(Source::Bundled(index), Vec::new(), contents.as_bytes());
+                listing.order = index;
+                puzzles.push(listing);
+            }
+        } else {
+            let mut cache = ListingCache::open();
+            for dir in &self.puzzle_dirs {
+                read_folder(dir, Vec::new(), &mut puzzles, &mut cache);
+            }
+            if let Some(cache) = cache.as_mut() {
+                cache.save();
             }
         }
+        puzzles.sort_by(|a, b| a.sections().cmp(&b.sections()).then(a.order.cmp(&b.order)).then_with(|| a.name.cmp(&b.name)));
         puzzles
-    }    
+    }
+}
+
+/// The centre of a node.
+fn parse_pos(node: &Node) -> Result<(f32, f32), String> {
+    let pos = node.children().find(|a| a.has_tag_name("POS")).and_then(|f|f.text()).ok_or("No POS in node")?;
+    match pos.split(',').map(|a| a.trim().parse::<f32>()).collect::<Result<Vec<_>, _>>().as_deref() {
+        Ok(&[x, y]) => Ok((x, y)),
+        _ => Err(format!("Unable to read POS: {}", pos)),
+    }
+}
+
+/// The corners of a node's polygon, relative to its centre.
+fn parse_points(node: &Node) -> Result<Vec<(f32, f32)>, String> {
+    let poly = node.children().find(|a| a.has_tag_name("POLY")).ok_or("No POLY in node")?;
+    let points = poly.children().find(|a| a.has_tag_name("POINTS")).and_then(|f|f.text()).ok_or("No POINTS in POLY")?;
+    let values = points.split(',').map(|a| a.trim().parse::<f32>()).collect::<Result<Vec<_>, _>>().map_err(|_| format!("Unable to read POINTS: {}", points))?;
+    if values.len() % 2 != 0 || values.len() < 6 {
+        return Err(format!("POINTS needs at least three pairs of numbers: {}", points));
+    }
+
+    Ok(values.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+/// Text from a puzzle file in whatever encoding it was saved in.  Handles byte order marks, UTF-16
+/// without one, and otherwise replaces anything that isn't UTF-8.  Valid UTF-8 is borrowed rather
+/// than copied, so XML is parsed straight from the bytes read.
+fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]])).collect::<Vec<_>>();
+        Cow::Owned(String::from_utf16_lossy(&units))
+    };
+
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        // XML starts with '<', which UTF-16 pairs with a zero byte
+        [b'<', 0, ..] => utf16(bytes, u16::from_le_bytes),
+        [0, b'<', ..] => utf16(bytes, u16::from_be_bytes),
+        _ => String::from_utf8_lossy(bytes),
+    }
+}
+
+fn read_folder(dir: &Path, folder: Vec<String>, puzzles: &mut Vec<PuzzleListing>, cache: &mut Option<ListingCache>) {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).expect("Unable to read puzzle directory!") {
+        let entry = entry.expect("Unable to read puzzle directory!");
+        let path = entry.path();
+        let is_zip = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+        if path.is_file() && is_zip {
+            let mut archive_folder = folder.clone();
+            archive_folder.push(path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default());
+            read_zip(&path, archive_folder, puzzles);
+        } else if path.is_file() {
+            files.push(entry.file_name().to_string_lossy().into_owned());
+        } else if path.is_dir() {
+            let mut subfolder = folder.clone();
+            subfolder.push(entry.file_name().to_string_lossy().into_owned());
+            read_folder(&path, subfolder, puzzles, cache);
+        }
+    }
+
+    let index = INDEX_FILES.iter().find_map(|name| fs::read_to_string(dir.join(name)).ok());
+    for (order, name) in ordered(files, index.as_deref()).into_iter().enumerate() {
+        let mut listing = PuzzleListing::listed(dir.join(name), folder.clone(), cache);
+        listing.order = order;
+        puzzles.push(listing);
+    }
+}
+
+/// Lists the puzzles in a zip archive as though it were a folder.
+fn read_zip(path: &Path, folder: Vec<String>, puzzles: &mut Vec<PuzzleListing>) {
+    let file = File::open(path).unwrap_or_else(|e| panic!("Unable to open {}: {}", path.to_string_lossy(), e));
+    let mut archive = ZipArchive::new(file).unwrap_or_else(|e| panic!("Unable to read {}: {}", path.to_string_lossy(), e));
+    // The files in each folder of the archive
+    let mut folders: HashMap<String, Vec<(String, Vec<u8>)>> = HashMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).expect("Unable to read zip archive!");
+        // Skip folders, and the resource forks macOS adds to archives
+        if file.is_dir() || file.name().starts_with("__MACOSX/") {
+            continue;
+        }
+
+        let entry = file.name().to_string();
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap_or_else(|e| panic!("Unable to read {} in {}: {}", entry, path.to_string_lossy(), e));
+        let (parent, name) = entry.rsplit_once('/').unwrap_or(("", &entry));
+        folders.entry(parent.to_string()).or_default().push((name.to_string(), contents));
+    }
+
+    for (parent, mut files) in folders {
+        let index = files.iter().find(|(name, _)| INDEX_FILES.contains(&name.as_str())).map(|(_, contents)| decode(contents));
+        let names = ordered(files.iter().map(|(name, _)| name.clone()).collect(), index.as_deref());
+
+        // Folders inside the archive are sections too
+        let mut entry_folder = folder.clone();
+        entry_folder.extend(parent.split('/').filter(|part| !part.is_empty()).map(String::from));
+
+        for (order, name) in names.into_iter().enumerate() {
+            let position = files.iter().position(|(file, _)| *file == name).unwrap();
+            let (name, contents) = files.swap_remove(position);
+            let entry = if parent.is_empty() { name } else { format!("{}/{}", parent, name) };
+            let source = Source::Zipped { archive: path.to_path_buf(), entry };
+            let mut listing = PuzzleListing::from_contents(source, entry_folder.clone(), &contents);
+            listing.order = order;
+            puzzles.push(listing);
+        }
+    }
+}
+
+/// The puzzle files in a folder, in the order its index lists them and then the rest in natural
+/// order, so `puzzle2` comes before `puzzle10`.  Leaves out the index itself, and web pages such as
+/// walkthroughs written next to the puzzles.
+fn ordered(mut names: Vec<String>, index: Option<&str>) -> Vec<String> {
+    names.retain(|name| !INDEX_FILES.contains(&name.as_str()) && !name.to_ascii_lowercase().ends_with(".html"));
+    names.sort_by(|a, b| natural_cmp(a, b));
+    let listed = index.unwrap_or_default().lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let mut ordered = Vec::new();
+    for name in listed {
+        if let Some(position) = names.iter().position(|file| file == name) {
+            ordered.push(names.remove(position));
+        }
+    }
+    ordered.extend(names);
+    ordered
+}
+
+/// Compares runs of digits by their value and everything else as text.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (a_digits, b_digits) = (a.starts_with(|c: char| c.is_ascii_digit()), b.starts_with(|c: char| c.is_ascii_digit()));
+        let split = |s: &str, digits: bool| s.find(|c: char| c.is_ascii_digit() != digits).unwrap_or(s.len());
+        let (a_run, a_rest) = a.split_at(split(a, a_digits));
+        let (b_run, b_rest) = b.split_at(split(b, b_digits));
+        let ordering = match (a_run.is_empty() || b_run.is_empty(), a_digits && b_digits) {
+            (true, _) => return a_run.cmp(b_run),
+            (false, true) => a_run.trim_start_matches('0').len().cmp(&b_run.trim_start_matches('0').len()).then_with(|| a_run.trim_start_matches('0').cmp(b_run.trim_start_matches('0'))),
+            (false, false) => a_run.cmp(b_run),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a = a_rest;
+        b = b_rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{solver::{SolveOutcome, SolverConfig}, trace::{Resolved, Trace}};
+
+    use super::{BUNDLE_VERSION, Bundle, SAMPLES};
+
+    fn bundle() -> Bundle {
+        let mut metadata = BTreeMap::new();
+        metadata.insert(String::from("saved by"), String::from("tametsi test"));
+        let resolved = Resolved { square: String::from("0"), flagged: false, technique: Some(String::from("Naked single")) };
+        Bundle {
+            title: String::from("Sample: First Steps"),
+            author: Some(String::from("tametsi-solver")),
+            puzzle: SAMPLES[0].1.to_string(),
+            config: Some(SolverConfig::default()),
+            replay: Some(Trace { title: String::from("Sample: First Steps"), settings: String::new(), outcome: SolveOutcome::Stopped(String::from("saved partway through")), steps: vec![vec![resolved]] }),
+            metadata,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bundle = bundle();
+        let read = Bundle::from_bytes(&bundle.to_bytes()).unwrap();
+        assert_eq!(read.title, bundle.title);
+        assert_eq!(read.author, bundle.author);
+        assert_eq!(read.puzzle, bundle.puzzle);
+        assert_eq!(read.config, bundle.config);
+        assert_eq!(read.replay, bundle.replay);
+        assert_eq!(read.metadata, bundle.metadata);
+    }
+
+    #[test]
+    fn cut_short() {
+        let bytes = bundle().to_bytes();
+        let error = Bundle::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(error.contains("cut short"), "{}", error);
+        assert!(Bundle::from_bytes(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn flipped_byte() {
+        let mut bytes = bundle().to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let error = Bundle::from_bytes(&bytes).unwrap_err();
+        assert!(error.contains("checksum"), "{}", error);
+    }
+
+    #[test]
+    fn future_version() {
+        let mut bytes = bundle().to_bytes();
+        bytes[4..8].copy_from_slice(&(BUNDLE_VERSION + 1).to_le_bytes());
+        let error = Bundle::from_bytes(&bytes).unwrap_err();
+        assert!(error.contains("newer"), "{}", error);
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut bytes = bundle().to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(Bundle::from_bytes(&bytes).unwrap_err(), "Not a bundle");
+    }
 }
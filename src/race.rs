@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use crate::solver::{Action, Solver, StepResult};
+
+/// A square the player resolved while racing the solver.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayerMove {
+    pub square: usize,
+    pub action: Action,
+    /// Since the race started.
+    pub at: Duration,
+    /// Whether what the board showed proved it when it was made, rather than it being a guess.
+    pub justified: bool,
+    /// Whether the square really was safe, or a mine.  Wrong moves are refused.
+    pub correct: bool,
+}
+
+/// How the solver got on from where the race started, on a copy nobody sees.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SolverRun {
+    /// Squares in the order the solver resolved them, with the step that did it, counting from 1.
+    pub moves: Vec<(usize, Action, usize)>,
+    pub steps: usize,
+    pub deductions: usize,
+    pub elapsed: Duration,
+    /// Whether it resolved every square, rather than getting stuck or stopping.
+    pub finished: bool,
+}
+
+impl SolverRun {
+    /// Steps `solver`, a copy of the one being played, until it stops.
+    pub fn solve(mut solver: Solver) -> SolverRun {
+        let start = Instant::now();
+        let mut run = SolverRun { moves: Vec::new(), steps: 0, deductions: 0, elapsed: Duration::ZERO, finished: false };
+        loop {
+            let result = solver.step();
+            run.steps += 1;
+            match result {
+                StepResult::Progress{revealed, flagged, deductions} => {
+                    let step = run.steps;
+                    run.deductions += deductions.len();
+                    run.moves.extend(revealed.iter_ones().map(|square| (square, Action::Reveal, step)));
+                    run.moves.extend(flagged.iter_ones().map(|square| (square, Action::Flag, step)));
+                }
+                StepResult::CrossConstraint(_) | StepResult::CliqueConstraint(_) => {}
+                result => {
+                    run.finished = matches!(result, StepResult::Finished);
+                    break;
+                }
+            }
+        }
+        run.elapsed = start.elapsed();
+        run
+    }
+
+    /// The step that resolved `square`, if the solver got to it.
+    pub fn step_of(&self, square: usize) -> Option<usize> {
+        self.moves.iter().find(|&&(resolved, _, _)| resolved == square).map(|&(_, _, step)| step)
+    }
+
+    pub fn per_deduction(&self) -> Duration {
+        self.elapsed / self.deductions.max(1) as u32
+    }
+}
+
+/// A player solving a puzzle by hand against the solver.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Race {
+    pub player: Vec<PlayerMove>,
+    /// Once the solver has stopped.
+    pub solver: Option<SolverRun>,
+}
+
+impl Race {
+    /// The moves that were let through, in order.
+    pub fn accepted(&self) -> impl Iterator<Item = &PlayerMove> {
+        self.player.iter().filter(|played| played.correct)
+    }
+
+    /// How long the player took for each accepted move, on average.
+    pub fn per_move(&self) -> Option<Duration> {
+        let count = self.accepted().count();
+        self.accepted().last().map(|last| last.at / count as u32)
+    }
+
+    /// Moves that were right, but that the board didn't prove yet.
+    pub fn unjustified(&self) -> impl Iterator<Item = &PlayerMove> {
+        self.accepted().filter(|played| !played.justified)
+    }
+
+    /// Of the accepted moves after the first, how many the solver also made no earlier than the
+    /// move before, out of how many it made at all.
+    pub fn in_order(&self, run: &SolverRun) -> (usize, usize) {
+        let steps = self.accepted().filter_map(|played| run.step_of(played.square)).collect::<Vec<_>>();
+        let agreeing = steps.windows(2).filter(|pair| pair[0] <= pair[1]).count();
+        (agreeing, steps.len().saturating_sub(1))
+    }
+}
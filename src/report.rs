@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{core::{Bits, Puzzle}, difficulty::DifficultyModel, generator::Rng, parser::PuzzleListing, solver::{GuessAccounting, GuessCandidate, InferenceRule, InferenceTier, SolveOutcome, Solver, SolverConfig, SolverStats, StepResult, Uniqueness, account_guesses}, technique::Technique};
+
+/// How many of the best guesses a report keeps.
+const BEST_GUESSES: usize = 5;
+
+/// Everything learned from solving a puzzle once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SolveReport {
+    pub title: String,
+    pub outcome: SolveOutcome,
+    pub stats: SolverStats,
+    pub difficulty: f64,
+    /// Empty unless guess accounting was asked for.
+    pub guesses: Vec<GuessAccounting>,
+    /// The best squares to guess where the solver got stuck, best first.  Empty unless it got stuck.
+    pub best_guesses: Vec<GuessCandidate>,
+}
+
+impl SolveReport {
+    /// Solves `puzzle` with `rules` as well as crossing.
+    pub fn generate(title: &str, puzzle: &Puzzle, config: SolverConfig, rules: &[Arc<dyn InferenceRule>], guesses: bool) -> SolveReport {
+        let mut solver = Solver::new(puzzle.clone(), config);
+        for rule in rules {
+            solver.add_rule(rule.clone());
+        }
+        let result = solver.solve();
+        let mut report = SolveReport::from_solver(title, &solver, &result);
+        if guesses {
+            report.guesses = account_guesses(puzzle, config);
+        }
+        report
+    }
+
+    /// The report for a solver that has stopped with `result`.
+    pub fn from_solver(title: &str, solver: &Solver, result: &StepResult) -> SolveReport {
+        SolveReport {
+            title: title.to_string(),
+            outcome: SolveOutcome::from_step(result).expect("The solve hasn't ended!"),
+            stats: solver.stats.clone(),
+            difficulty: DifficultyModel::default().score(&solver.stats),
+            guesses: Vec::new(),
+            best_guesses: match result {
+                StepResult::Stuck => solver.rank_guesses().into_iter().take(BEST_GUESSES).collect(),
+                _ => Vec::new(),
+            },
+        }
+    }
+}
+
+/// How far a player who only knows some techniques gets through a puzzle.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PlayerSimulation {
+    pub outcome: SolveOutcome,
+    /// Squares resolved when the player stopped, out of `total`.
+    pub resolved: usize,
+    pub total: usize,
+    /// Unresolved squares next to a number the player can see, where they would be looking when
+    /// they got stuck.
+    pub frontier: Bits,
+    /// The board where the player stopped, drawn as text with the frontier marked.
+    pub board: String,
+}
+
+impl PlayerSimulation {
+    /// Plays the puzzle using only numbers a player can see and only `techniques`.  Looking at
+    /// single numbers is always allowed.  Without subsets or crossing nothing is combined, without
+    /// hints the puzzle's hints are ignored and without mine counting so is the mine count.
+    pub fn run(puzzle: &Puzzle, config: SolverConfig, techniques: &[Technique]) -> PlayerSimulation {
+        let (puzzle, config) = as_player(puzzle, config, techniques);
+        let mut solver = Solver::new(puzzle, config);
+        let outcome = SolveOutcome::from_step(&solver.solve()).expect("The solve hasn't ended!");
+
+        let state = &solver.puzzle;
+        let open = !state.revealed & !state.flagged;
+        let frontier = (state.revealed & !state.base.unknowns).iter_ones().fold(Bits::zeroed(), |frontier, square| frontier | (state.base.neighbors[square] & open));
+        PlayerSimulation {
+            outcome,
+            resolved: (state.revealed | state.flagged).count_ones(),
+            total: state.base.size(),
+            frontier,
+            board: state.draw(frontier),
+        }
+    }
+}
+
+/// The puzzle and settings for solving as a player who only knows `techniques` would.
+fn as_player(puzzle: &Puzzle, config: SolverConfig, techniques: &[Technique]) -> (Puzzle, SolverConfig) {
+    let knows = |technique| techniques.contains(&technique);
+    let tier = if knows(Technique::Cross) {
+        InferenceTier::Cross
+    } else if knows(Technique::Subset) {
+        InferenceTier::Subset
+    } else {
+        InferenceTier::Single
+    };
+    let mut puzzle = puzzle.clone();
+    if !knows(Technique::Hint) {
+        puzzle.hints.clear();
+    }
+
+    (puzzle, SolverConfig { tier, known_mine_count: config.known_mine_count && knows(Technique::MineCount), player_knowledge_only: true, ..config })
+}
+
+/// How a simulated player picks a square when they have to guess.  Every policy breaks ties at
+/// random, so repeated games differ.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GuessPolicy {
+    /// One of the squares `Solver::rank_guesses` finds most likely to be safe, which leaves out
+    /// the mine count.
+    Safest,
+    /// Any square next to a number the player can see.
+    Frontier,
+    /// Any square that isn't revealed or flagged.
+    Random,
+}
+
+impl GuessPolicy {
+    pub const ALL: [GuessPolicy; 3] = [GuessPolicy::Safest, GuessPolicy::Frontier, GuessPolicy::Random];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GuessPolicy::Safest => "safest",
+            GuessPolicy::Frontier => "frontier",
+            GuessPolicy::Random => "random",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GuessPolicy> {
+        GuessPolicy::ALL.iter().copied().find(|policy| policy.name() == name)
+    }
+
+    /// The square to guess next, or `None` if every square is resolved.
+    fn pick(self, solver: &Solver, rng: &mut Rng) -> Option<usize> {
+        let state = &solver.puzzle;
+        let open = !state.revealed & !state.flagged;
+        let open = (0..state.base.size()).filter(|&square| open[square]).collect::<Vec<_>>();
+        let choices = match self {
+            GuessPolicy::Safest => {
+                let candidates = solver.rank_guesses();
+                // Ranked to the percent, like the candidates themselves
+                let percent = |candidate: &GuessCandidate| (candidate.safety * 100.0).round() as i64;
+                let best = candidates.first().map(percent);
+                candidates.iter().filter(|candidate| Some(percent(candidate)) == best).map(|candidate| candidate.square).collect()
+            }
+            GuessPolicy::Frontier => {
+                let numbers = (state.revealed & !state.base.unknowns).iter_ones().fold(Bits::zeroed(), |near, square| near | state.base.neighbors[square]);
+                open.iter().copied().filter(|&square| numbers[square]).collect()
+            }
+            GuessPolicy::Random => Vec::new(),
+        };
+        let choices = if choices.is_empty() { open } else { choices };
+        (!choices.is_empty()).then(|| choices[rng.below(choices.len())])
+    }
+}
+
+/// Steps a simulated game gets when the settings don't set a budget.
+const GUESS_GAME_BUDGET: usize = 2_000;
+
+/// Games of a puzzle played to the end by a player who guesses with one policy whenever they're
+/// stuck, against the puzzle's real layout.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GuessSimulation {
+    pub policy: GuessPolicy,
+    pub games: usize,
+    pub wins: usize,
+    /// Games where the solver stopped or found a contradiction before the player won or hit a mine.
+    pub unfinished: usize,
+    /// Guesses made over the games played to the end, counting the ones that hit a mine.
+    pub guesses: usize,
+}
+
+impl GuessSimulation {
+    /// Plays `games` games as a player who only knows `techniques`, like `PlayerSimulation`.  Game
+    /// `n` uses seed `n`, so the same settings always give the same result.  Bad guesses can leave
+    /// the solver a board it crosses for a long time, so without a step budget each game gets
+    /// `GUESS_GAME_BUDGET`.
+    pub fn run(puzzle: &Puzzle, config: SolverConfig, techniques: &[Technique], policy: GuessPolicy, games: usize) -> GuessSimulation {
+        let (puzzle, config) = as_player(puzzle, config, techniques);
+        let config = SolverConfig { budget: config.budget.or(Some(GUESS_GAME_BUDGET)), ..config };
+        let mut simulation = GuessSimulation { policy, games, wins: 0, unfinished: 0, guesses: 0 };
+        for game in 0..games {
+            let mut rng = Rng(game as u64);
+            let mut solver = Solver::new(puzzle.clone(), config);
+            let mut guesses = 0;
+            loop {
+                match solver.solve() {
+                    StepResult::Finished => {
+                        simulation.wins += 1;
+                        break;
+                    }
+                    StepResult::Stuck => {
+                        let square = match policy.pick(&solver, &mut rng) {
+                            Some(square) => square,
+                            None => break,
+                        };
+                        guesses += 1;
+                        if !solver.guess_square(square) {
+                            break;
+                        }
+                    }
+                    _ => {
+                        simulation.unfinished += 1;
+                        guesses = 0;
+                        break;
+                    }
+                }
+            }
+            simulation.guesses += guesses;
+        }
+        simulation
+    }
+
+    /// The fraction of games won, out of those played to the end.
+    pub fn win_rate(&self) -> f64 {
+        match self.games - self.unfinished {
+            0 => 0.0,
+            finished => self.wins as f64 / finished as f64,
+        }
+    }
+
+    /// The average guesses in a game, out of those played to the end.
+    pub fn guesses_per_game(&self) -> f64 {
+        match self.games - self.unfinished {
+            0 => 0.0,
+            finished => self.guesses as f64 / finished as f64,
+        }
+    }
+}
+
+/// A summary of how a puzzle fares with the default solver, shown in the puzzle list.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Badge {
+    Solved,
+    /// The solver gets stuck, but the puzzle has only one solution, so it takes reasoning the solver
+    /// doesn't do (clique logic, for one).
+    BeyondSolver,
+    /// The solver gets stuck and the clues allow more than one layout.
+    NeedsGuessing,
+    /// The solver gets stuck and the uniqueness search gave up.
+    Stuck,
+    /// The file couldn't be read as a puzzle.
+    Unreadable,
+}
+
+impl Badge {
+    pub fn name(self) -> &'static str {
+        match self {
+            Badge::Solved => "solved",
+            Badge::BeyondSolver => "beyond the solver",
+            Badge::NeedsGuessing => "needs guessing",
+            Badge::Stuck => "stuck",
+            Badge::Unreadable => "unreadable",
+        }
+    }
+
+    pub fn of(listing: &PuzzleListing) -> Badge {
+        // The parser panics on malformed files
+        let (puzzle, _) = match std::panic::catch_unwind(|| listing.read()) {
+            Ok(read) => read,
+            Err(_) => return Badge::Unreadable,
+        };
+
+        let mut solver = Solver::new(puzzle, SolverConfig::default());
+        match solver.solve() {
+            StepResult::Finished => Badge::Solved,
+            _ => match solver.verify_unique() {
+                Uniqueness::Unique => Badge::BeyondSolver,
+                Uniqueness::Ambiguous(_) => Badge::NeedsGuessing,
+                Uniqueness::Undetermined => Badge::Stuck,
+            },
+        }
+    }
+}
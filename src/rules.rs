@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use crate::{core::Bits, solver::{Action, Constraint, Inference, InferenceRule, PuzzleState}, technique::Technique};
+
+/// The rules that come with the solver, by the name the command line knows them by.
+pub const NAMES: [&str; 1] = ["disjoint-count"];
+
+pub fn from_name(name: &str) -> Option<Arc<dyn InferenceRule>> {
+    match name {
+        "disjoint-count" => Some(Arc::new(DisjointCount)),
+        _ => None,
+    }
+}
+
+/// Takes as many unsolved constraints as don't overlap, most mines first, and bounds the mines in
+/// every other open square by what the board's mine count leaves for them.  Crossing only gets
+/// there one pair at a time, and not at all when the mine count is too large to cross.  Only sound
+/// when the mine count is shown.
+pub struct DisjointCount;
+
+impl InferenceRule for DisjointCount {
+    fn technique(&self) -> Technique {
+        Technique::MineCount
+    }
+
+    fn infer(&self, constraints: &[Constraint], state: &PuzzleState) -> Vec<Inference> {
+        let mut constraints = constraints.to_vec();
+        constraints.sort_by_key(|constraint| std::cmp::Reverse(constraint.min_mines));
+
+        let (mut covered, mut min, mut max) = (Bits::zeroed(), 0, 0);
+        for constraint in constraints {
+            if (covered & constraint.bits).not_any() {
+                covered |= constraint.bits;
+                min += constraint.min_mines;
+                max += constraint.max_mines;
+            }
+        }
+
+        let mut rest = Bits::zeroed();
+        for square in 0..state.base.size() {
+            rest.set(square, !state.revealed[square] && !state.flagged[square] && !covered[square]);
+        }
+        let remaining = state.base.mines.count_ones() - state.flagged.count_ones();
+        if rest.not_any() || min > remaining {
+            return Vec::new();
+        }
+        let (rest_min, rest_max) = (remaining.saturating_sub(max), remaining - min);
+        if rest_max == 0 {
+            vec![Inference::Resolved(rest, Action::Reveal)]
+        } else if rest_min == rest.count_ones() {
+            vec![Inference::Resolved(rest, Action::Flag)]
+        } else {
+            vec![Inference::Constraint(Constraint { bits: rest, min_mines: rest_min, max_mines: rest_max.min(rest.count_ones()), size: rest.count_ones(), technique: Technique::MineCount, depth: 0 })]
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+
+use crate::{ascii::Layout, config::Config, convert::{self, Format, PuzzleFile}, parser::Parser, report::SolveReport, solver::{Preset, SolveOutcome, SolverConfig}};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Runs the Rhai script at `path`, which sees `args` as `ARGS`.
+///
+/// Besides Rhai's own functions, scripts get:
+/// - `load(path)` and `library(folder)`, or `library()` for the config file's folders or the Steam
+///   install, which give puzzles
+/// - a puzzle's `title`, `author`, `size`, `mines`, `revealed` and `hints`
+/// - `reveal(id)`, `unreveal(id)`, `hide_number(id)` and `show_number(id)` to change which squares
+///   start revealed and which hide their number, by the square's ID
+/// - `solve(puzzle)` or `solve(puzzle, preset)`, which give a map of the outcome, steps,
+///   crossings, difficulty and the deductions made with each technique
+/// - `compose(puzzle, other, x, y)`, a new puzzle with both boards side by side, `other`'s moved
+///   by `x` and `y`
+/// - `save(puzzle, path)`, in the format the extension says, and `write(path, text)`
+pub fn run(path: &Path, args: Vec<String>) -> Result<(), String> {
+    let mut scope = Scope::new();
+    scope.push_constant("ARGS", args.into_iter().map(Dynamic::from).collect::<Array>());
+    engine().run_file_with_scope(&mut scope, path.to_path_buf()).map_err(|e| e.to_string())
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.register_type_with_name::<PuzzleFile>("Puzzle")
+        .register_get("title", |file: &mut PuzzleFile| file.title.clone())
+        .register_get("author", |file: &mut PuzzleFile| file.author.clone().map_or(Dynamic::UNIT, Dynamic::from))
+        .register_get("size", |file: &mut PuzzleFile| file.puzzle.size() as i64)
+        .register_get("mines", |file: &mut PuzzleFile| file.puzzle.mines.count_ones() as i64)
+        .register_get("revealed", |file: &mut PuzzleFile| file.puzzle.revealed.count_ones() as i64)
+        .register_get("hints", |file: &mut PuzzleFile| file.puzzle.hints.len() as i64)
+        .register_fn("reveal", |file: &mut PuzzleFile, id: &str| -> ScriptResult<()> {
+            let square = square(file, id)?;
+            if file.puzzle.mines[square] {
+                return Err(format!("{} is a mine", id).into());
+            }
+            file.puzzle.revealed.set(square, true);
+            Ok(())
+        })
+        .register_fn("unreveal", |file: &mut PuzzleFile, id: &str| -> ScriptResult<()> {
+            let square = square(file, id)?;
+            file.puzzle.revealed.set(square, false);
+            Ok(())
+        })
+        .register_fn("hide_number", |file: &mut PuzzleFile, id: &str| -> ScriptResult<()> {
+            let square = square(file, id)?;
+            file.puzzle.unknowns.set(square, true);
+            Ok(())
+        })
+        .register_fn("show_number", |file: &mut PuzzleFile, id: &str| -> ScriptResult<()> {
+            let square = square(file, id)?;
+            file.puzzle.unknowns.set(square, false);
+            Ok(())
+        })
+        .register_fn("load", load)
+        .register_fn("library", |folder: &str| library(Parser::from_folder(folder)))
+        .register_fn("library", || -> ScriptResult<Array> {
+            Ok(library(Parser::from_folders(Config::load()?.puzzles)))
+        })
+        .register_fn("solve", |file: &mut PuzzleFile| solve(file, SolverConfig::default()))
+        .register_fn("solve", |file: &mut PuzzleFile, preset: &str| -> ScriptResult<Map> {
+            let preset = Preset::from_name(preset).ok_or(format!("Unknown preset: {}", preset))?;
+            Ok(solve(file, SolverConfig::default().with_preset(preset)))
+        })
+        .register_fn("compose", |file: &mut PuzzleFile, other: PuzzleFile, x: f64, y: f64| compose(file, &other, (x as f32, y as f32)))
+        .register_fn("compose", |file: &mut PuzzleFile, other: PuzzleFile, x: i64, y: i64| compose(file, &other, (x as f32, y as f32)))
+        .register_fn("save", |file: &mut PuzzleFile, path: &str| -> ScriptResult<()> {
+            let format = Format::of(Path::new(path)).ok_or(format!("Can't tell the format of {}", path))?;
+            let contents = convert::write(file, format)?;
+            fs::write(path, contents).map_err(|e| format!("Unable to write {}: {}", path, e).into())
+        })
+        .register_fn("write", |path: &str, text: &str| -> ScriptResult<()> {
+            fs::write(path, text).map_err(|e| format!("Unable to write {}: {}", path, e).into())
+        });
+    engine
+}
+
+/// The square with this ID.
+fn square(file: &PuzzleFile, id: &str) -> ScriptResult<usize> {
+    file.puzzle.id_labels.iter().position(|label| label == id).ok_or_else(|| format!("{} has no square {}", file.title, id).into())
+}
+
+/// Both puzzles as one, laid out as they're drawn.
+fn compose(file: &PuzzleFile, other: &PuzzleFile, offset: (f32, f32)) -> ScriptResult<PuzzleFile> {
+    let mut puzzle = file.puzzle.compose(&other.puzzle, (0, 0))?;
+    let gui = file.gui.compose(&file.puzzle, &other.gui, offset);
+    puzzle.layout = Layout::new(&gui);
+    Ok(PuzzleFile { title: file.title.clone(), author: file.author.clone(), puzzle, gui })
+}
+
+/// Reads a puzzle in the format its extension says, or XML if it doesn't say.
+fn load(path: &str) -> ScriptResult<PuzzleFile> {
+    let path = PathBuf::from(path);
+    let format = Format::of(&path).unwrap_or(Format::Xml);
+    Ok(convert::read(&path, format)?.0)
+}
+
+fn library(parser: Parser) -> Array {
+    parser.read_all_puzzles().into_iter().map(|listing| {
+        let (puzzle, gui) = listing.read();
+        Dynamic::from(PuzzleFile { title: listing.name, author: listing.author, puzzle, gui })
+    }).collect()
+}
+
+fn solve(file: &PuzzleFile, config: SolverConfig) -> Map {
+    let report = SolveReport::generate(&file.title, &file.puzzle, config, &[], false);
+    let techniques = report.stats.techniques.used().map(|(technique, count)| (technique.name().into(), Dynamic::from(count as i64))).collect::<Map>();
+    let mut map = Map::new();
+    map.insert("outcome".into(), report.outcome.to_string().into());
+    map.insert("solved".into(), matches!(report.outcome, SolveOutcome::Solved).into());
+    map.insert("steps".into(), (report.stats.steps as i64).into());
+    map.insert("crossings".into(), (report.stats.crosses as i64).into());
+    map.insert("difficulty".into(), report.difficulty.into());
+    map.insert("techniques".into(), techniques.into());
+    map
+}
@@ -1,17 +1,57 @@
-use std::{collections::{HashMap, HashSet, VecDeque}};
+use std::{collections::{HashMap, HashSet, VecDeque}, fmt, hash::{Hash, Hasher}, mem::size_of, sync::{Arc, atomic::{AtomicBool, Ordering as AtomicOrdering}}};
 
-use crate::core::{Bits, Puzzle, bits_to_string};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 
+use crate::{core::{Bits, BitsKey, Puzzle, bits_hash, bits_to_string}, exhaustive::{Exhaustive, Outcome}, filter::ConstraintFilter, symmetry::{self, Permutation}, technique::{Technique, TechniqueCounts}};
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+
+/// Mirror images kept without allocating: every symmetry of a hexagonal board but the identity.
+const MAX_MIRRORS: usize = 11;
+
+#[derive(Clone, Copy, Debug)]
 pub struct Constraint {
     pub bits: Bits,
     pub min_mines: usize,
     pub max_mines: usize,
     pub size: usize,
+    /// How this constraint was derived.  Not part of the constraint's identity.
+    pub technique: Technique,
+    /// How many crossings it took to derive this constraint from the numbers on the board.  Not
+    /// part of the constraint's identity.
+    pub depth: usize,
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits && self.min_mines == other.min_mines && self.max_mines == other.max_mines && self.size == other.size
+    }
+}
+
+impl Eq for Constraint {}
+
+impl Hash for Constraint {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The size follows from the bits
+        state.write_u64(bits_hash(self.bits));
+        self.min_mines.hash(state);
+        self.max_mines.hash(state);
+    }
 }
 
 impl Constraint {
+    /// A constraint with exactly `mines` mines among `bits`.
+    pub fn exact(bits: Bits, mines: usize, technique: Technique) -> Constraint {
+        Constraint {
+            bits,
+            min_mines: mines,
+            max_mines: mines,
+            size: bits.count_ones(),
+            technique,
+            depth: 0,
+        }
+    }
+
     fn to_string(self, len: usize) -> String {
         format!("{} {}->{}/{}", bits_to_string(self.bits, len), self.min_mines, self.max_mines, self.size)
     }
@@ -32,43 +72,333 @@ pub struct PuzzleState {
     pub flagged: Bits,
 }
 
+impl PuzzleState {
+    /// The board drawn as text, in the characters text grids use for revealed (`o`, or `!` with a
+    /// secret number), flagged (`x`) and open (`.`) squares, with `#` on the open squares in `marked`.
+    pub fn draw(&self, marked: Bits) -> String {
+        self.base.layout.draw(self.base.size(), |i| if self.revealed[i] {
+            if self.base.unknowns[i] { '!' } else { 'o' }
+        } else if self.flagged[i] {
+            'x'
+        } else if marked[i] {
+            '#'
+        } else {
+            '.'
+        })
+    }
+}
+
 impl ToString for PuzzleState {
     fn to_string(&self) -> String {
-        let mut line = String::new();
-        line.push('[');
-        for i in 0..self.base.size() {
-            if self.revealed[i] {
-                if self.base.unknowns[i] {
-                    line.push('?');
-                } else {
-                    line.push(' ');
-                }
-            } else if self.flagged[i] {
-                line.push('*')
-            } else {
-                line.push('.')
-            }
+        self.draw(Bits::zeroed())
+    }
+}
+
+/// How much of the solved work a single call to `step()` applies.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum StepGranularity {
+    /// Every currently solved constraint at once, with any zero this reveals opening the squares
+    /// around it as the game does.
+    AllSolved,
+    /// One solved constraint per step.
+    Constraint,
+    /// One square per step.
+    Cell,
+}
+
+impl StepGranularity {
+    pub const ALL: [StepGranularity; 3] = [StepGranularity::AllSolved, StepGranularity::Constraint, StepGranularity::Cell];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StepGranularity::AllSolved => "All solved",
+            StepGranularity::Constraint => "One constraint",
+            StepGranularity::Cell => "One square",
+        }
+    }
+}
+
+/// How much reasoning the solver may do when combining constraints.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum InferenceTier {
+    /// Constraints are only used on their own; nothing is crossed.
+    Single,
+    /// Constraints are only crossed when one contains the other.
+    Subset,
+    /// Any two overlapping constraints are crossed.
+    Cross,
+}
+
+impl InferenceTier {
+    pub const ALL: [InferenceTier; 3] = [InferenceTier::Single, InferenceTier::Subset, InferenceTier::Cross];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InferenceTier::Single => "Single",
+            InferenceTier::Subset => "Subset",
+            InferenceTier::Cross => "Cross",
+        }
+    }
+}
+
+/// Which pending constraint is crossed next, among those of the smallest size and slack.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum CrossOrder {
+    Newest,
+    Oldest,
+}
+
+impl CrossOrder {
+    pub const ALL: [CrossOrder; 2] = [CrossOrder::Newest, CrossOrder::Oldest];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CrossOrder::Newest => "Newest first",
+            CrossOrder::Oldest => "Oldest first",
         }
-        line.push(']');
-        line
     }
 }
 
+/// A named bundle of inference tier, crossing limits, ordering, budget and whether to cross
+/// constraints three at a time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+    Fast,
+    Thorough,
+    HumanLike,
+    Exhaustive,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Fast, Preset::Thorough, Preset::HumanLike, Preset::Exhaustive];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Fast => "fast",
+            Preset::Thorough => "thorough",
+            Preset::HumanLike => "human-like",
+            Preset::Exhaustive => "exhaustive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.iter().copied().find(|p| p.name() == name)
+    }
+}
+
+/// The most layouts `Solver::rank_guesses` looks at.
+const MAX_GUESS_LAYOUTS: usize = 10_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct SolverConfig {
+    /// Constraints larger than this are only crossed if they have few enough mines.
+    pub max_cells: usize,
+    /// Constraints with more mines than this are only crossed if they are small enough.
+    pub max_mines: usize,
+    /// Whether the total number of mines is shown to the player.  When it isn't, the solver
+    /// only reasons from hints and the numbers on revealed squares.
+    pub known_mine_count: bool,
+    pub granularity: StepGranularity,
+    /// Only use numbers a player could see.  The mine layout is then only used to check the
+    /// solver's work, never as a source of information.
+    pub player_knowledge_only: bool,
+    pub tier: InferenceTier,
+    pub order: CrossOrder,
+    /// The number of steps after which the solver gives up.
+    pub budget: Option<usize>,
+    /// When crossing pairs stalls, also combine overlapping constraints three at a time, within the
+    /// same limits.
+    #[serde(default)]
+    pub triple_crossing: bool,
+}
+
+impl SolverConfig {
+    pub fn with_preset(self, preset: Preset) -> SolverConfig {
+        let (tier, max_cells, max_mines, order, budget, triple_crossing) = match preset {
+            Preset::Fast => (InferenceTier::Cross, 3, 2, CrossOrder::Newest, Some(5_000), false),
+            Preset::Thorough => (InferenceTier::Cross, 9, 9, CrossOrder::Newest, Some(100_000), false),
+            // People mostly spot one group of squares inside another, rather than crossing any two
+            Preset::HumanLike => (InferenceTier::Subset, 4, 2, CrossOrder::Oldest, None, false),
+            Preset::Exhaustive => (InferenceTier::Cross, usize::MAX, usize::MAX, CrossOrder::Newest, None, true),
+        };
+
+        SolverConfig {
+            max_cells,
+            max_mines,
+            tier,
+            order,
+            budget,
+            triple_crossing,
+            ..self
+        }
+    }
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        SolverConfig {
+            max_cells: 3,
+            max_mines: 9,
+            known_mine_count: true,
+            granularity: StepGranularity::AllSolved,
+            player_knowledge_only: false,
+            tier: InferenceTier::Cross,
+            order: CrossOrder::Newest,
+            budget: None,
+            triple_crossing: false,
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct SolverStats {
+    pub steps: usize,
+    pub crosses: usize,
+    /// Crossings skipped because bounds arithmetic showed they couldn't tighten anything.
+    pub crosses_skipped: usize,
+    /// Constraints left uncrossed because a symmetric copy of them was crossed instead, with the
+    /// results mapped back across the symmetry.
+    pub crosses_mirrored: usize,
+    /// Sets of three constraints combined after crossing pairs stalled.
+    #[serde(default)]
+    pub triple_crosses: usize,
+    pub techniques: TechniqueCounts,
+    pub cells_total: usize,
+    /// Squares that are neither revealed nor flagged.
+    pub cells_remaining: usize,
+    pub unsolved_constraints: usize,
+    /// Unsolved constraints from the previous step, to show whether the solver is converging.
+    pub previous_unsolved_constraints: usize,
+    /// Constraints still waiting to be crossed.
+    pub pending_constraints: usize,
+    /// Solved constraints waiting to be applied.
+    #[serde(default)]
+    pub solved_constraints: usize,
+    /// Estimated bits of uncertainty left over squares covered by unsolved constraints, treating each
+    /// square as independently a mine with the average density of the constraints covering it.
+    pub frontier_entropy: f64,
+    /// Squares resolved by deduction.
+    pub cells_forced: usize,
+    /// Squares revealed by guessing.  Each guess reveals one square.
+    pub cells_guessed: usize,
+    pub guesses: usize,
+    /// Total size of every pair of constraints considered for crossing.
+    pub cells_scanned: usize,
+    /// The most unsolved constraints held at once.
+    pub peak_unsolved_constraints: usize,
+    /// Sum of the depths of every deduction.
+    pub total_depth: usize,
+    pub max_depth: usize,
+}
+
+impl SolverStats {
+    /// Fraction of squares resolved so far.
+    pub fn progress(&self) -> f32 {
+        if self.cells_total == 0 {
+            1.0
+        } else {
+            1.0 - self.cells_remaining as f32 / self.cells_total as f32
+        }
+    }
+}
+
+/// Roughly how many bytes a solver holds, by what holds them.  Counts what its collections have room
+/// for rather than what they hold, since that's what stays allocated.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MemoryReport {
+    /// Unsolved and solved constraints.
+    pub constraints: usize,
+    /// The constraints on each square.
+    pub index: usize,
+    /// Constraints waiting to be crossed.
+    pub queues: usize,
+    /// The puzzle, cliques and symmetries.
+    pub other: usize,
+}
+
+impl MemoryReport {
+    pub fn total(&self) -> usize {
+        self.constraints + self.index + self.queues + self.other
+    }
+}
+
+/// What an [`InferenceRule`] found.
+#[derive(Clone, Copy, Debug)]
+pub enum Inference {
+    /// A constraint to cross with the rest.  Squares already revealed or flagged are taken out.
+    Constraint(Constraint),
+    /// Squares that are all safe, or all mines.
+    Resolved(Bits, Action),
+}
+
+/// A deduction rule from outside the solver.  Registered rules are tried whenever there is
+/// nothing left to cross, and crossing carries on with whatever they find.
+pub trait InferenceRule: Send + Sync {
+    /// What deductions that follow from the rule are counted as.
+    fn technique(&self) -> Technique;
+
+    /// What the rule can tell from the unsolved constraints and the board.
+    fn infer(&self, constraints: &[Constraint], state: &PuzzleState) -> Vec<Inference>;
+}
+
+/// Asks a solver on another thread to stop at its next chance.  Clones share the request.
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Withdraws the request, so solvers holding the token can carry on.
+    pub fn reset(&self) {
+        self.0.store(false, AtomicOrdering::Relaxed);
+    }
+}
+
+#[derive(Clone)]
 pub struct Solver {
     pub puzzle: PuzzleState,
+    pub stats: SolverStats,
     unsolved_cliques: Vec<(Bits, HashSet<Bits>, HashSet<Bits>)>,
-    unsolved: HashMap<Bits, Constraint>,
-    processing_stack: Vec<Vec<VecDeque<Constraint>>>,
-    square_constraints: Vec<HashSet<Constraint>>,
-    removed: HashSet<Constraint>,
-    solved: HashSet<Constraint>,
+    /// Every live constraint, solved or not, by ID.  Slots of dropped constraints are reused.
+    constraints: Vec<Constraint>,
+    free: Vec<usize>,
+    /// Bumped whenever the constraint in a slot is changed or dropped, so queued entries for what
+    /// used to be there can be told apart.
+    generations: Vec<u32>,
+    unsolved: HashMap<BitsKey, usize>,
+    /// IDs of constraints waiting to be crossed, with the generation they were queued at.
+    processing_stack: Vec<Vec<VecDeque<(usize, u32)>>>,
+    /// The IDs of the constraints on each square.
+    square_constraints: Vec<HashSet<usize>>,
+    solved: HashMap<Constraint, usize>,
+    contradiction: Vec<Constraint>,
     all_bits: Bits,
-    max_cells: usize,
-    max_mines: usize,
+    config: SolverConfig,
+    /// Symmetries of the puzzle, kept only while every reveal and flag respects them.
+    symmetries: Vec<Permutation>,
+    /// The last revealed and flagged squares found to respect `symmetries`.
+    symmetric_state: Option<(Bits, Bits)>,
+    rules: Vec<Arc<dyn InferenceRule>>,
+    /// The squares that have each square as a neighbor, whose counts change when it's revealed or
+    /// flagged.
+    neighbor_of: Vec<SmallVec<[usize; 8]>>,
+    /// For each square, how many of its neighbors are mines that aren't flagged yet.
+    unflagged_mines: Vec<usize>,
+    /// For each square, how many of its neighbors are neither revealed nor flagged.
+    open_neighbors: Vec<usize>,
+    /// Checked between steps and during long searches.  A cancelled solve stops with
+    /// `StepResult::UnexpectedStop`.
+    cancel: Option<CancelToken>,
 }
 
 impl Solver {
-    pub fn new(base: Puzzle, max_cells: usize, max_mines: usize) -> Solver {
+    pub fn new(base: Puzzle, config: SolverConfig) -> Solver {
         let revealed = base.revealed;
 
         let puzzle = PuzzleState {
@@ -90,18 +420,38 @@ impl Solver {
             all_bits.set(i, true);
         }
 
+        let mut neighbor_of = vec![SmallVec::new(); puzzle.base.neighbors.len()];
+        for (square, neighbors) in puzzle.base.neighbors.iter().enumerate() {
+            for neighbor in neighbors.iter_ones() {
+                neighbor_of[neighbor].push(square);
+            }
+        }
+        let unflagged_mines = puzzle.base.neighbors.iter().map(|&neighbors| puzzle.base.displayed_count(neighbors)).collect();
+        let open_neighbors = puzzle.base.neighbors.iter().map(|neighbors| neighbors.count_ones()).collect();
+
         let mut solver = Solver {
             unsolved_cliques: vec![(Bits::zeroed(), puzzle.base.hints.iter().copied().collect(), HashSet::new())],
             all_bits,
             puzzle,
+            stats: SolverStats::default(),
+            constraints: Vec::new(),
+            free: Vec::new(),
+            generations: Vec::new(),
             unsolved: HashMap::new(),
-            solved: HashSet::new(),
-            removed: HashSet::new(),
+            solved: HashMap::new(),
+            contradiction: Vec::new(),
             processing_stack,
             square_constraints,
-            max_cells,
-            max_mines,
+            config,
+            symmetries: Vec::new(),
+            symmetric_state: None,
+            rules: Vec::new(),
+            neighbor_of,
+            unflagged_mines,
+            open_neighbors,
+            cancel: None,
         };
+        solver.symmetries = symmetry::automorphisms(&solver.puzzle.base);
         
         let mut initial_constraints = HashSet::new();
 
@@ -111,17 +461,56 @@ impl Solver {
         }
 
         
-        if initial_constraints.len() == 0 {        
-            solver.add_constraint_from_mine_count(all_bits);
+        if initial_constraints.len() == 0 && config.known_mine_count {
+            solver.add_constraint_from_mine_count(all_bits, Technique::MineCount);
         }      
 
         for square in revealed.iter_ones() {
             solver.reveal_square(square);
         }
+        let flagged = solver.puzzle.base.flagged;
+        for square in flagged.iter_ones() {
+            solver.flag_square(square);
+        }
 
+        solver.update_estimates();
         solver
     }
 
+    /// Starts from a position partway through the puzzle, as in an imported save or after playing by
+    /// hand, without replaying how it was reached.  `revealed` and `flagged` are added to the
+    /// puzzle's own starting squares, and every hint and the mine count, when known, are constrained
+    /// up front rather than once crossing runs out.  Fails if the position doesn't fit the puzzle: a
+    /// revealed mine, a flag on a safe square, or a square both revealed and flagged.
+    pub fn from_state(mut puzzle: Puzzle, revealed: Bits, flagged: Bits, config: SolverConfig) -> Result<Solver, String> {
+        let mut squares = Bits::zeroed();
+        for i in 0..puzzle.size() {
+            squares.set(i, true);
+        }
+        let problems = [
+            ((revealed | flagged) & !squares, "that aren't in the puzzle"),
+            (revealed & puzzle.mines, "revealed that are mines"),
+            (flagged & !puzzle.mines, "flagged that are safe"),
+            ((revealed | puzzle.revealed) & (flagged | puzzle.flagged), "both revealed and flagged"),
+        ];
+        if let Some((bits, why)) = problems.iter().find(|(bits, _)| bits.any()) {
+            let squares = bits.iter_ones().map(|i| puzzle.id_labels.get(i).map_or_else(|| i.to_string(), String::clone)).collect::<Vec<_>>();
+            return Err(format!("The position has squares {}: {}", why, squares.join(", ")));
+        }
+
+        puzzle.revealed |= revealed;
+        puzzle.flagged |= flagged;
+        let mut solver = Solver::new(puzzle, config);
+        for hint in solver.puzzle.base.hints.clone() {
+            solver.add_constraint_from_mine_count(hint, Technique::Hint);
+        }
+        if config.known_mine_count {
+            solver.add_constraint_from_mine_count(solver.all_bits, Technique::MineCount);
+        }
+        solver.update_estimates();
+        Ok(solver)
+    }
+
     fn find_cliques(&mut self) -> Option<Bits> {
         loop {
             if let Some((mut clique, mut remaining, mut excluded)) = self.unsolved_cliques.pop() {
@@ -161,14 +550,17 @@ impl Solver {
         }
     }
 
-    fn add_constraint_from_mine_count(self: &mut Solver, bits: Bits) -> Constraint {
-        let bits = bits & !self.puzzle.revealed & !self.puzzle.flagged;
-        let mines = (bits & self.puzzle.base.mines).count_ones();
+    /// Adds a constraint from a number shown to the player, less the flags already placed in its group.
+    fn add_constraint_from_mine_count(self: &mut Solver, group: Bits, technique: Technique) -> Constraint {
+        let bits = group & !self.puzzle.revealed & !self.puzzle.flagged;
+        let mines = self.puzzle.base.displayed_count(group) - (group & self.puzzle.flagged).count_ones();
         let constraint = Constraint {
             bits,
             min_mines: mines,
             max_mines: mines,
-            size: bits.count_ones()
+            size: bits.count_ones(),
+            technique,
+            depth: 0,
         };
         self.add_constraint(constraint);
 
@@ -176,17 +568,37 @@ impl Solver {
     }
     
     fn add_constraint(self: &mut Solver, constraint: Constraint) {
-        assert!((constraint.bits & self.puzzle.revealed).not_any(), "Constraint involves revealed square! \nConstraint: {}, \nPuzzle:   {}", constraint.to_string(self.puzzle.base.size()), self.puzzle.to_string());
-        assert!((constraint.bits & self.puzzle.flagged).not_any(), "Constraint involves flagged square! \nConstraint: {}, \nPuzzle:    {}", constraint.bits.to_string(), self.puzzle.to_string());
-        assert!(constraint.max_mines <= constraint.size, "Constraint has more max mines than its size! Constraint: {}", constraint.to_string(self.puzzle.base.size()));
-        
+        self.store_constraint(constraint, None);
+    }
+
+    /// Adds `constraint`, reusing `slot` if given: a constraint taken out of the lookups by
+    /// [`Solver::forget`] and off the list of the one square it no longer covers, but still listed on
+    /// the rest.  Storing it there leaves those lists alone.
+    fn store_constraint(self: &mut Solver, constraint: Constraint, slot: Option<usize>) {
+        assert!((constraint.bits & self.puzzle.revealed).not_any(), "Constraint involves revealed square! \nConstraint: {} ({}), \nPuzzle:\n{}", constraint.to_string(self.puzzle.base.size()), self.puzzle.base.labels(constraint.bits & self.puzzle.revealed), self.puzzle.to_string());
+        assert!((constraint.bits & self.puzzle.flagged).not_any(), "Constraint involves flagged square! \nConstraint: {} ({}), \nPuzzle:\n{}", constraint.bits.to_string(), self.puzzle.base.labels(constraint.bits & self.puzzle.flagged), self.puzzle.to_string());
+
+        if constraint.min_mines > constraint.max_mines || constraint.max_mines > constraint.size {
+            self.contradict(&[constraint]);
+            self.release(slot);
+            return;
+        }
+
+        if self.config.player_knowledge_only {
+            let actual = (constraint.bits & self.puzzle.base.mines).count_ones();
+            assert!(constraint.min_mines <= actual && actual <= constraint.max_mines, "Constraint disagrees with the mine layout! \nConstraint: {} on {}, \nMines: {}", constraint.to_string(self.puzzle.base.size()), self.puzzle.base.labels(constraint.bits), actual);
+        }
+
         if constraint.is_useless() {
+            self.release(slot);
             return;
         }
 
-        if let Some(&known) = self.unsolved.get(&constraint.bits) {
+        if let Some(&known_id) = self.unsolved.get(&constraint.bits.into()) {
+            let known = self.constraints[known_id];
             assert!(constraint.bits == known.bits, "Constraint bits don't match known bits! \nConstraint: {}, \nKnown:   {}", constraint.to_string(self.puzzle.base.size()), known.to_string(self.puzzle.base.size()));
             if known.min_mines >= constraint.min_mines && known.max_mines <= constraint.max_mines {
+                self.release(slot);
                 return;
             }
 
@@ -195,90 +607,314 @@ impl Solver {
                 min_mines: known.min_mines.max(constraint.min_mines),
                 max_mines: known.max_mines.min(constraint.max_mines),
                 size: constraint.bits.count_ones(),
+                technique: constraint.technique,
+                depth: constraint.depth,
             };
 
-            self.remove_constraint(known);
-            self.add_constraint(new);
+            if new.min_mines > new.max_mines {
+                self.contradict(&[known, constraint]);
+                self.release(slot);
+                return;
+            }
+
+            self.remove_constraint(known_id);
+            self.store_constraint(new, slot);
+            return;
+        }
+
+        if constraint.is_solved() && self.solved.contains_key(&constraint) {
+            self.release(slot);
             return;
         }
 
+        let id = match slot {
+            Some(id) => {
+                self.constraints[id] = constraint;
+                id
+            }
+            None => {
+                let id = match self.free.pop() {
+                    Some(id) => {
+                        self.constraints[id] = constraint;
+                        id
+                    }
+                    None => {
+                        self.constraints.push(constraint);
+                        self.generations.push(0);
+                        self.constraints.len() - 1
+                    }
+                };
+                constraint.bits.iter_ones().for_each(|square| {self.square_constraints[square].insert(id);});
+                id
+            }
+        };
+
         if constraint.is_solved() {
-            self.solved.insert(constraint);
+            self.solved.insert(constraint, id);
         } else {
-            self.unsolved.insert(constraint.bits, constraint);
-            self.processing_stack[constraint.size-1][constraint.max_mines - constraint.min_mines].push_back(constraint);
+            self.unsolved.insert(constraint.bits.into(), id);
+            self.processing_stack[constraint.size-1][constraint.max_mines - constraint.min_mines].push_back((id, self.generations[id]));
         }
+    }
 
-        constraint.bits.iter_ones().for_each(|square| {self.square_constraints[square].insert(constraint);});
+    /// Records that the given constraints can't all hold.  Only the first contradiction found is kept.
+    fn contradict(&mut self, constraints: &[Constraint]) {
+        if self.contradiction.is_empty() {
+            self.contradiction.extend_from_slice(constraints);
+        }
     }
 
-    fn remove_constraint(self: &mut Solver, constraint: Constraint) {
-        let known = if constraint.is_solved() {
-            if self.solved.remove(&constraint) {
-                constraint
-            } else {
-                panic!("Constraint not in solved: {}", constraint.to_string(self.puzzle.base.size()))
+    fn remove_constraint(self: &mut Solver, id: usize) {
+        self.forget(id);
+        self.release(Some(id));
+    }
+
+    /// Takes constraint `id` out of the solved and unsolved lookups, leaving it on its squares.  Any
+    /// queued entry for it goes stale.
+    fn forget(self: &mut Solver, id: usize) {
+        let constraint = self.constraints[id];
+        self.generations[id] = self.generations[id].wrapping_add(1);
+        if constraint.is_solved() {
+            if self.solved.remove(&constraint).is_none() {
+                panic!("Constraint not in solved: {} on {}", constraint.to_string(self.puzzle.base.size()), self.puzzle.base.labels(constraint.bits))
             }
         } else {
-            self.removed.insert(constraint);
-            self.unsolved.remove(&constraint.bits).expect("Attempted to remove constraint that did not exist!")
-        };
+            self.unsolved.remove(&constraint.bits.into()).expect("Attempted to remove constraint that did not exist!");
+        }
+    }
+
+    /// Takes a constraint off the squares it's listed on and frees its ID for reuse.
+    fn release(self: &mut Solver, slot: Option<usize>) {
+        if let Some(id) = slot {
+            for square in self.constraints[id].bits.iter_ones() {
+                self.square_constraints[square].remove(&id);
+            }
+            self.free.push(id);
+        }
+    }
+
+    /// Reveals a safe square as if the puzzle had started with it revealed, for trying out changes to
+    /// a puzzle without solving it again from the start.
+    pub fn reveal(&mut self, square: usize) {
+        self.symmetries.clear();
+        self.reveal_square(square);
+    }
+
+    /// Flags a mine as if the puzzle had started with it flagged, as a player does by hand.
+    pub fn flag(&mut self, square: usize) {
+        self.symmetries.clear();
+        self.flag_square(square);
+    }
+
+    /// Adds a hint over `group` as if the puzzle had started with it.
+    pub fn add_hint(&mut self, group: Bits) {
+        self.symmetries.clear();
+        self.puzzle.base.hints.push(group);
+        if (group & !self.puzzle.revealed & !self.puzzle.flagged).any() {
+            self.add_constraint_from_mine_count(group, Technique::Hint);
+        }
+    }
+
+    /// Carries on from here with `config`.  Every unsolved constraint is queued to be crossed again,
+    /// as new limits may allow crossings that were passed over.
+    pub fn reconfigure(&mut self, config: SolverConfig) {
+        self.config = config;
+        for &id in self.unsolved.values() {
+            // Entries already queued go stale, so nothing is crossed twice
+            self.generations[id] = self.generations[id].wrapping_add(1);
+            let constraint = &self.constraints[id];
+            self.processing_stack[constraint.size-1][constraint.max_mines - constraint.min_mines].push_back((id, self.generations[id]));
+        }
+    }
+
+    /// Stops the solve at its next chance once `token` is cancelled.
+    pub fn set_cancel(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
 
-        for i in known.bits.iter_ones() {
-            let removed = self.square_constraints[i].remove(&known);
-            assert!(removed, "Attempted to remove constraint from a square that did not exist!");
-        }  
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Adds `rule` to the ones tried when crossing runs out.
+    pub fn add_rule(&mut self, rule: Arc<dyn InferenceRule>) {
+        self.rules.push(rule);
+    }
+
+    /// Runs every registered rule on where the solver is and adds what they find.
+    fn apply_rules(&mut self) {
+        let constraints = self.unsolved_constraints();
+        let open = !self.puzzle.revealed & !self.puzzle.flagged;
+        for rule in self.rules.clone() {
+            for inference in rule.infer(&constraints, &self.puzzle) {
+                let constraint = match inference {
+                    Inference::Constraint(constraint) => constraint,
+                    Inference::Resolved(bits, Action::Reveal) => Constraint::exact(bits, 0, rule.technique()),
+                    Inference::Resolved(bits, Action::Flag) => Constraint::exact(bits, bits.count_ones(), rule.technique()),
+                };
+                let flagged = (constraint.bits & self.puzzle.flagged).count_ones();
+                let bits = constraint.bits & open;
+                self.add_constraint(Constraint {
+                    bits,
+                    min_mines: constraint.min_mines.saturating_sub(flagged),
+                    max_mines: constraint.max_mines.saturating_sub(flagged),
+                    size: bits.count_ones(),
+                    ..constraint
+                });
+            }
+        }
     }
 
     fn reveal_square(self: &mut Solver, square: usize) {
-        assert!(!self.puzzle.revealed[square], "Square {} already revealed! \nPuzzle:   {}", square, self.puzzle.to_string());
-        assert!(!self.puzzle.base.mines[square], "Square {} was revealed, but was a mine!", square);
+        assert!(!self.puzzle.revealed[square], "Square {} already revealed! \nPuzzle:\n{}", self.puzzle.base.id_labels[square], self.puzzle.to_string());
+        assert!(!self.puzzle.base.mines[square], "Square {} was revealed, but was a mine!", self.puzzle.base.id_labels[square]);
 
-        for mut constraint in self.square_constraints[square].clone() {
+        for id in self.constraints_at(square) {
+            let mut constraint = self.constraints[id];
             assert!(constraint.size > 0, "Revealed a square in a 0-sized constraint!");
             assert!(constraint.bits[square], "Constraint did not include target square!");
-            
-            self.remove_constraint(constraint);
+
+            self.forget(id);
+            self.square_constraints[square].remove(&id);
             constraint.bits.set(square, false);
             constraint.size -= 1;
             constraint.max_mines = constraint.max_mines.min(constraint.size);
-            self.add_constraint(constraint);
+            self.store_constraint(constraint, Some(id));
         }
 
         self.puzzle.revealed.set(square, true);
+        for &watcher in &self.neighbor_of[square] {
+            self.open_neighbors[watcher] -= 1;
+        }
 
         if !self.puzzle.base.unknowns[square] {
-            self.add_constraint(get_neighbor_constraint(&self.puzzle, square))
+            self.add_constraint(self.neighbor_constraint(square))
+        }
+    }
+
+    /// What the number on a revealed square says about its open neighbors, from the counts kept
+    /// up to date as squares are revealed and flagged.
+    fn neighbor_constraint(&self, square: usize) -> Constraint {
+        assert!(self.puzzle.revealed[square] && !self.puzzle.base.unknowns[square], "Square {} doesn't show a number!", self.puzzle.base.id_labels[square]);
+        let remaining_mines = self.unflagged_mines[square];
+
+        Constraint {
+            bits: self.puzzle.base.neighbors[square] & !self.puzzle.revealed & !self.puzzle.flagged,
+            max_mines: remaining_mines,
+            min_mines: remaining_mines,
+            size: self.open_neighbors[square],
+            technique: Technique::NakedSingle,
+            depth: 0,
+        }
+    }
+
+    /// Reveals the open squares around every zero in `opened`, then around any zeros that turns up,
+    /// until the whole empty region is open.  Each zero's neighbors are added to `deductions`, and
+    /// all the squares revealed are returned.
+    fn open_zeros(&mut self, opened: Bits, deductions: &mut Vec<Deduction>) -> Bits {
+        let mut revealed = Bits::zeroed();
+        let mut pending = opened;
+        while let Some(square) = pending.first_one() {
+            pending.set(square, false);
+            let base = &self.puzzle.base;
+            if base.unknowns[square] || base.displayed_count(base.neighbors[square]) > 0 {
+                continue;
+            }
+
+            let constraint = self.neighbor_constraint(square);
+            if constraint.size == 0 {
+                continue;
+            }
+            for cell in constraint.bits.iter_ones() {
+                self.reveal_square(cell);
+            }
+            self.stats.techniques.record(constraint.technique);
+            deductions.push(Deduction { constraint, action: Action::Reveal, cells: constraint.bits });
+            revealed |= constraint.bits;
+            pending |= constraint.bits;
         }
+        revealed
+    }
+
+    /// The IDs of the constraints through `square`, to change while going through them.  Rarely more
+    /// than a dozen, so they're kept off the heap.
+    fn constraints_at(&self, square: usize) -> SmallVec<[usize; 16]> {
+        self.square_constraints[square].iter().copied().collect()
     }
 
     fn flag_square(self: &mut Solver, square: usize) {
-        assert!(!self.puzzle.flagged[square], "Square {} already flagged! \nPuzzle:   {}", square, self.puzzle.to_string());
-        assert!(self.puzzle.base.mines[square], "Flagged a non-mine!");
+        assert!(!self.puzzle.flagged[square], "Square {} already flagged! \nPuzzle:\n{}", self.puzzle.base.id_labels[square], self.puzzle.to_string());
+        assert!(self.puzzle.base.mines[square], "Flagged a non-mine: {}", self.puzzle.base.id_labels[square]);
         
-        for mut constraint in self.square_constraints[square].clone() {
-            assert!(constraint.max_mines > 0, "Flagged a mine in a constraint with 0 max mines!");
+        for id in self.constraints_at(square) {
+            let mut constraint = self.constraints[id];
+            if constraint.max_mines == 0 {
+                // Dropped so it can't be stored again with the square flagged
+                self.contradict(&[constraint]);
+                self.remove_constraint(id);
+                continue;
+            }
             assert!(constraint.size > 0, "Flagged a mine in a constraint with a size of 0!");
             assert!(constraint.bits[square], "Constraint did not include target square!");
 
-            self.remove_constraint(constraint);
+            self.forget(id);
+            self.square_constraints[square].remove(&id);
             constraint.bits.set(square, false);
             constraint.size -= 1;
             constraint.max_mines -= 1;
             constraint.min_mines = constraint.min_mines.saturating_sub(1);
-            self.add_constraint(constraint);
+            self.store_constraint(constraint, Some(id));
         }
 
         self.puzzle.flagged.set(square, true);
+        for &watcher in &self.neighbor_of[square] {
+            self.unflagged_mines[watcher] -= 1;
+            self.open_neighbors[watcher] -= 1;
+        }
+    }
+
+    /// Whether the solver's state still respects the puzzle's symmetries.  Once it doesn't, say after
+    /// a guess, they are dropped for good, since the constraints no longer come in symmetric sets.
+    fn symmetric(&mut self) -> bool {
+        let state = (self.puzzle.revealed, self.puzzle.flagged);
+        if self.symmetries.is_empty() || self.symmetric_state == Some(state) {
+            return !self.symmetries.is_empty();
+        }
+
+        if self.symmetries.iter().all(|permutation| symmetry::map(permutation, state.0) == state.0 && symmetry::map(permutation, state.1) == state.1) {
+            self.symmetric_state = Some(state);
+            true
+        } else {
+            self.symmetries.clear();
+            false
+        }
+    }
+
+    /// The images of a constraint under each of the puzzle's symmetries.
+    fn mirrors(&self, constraint: Constraint) -> SmallVec<[Constraint; MAX_MIRRORS]> {
+        self.symmetries.iter().map(|permutation| Constraint { bits: symmetry::map(permutation, constraint.bits), ..constraint }).collect()
     }
 
     fn add_all_crosses(self: &mut Solver, constraint: Constraint) {
+        if self.config.tier == InferenceTier::Single {
+            return;
+        }
+
+        // Every constraint's mirror images are added along with it, so if a smaller one is waiting,
+        // crossing that covers this one too
+        let symmetric = self.symmetric();
+        if symmetric && self.mirrors(constraint).iter().any(|mirror| mirror.bits < constraint.bits && self.unsolved.get(&mirror.bits.into()).map(|&id| &self.constraints[id]) == Some(mirror)) {
+            self.stats.crosses_mirrored += 1;
+            return;
+        }
+
         let mut seen = Bits::zeroed();
-        let mut crosses = Vec::new();
+        let mut crosses = SmallVec::<[Constraint; 6]>::new();
 
         for square in constraint.bits.iter_ones() {
-            for &to_cross in &self.square_constraints[square] {
-                if to_cross.max_mines > self.max_mines && to_cross.size > self.max_cells
+            for &id in &self.square_constraints[square] {
+                let to_cross = self.constraints[id];
+                if to_cross.max_mines > self.config.max_mines && to_cross.size > self.config.max_cells
                 {
                     continue;
                 }
@@ -287,6 +923,17 @@ impl Solver {
                     continue;
                 }
 
+                if self.config.tier == InferenceTier::Subset && (constraint.bits & !to_cross.bits).any() && (to_cross.bits & !constraint.bits).any() {
+                    continue;
+                }
+
+                self.stats.cells_scanned += constraint.size + to_cross.size;
+                if !can_tighten(constraint, to_cross) {
+                    self.stats.crosses_skipped += 1;
+                    continue;
+                }
+
+                self.stats.crosses += 1;
                 crosses.extend(cross_constraints(constraint, to_cross))
             }
 
@@ -295,93 +942,807 @@ impl Solver {
 
         for cross in crosses {
             self.add_constraint(cross);
+            if symmetric {
+                for mirror in self.mirrors(cross) {
+                    self.add_constraint(mirror);
+                }
+            }
         }
     }
 
     pub fn step(&mut self) -> StepResult {
+        let result = self.next_step();
+        self.update_estimates();
+        result
+    }
+
+    fn update_estimates(&mut self) {
+        let size = self.puzzle.base.size();
+        let mut density = vec![0.0; size];
+        let mut coverage = vec![0; size];
+        for constraint in self.unsolved_values() {
+            let average = (constraint.min_mines + constraint.max_mines) as f64 / 2.0 / constraint.size as f64;
+            for square in constraint.bits.iter_ones() {
+                density[square] += average;
+                coverage[square] += 1;
+            }
+        }
+
+        let remaining = self.remaining();
+        let stats = &mut self.stats;
+        stats.frontier_entropy = density.iter().zip(&coverage).filter(|&(_, &count)| count > 0).map(|(&sum, &count)| {
+            let p = sum / count as f64;
+            if p <= 0.0 || p >= 1.0 {
+                0.0
+            } else {
+                -p * p.log2() - (1.0 - p) * (1.0 - p).log2()
+            }
+        }).sum();
+        stats.cells_total = size;
+        stats.cells_remaining = remaining;
+        stats.previous_unsolved_constraints = stats.unsolved_constraints;
+        stats.unsolved_constraints = self.unsolved.len();
+        stats.peak_unsolved_constraints = stats.peak_unsolved_constraints.max(stats.unsolved_constraints);
+        stats.solved_constraints = self.solved.len();
+        let generations = &self.generations;
+        stats.pending_constraints = self.processing_stack.iter().flatten().flatten().filter(|&&(id, generation)| generations[id] == generation).count();
+    }
+
+    fn next_step(&mut self) -> StepResult {
         /* 
         if let Some(clique) = self.find_cliques() {
             let constraint = self.add_constraint_from_mine_count(!clique & self.all_bits);
             return StepResult::CliqueConstraint(constraint)
         }*/
 
-        if !self.solved.is_empty() 
-        {
-            let mut to_reveal = Bits::zeroed();
-            let mut to_flag = Bits::zeroed();
-            for &constraint in &self.solved {
-                assert!(constraint.size > 0, "Constraint of size 0 in solved!");
-                if constraint.max_mines == 0 {
-                    to_reveal |= constraint.bits;
-                } else {
-                    to_flag |= constraint.bits;
+        if !self.contradiction.is_empty() {
+            return StepResult::Contradiction(self.contradiction.clone());
+        }
+
+        if self.config.budget.is_some_and(|budget| self.stats.steps >= budget) {
+            return StepResult::UnexpectedStop(String::from("Step budget exhausted"));
+        }
+        if self.cancelled() {
+            return StepResult::UnexpectedStop(String::from("Cancelled"));
+        }
+        self.stats.steps += 1;
+
+        let mut rules_used = self.rules.is_empty();
+        let mut fallback_used = false;
+        let mut triples_used = !self.config.triple_crossing || self.config.tier != InferenceTier::Cross;
+        loop {
+            if !self.solved.is_empty() {
+                return self.apply_solved();
+            }
+
+            let order = self.config.order;
+            let next = self.processing_stack.iter_mut().flatten().find_map(|f| match order {
+                CrossOrder::Newest => f.pop_back(),
+                CrossOrder::Oldest => f.pop_front(),
+            });
+
+            if let Some((id, generation)) = next {
+                if self.generations[id] == generation {
+                    let next = self.constraints[id];
+                    self.add_all_crosses(next);
+                    if !self.contradiction.is_empty() {
+                        return StepResult::Contradiction(self.contradiction.clone());
+                    }
+                    return StepResult::CrossConstraint(next);
+                }
+            } else if !rules_used {
+                self.apply_rules();
+                rules_used = true;
+            } else if fallback_used && !triples_used {
+                self.add_all_triple_crosses();
+                if !self.contradiction.is_empty() {
+                    return StepResult::Contradiction(self.contradiction.clone());
+                }
+                if self.cancelled() {
+                    return StepResult::UnexpectedStop(String::from("Cancelled"));
+                }
+                triples_used = true;
+            } else if fallback_used {
+                return StepResult::Stuck;
+            } else {
+                // This can happen if a previous constraint combination was ignored due to size
+                for constraint in self.puzzle.base.hints.clone() {
+                    self.add_constraint_from_mine_count(constraint, Technique::Hint);
+                }
+
+                for (square, neighborhood) in self.puzzle.base.neighbors.clone().into_iter().enumerate() {
+                    // A player only sees the numbers on revealed squares, and without the mine count
+                    // neither does the solver
+                    let visible = self.puzzle.revealed[square] && !self.puzzle.base.unknowns[square];
+                    if visible || (self.config.known_mine_count && !self.config.player_knowledge_only) {
+                        self.add_constraint_from_mine_count(neighborhood, Technique::NakedSingle);
+                    }
+                }
+
+                if self.config.known_mine_count {
+                    self.add_constraint_from_mine_count(self.all_bits, Technique::MineCount);
                 }
+
+                fallback_used = true;
             }
+        }
+    }
 
-            assert!((to_flag & self.puzzle.revealed).not_any(), "Revealing existing squares! \nSquares:  {}\nPuzzle: {}\nConstraints: \n{}", bits_to_string(to_reveal, self.puzzle.base.size()), self.puzzle.to_string(), self.solved.iter().map(|c| c.to_string(self.puzzle.base.size())).collect::<Vec<String>>().join("\n"));
-            assert!((to_flag & self.puzzle.flagged).not_any(), "Flagging existing flags! \nFlags:    {}\nExisting: {}\nConstraints: {}", bits_to_string(to_flag, self.puzzle.base.size()), self.puzzle.to_string(), self.solved.iter().map(|c| c.to_string(self.puzzle.base.size())).collect::<Vec<String>>().join("\n"));
+    /// Combines every three unsolved constraints where one overlaps the other two, and bounds the
+    /// mines in each region of their overlap.  Constraints past the crossing limits are left out.
+    fn add_all_triple_crosses(&mut self) {
+        let candidates = self.unsolved_values()
+            .filter(|c| c.max_mines <= self.config.max_mines || c.size <= self.config.max_cells)
+            .copied()
+            .collect::<Vec<_>>();
+        let overlapping = candidates.iter().enumerate().map(|(a, constraint)| {
+            (0..candidates.len()).filter(|&b| b != a && (candidates[b].bits & constraint.bits).any()).collect::<Vec<_>>()
+        }).collect::<Vec<_>>();
 
-            for square in to_reveal.iter_ones() {
-                //println!("Revealing squares: {}", to_reveal);
-                self.reveal_square(square);
+        let mut crosses = Vec::new();
+        for (hub, others) in overlapping.iter().enumerate() {
+            // Half the crossings would leave the solve depending on where it was cancelled
+            if self.cancelled() {
+                return;
             }
-            
-            for square in to_flag.iter_ones() {
-                //println!("Flagging squares: {}", to_flag);
-                self.flag_square(square);
+            for (n, &left) in others.iter().enumerate() {
+                for &right in &others[n + 1..] {
+                    // A triple that overlaps all around is found from each of its constraints, so only
+                    // keep it from the first
+                    if (candidates[left].bits & candidates[right].bits).any() && hub > left {
+                        continue;
+                    }
+                    self.stats.cells_scanned += candidates[hub].size + candidates[left].size + candidates[right].size;
+                    match cross_three([candidates[hub], candidates[left], candidates[right]]) {
+                        Some(regions) => {
+                            self.stats.triple_crosses += 1;
+                            crosses.extend(regions);
+                        }
+                        None => {
+                            self.contradict(&[candidates[hub], candidates[left], candidates[right]]);
+                            return;
+                        }
+                    }
+                }
             }
-            let remaining = self.puzzle.base.size() - (self.puzzle.revealed | self.puzzle.flagged).count_ones();
-            if remaining == 0 {
-                return StepResult::Finished;
+        }
+
+        for cross in crosses {
+            self.add_constraint(cross);
+        }
+    }
+
+    /// How much memory the solver holds.
+    pub fn memory_report(&self) -> MemoryReport {
+        // Hash tables keep a control byte for every slot
+        let set = |capacity: usize, entry: usize| capacity * (entry + 1);
+        let constraint = size_of::<Constraint>();
+        let base = &self.puzzle.base;
+        MemoryReport {
+            constraints: self.constraints.capacity() * constraint
+                + self.free.capacity() * size_of::<usize>()
+                + self.generations.capacity() * size_of::<u32>()
+                + set(self.unsolved.capacity(), size_of::<BitsKey>() + size_of::<usize>())
+                + set(self.solved.capacity(), constraint + size_of::<usize>())
+                + self.contradiction.capacity() * constraint,
+            index: self.square_constraints.iter().map(|constraints| size_of::<HashSet<usize>>() + set(constraints.capacity(), size_of::<usize>())).sum(),
+            queues: self.processing_stack.iter().map(|tier| size_of::<Vec<VecDeque<(usize, u32)>>>() + tier.iter().map(|queue| size_of::<VecDeque<(usize, u32)>>() + queue.capacity() * size_of::<(usize, u32)>()).sum::<usize>()).sum(),
+            other: (base.neighbors.capacity() + base.hints.capacity()) * size_of::<Bits>()
+                + base.id_labels.iter().map(|label| size_of::<String>() + label.capacity()).sum::<usize>()
+                + self.unsolved_cliques.iter().map(|(_, a, b)| size_of::<Bits>() * 3 + set(a.capacity(), size_of::<Bits>()) + set(b.capacity(), size_of::<Bits>())).sum::<usize>()
+                + self.symmetries.iter().map(|permutation| permutation.capacity() * size_of::<usize>()).sum::<usize>(),
+        }
+    }
+
+    fn unsolved_values(&self) -> impl Iterator<Item = &Constraint> {
+        self.unsolved.values().map(move |&id| &self.constraints[id])
+    }
+
+    /// Every unsolved constraint, ordered by the squares they cover.
+    pub fn unsolved_constraints(&self) -> Vec<Constraint> {
+        let mut constraints = self.unsolved_values().copied().collect::<Vec<_>>();
+        constraints.sort_by_key(|c| (c.bits.first_one(), c.size, c.min_mines, c.max_mines));
+        constraints
+    }
+
+    /// The unsolved constraints that match `filter`.
+    pub fn query(&self, filter: &ConstraintFilter) -> Vec<Constraint> {
+        self.unsolved_constraints().into_iter().filter(|c| filter.matches(c)).collect()
+    }
+
+    /// The unsolved constraints that cover `square`, smallest first.
+    pub fn constraints_on(&self, square: usize) -> Vec<Constraint> {
+        let mut constraints = self.square_constraints[square].iter().map(|&id| self.constraints[id]).collect::<Vec<_>>();
+        constraints.sort_by_key(|c| (c.size, c.max_mines - c.min_mines, c.bits.first_one()));
+        constraints
+    }
+
+    /// Checks whether the puzzle's mines are the only layout consistent with every hint, every
+    /// number the finished board shows and, when known, the total mine count.  This ignores the order
+    /// squares are revealed in, so a unique puzzle may still require guessing.
+    pub fn verify_unique(&self) -> Uniqueness {
+        let base = &self.puzzle.base;
+        let (constraints, numbered) = self.full_information();
+
+        // Any other layout has to differ from this one on some square that doesn't show a number
+        let candidates = self.all_bits & !numbered;
+        let search = Exhaustive::new(&constraints, candidates);
+        let mut gave_up = false;
+        for square in candidates.iter_ones() {
+            let mut forced = Bits::zeroed();
+            forced.set(square, true);
+            let outcome = if base.mines[square] {
+                search.find(Bits::zeroed(), forced)
+            } else {
+                search.find(forced, Bits::zeroed())
+            };
+
+            match outcome {
+                Outcome::Found(mines) => return Uniqueness::Ambiguous(mines),
+                Outcome::GaveUp => gave_up = true,
+                Outcome::Impossible => {}
+            }
+        }
+
+        if gave_up {
+            Uniqueness::Undetermined
+        } else {
+            Uniqueness::Unique
+        }
+    }
+
+    /// Lists up to `limit` layouts other than the puzzle's own that fit every clue `verify_unique`
+    /// considers, restricted to the squares where some alternative differs from the real layout.
+    pub fn alternative_layouts(&self, limit: usize) -> Alternatives {
+        let mines = self.puzzle.base.mines;
+        let (constraints, numbered) = self.full_information();
+        // One extra, since the puzzle's own layout is among those found
+        let found = Exhaustive::new(&constraints, self.all_bits & !numbered).enumerate(Bits::zeroed(), Bits::zeroed(), limit + 1);
+
+        let others = found.layouts.into_iter().filter(|&layout| layout != mines).take(limit).collect::<Vec<_>>();
+        let ambiguous = others.iter().fold(Bits::zeroed(), |ambiguous, &layout| ambiguous | (layout ^ mines));
+        Alternatives {
+            complete: found.complete && others.len() < limit,
+            layouts: others.into_iter().map(|layout| layout & ambiguous).collect(),
+            ambiguous,
+        }
+    }
+
+    /// A smallest set of the puzzle's starting clues that can't all hold, found by leaving out each
+    /// clue in turn while the rest still can't.  `None` if the clues fit together, in which case a
+    /// contradiction is a mistake of the solver's rather than the puzzle's.
+    pub fn unsat_core(&self) -> Option<Vec<Constraint>> {
+        let base = &self.puzzle.base;
+        let mut clues = base.hints.iter().map(|&hint| Constraint::exact(hint, base.displayed_count(hint), Technique::Hint)).collect::<Vec<_>>();
+        for square in (base.revealed & !base.unknowns).iter_ones() {
+            let neighbors = base.neighbors[square];
+            clues.push(Constraint::exact(neighbors, base.displayed_count(neighbors), Technique::NakedSingle));
+        }
+        if self.config.known_mine_count {
+            clues.push(Constraint::exact(self.all_bits, base.displayed_count(self.all_bits), Technique::MineCount));
+        }
+
+        // Giving up counts as fitting together, so only clues that surely conflict are reported
+        let conflicting = |clues: &[Constraint]| Exhaustive::new(clues, self.all_bits).find(Bits::zeroed(), base.revealed) == Outcome::Impossible;
+        if !conflicting(&clues) {
+            return None;
+        }
+        let mut i = 0;
+        while i < clues.len() {
+            let clue = clues.remove(i);
+            if !conflicting(&clues) {
+                clues.insert(i, clue);
+                i += 1;
             }
+        }
+        Some(clues)
+    }
 
-            return StepResult::Progress {
-                revealed: to_reveal,
-                flagged: to_flag,
+    /// Every square that follows from what a player can see now: the hints, the numbers on revealed
+    /// squares, the flags and, when known, the mine count.  Unlike stepping, this finds everything at
+    /// once, searching layouts where the solver's own constraints don't settle a square, and leaves
+    /// the solver as it was.
+    pub fn prove_all_current(&self) -> Provable {
+        let open = self.all_bits & !self.puzzle.revealed & !self.puzzle.flagged;
+        let constraints = self.visible_constraints(self.config.known_mine_count);
+        let search = Exhaustive::new(&constraints, open);
+        let mut provable = Provable { safe: Bits::zeroed(), mines: Bits::zeroed(), complete: true };
+        let layout = match search.find(Bits::zeroed(), Bits::zeroed()) {
+            Outcome::Found(layout) => layout,
+            Outcome::Impossible | Outcome::GaveUp => {
+                provable.complete = false;
+                return provable;
+            }
+        };
+
+        // A square is settled if no layout disagrees with the first one on it.  Any layout that does
+        // also shows the other squares it differs on can't be settled.
+        let mut unsettled = Bits::zeroed();
+        for square in open.iter_ones() {
+            if unsettled[square] {
+                continue;
+            }
+            let mut forced = Bits::zeroed();
+            forced.set(square, true);
+            let outcome = if layout[square] {
+                search.find(Bits::zeroed(), forced)
+            } else {
+                search.find(forced, Bits::zeroed())
             };
+
+            match outcome {
+                Outcome::Found(other) => unsettled |= (other ^ layout) & open,
+                Outcome::GaveUp => provable.complete = false,
+                Outcome::Impossible if layout[square] => provable.mines.set(square, true),
+                Outcome::Impossible => provable.safe.set(square, true),
+            }
+        }
+        provable
+    }
+
+    /// Reveals and flags the squares `provable` settles, as one step.  Its deductions are put down to
+    /// contradiction, as every layout that disagrees on them was ruled out.
+    pub fn apply_provable(&mut self, provable: &Provable) -> StepResult {
+        let mut deductions = Vec::new();
+        for (cells, mines, action) in [(provable.safe, 0, Action::Reveal), (provable.mines, provable.mines.count_ones(), Action::Flag)] {
+            if cells.any() {
+                self.stats.techniques.record(Technique::Contradiction);
+                deductions.push(Deduction { constraint: Constraint::exact(cells, mines, Technique::Contradiction), action, cells });
+            }
+        }
+        for square in provable.safe.iter_ones() {
+            self.reveal(square);
+        }
+        for square in provable.mines.iter_ones() {
+            self.flag(square);
+        }
+        self.stats.cells_forced += (provable.safe | provable.mines).count_ones();
+        self.update_estimates();
+        StepResult::Progress { revealed: provable.safe, flagged: provable.mines, deductions }
+    }
+
+    /// Ranks the squares a player could guess when nothing more can be deduced, best first.  Safer
+    /// squares come first, to the nearest percent, and among those the ones whose number would let
+    /// the most other squares be worked out.  Both are estimated from up to `MAX_GUESS_LAYOUTS` of the
+    /// layouts that fit what the player can see, treating each as equally likely and leaving out the
+    /// mine count and squares no clue covers.
+    pub fn rank_guesses(&self) -> Vec<GuessCandidate> {
+        let base = &self.puzzle.base;
+        let open = self.all_bits & !self.puzzle.revealed & !self.puzzle.flagged;
+        let constraints = self.visible_constraints(false);
+        let cells = constraints.iter().fold(Bits::zeroed(), |cells, c| cells | c.bits) & open;
+        let layouts = Exhaustive::new(&constraints, cells).enumerate(Bits::zeroed(), Bits::zeroed(), MAX_GUESS_LAYOUTS).layouts;
+        if layouts.is_empty() {
+            return Vec::new();
+        }
+
+        // Squares that differ between some layouts in a set are the ones that set leaves undecided
+        let undecided = |layouts: &mut dyn Iterator<Item = Bits>| {
+            let (all, any) = layouts.fold((cells, Bits::zeroed()), |(all, any), layout| (all & layout, any | layout));
+            any & !all
+        };
+        let undecided_now = undecided(&mut layouts.iter().copied());
+        let total = layouts.len() as f64;
+
+        let mut candidates = cells.iter_ones().map(|square| {
+            // When the square is safe, the player learns its number, which narrows down the layouts
+            let mut outcomes: HashMap<usize, Vec<Bits>> = HashMap::new();
+            for &layout in layouts.iter().filter(|layout| !layout[square]) {
+                let number = if base.unknowns[square] { 0 } else { (layout & base.neighbors[square]).count_ones() };
+                outcomes.entry(number).or_default().push(layout);
+            }
+
+            let safe = outcomes.values().map(|layouts| layouts.len()).sum::<usize>();
+            let information = outcomes.values().map(|layouts| {
+                let mut decided = undecided_now & !undecided(&mut layouts.iter().copied());
+                decided.set(square, false);
+                layouts.len() as f64 / total * decided.count_ones() as f64
+            }).sum();
+            GuessCandidate { square, safety: safe as f64 / total, information }
+        }).collect::<Vec<_>>();
+
+        candidates.sort_by(|a, b| {
+            let percent = |candidate: &GuessCandidate| (candidate.safety * 100.0).round() as i64;
+            percent(b).cmp(&percent(a)).then(b.information.partial_cmp(&a.information).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        candidates
+    }
+
+    /// What a player can see, as constraints on the squares that aren't revealed or flagged: the hints,
+    /// the numbers on revealed squares and optionally the mine count.  Whatever the solver has derived
+    /// so far holds too, and is added to save searches some work.
+    fn visible_constraints(&self, mine_count: bool) -> Vec<Constraint> {
+        let state = &self.puzzle;
+        let base = &state.base;
+        let open = self.all_bits & !state.revealed & !state.flagged;
+        let clue = |group: Bits, technique| {
+            let bits = group & open;
+            Constraint::exact(bits, base.displayed_count(group) - (group & state.flagged).count_ones(), technique)
+        };
+
+        let mut constraints = base.hints.iter().map(|&hint| clue(hint, Technique::Hint)).collect::<Vec<_>>();
+        for square in (state.revealed & !base.unknowns).iter_ones() {
+            constraints.push(clue(base.neighbors[square], Technique::NakedSingle));
         }
+        if mine_count {
+            constraints.push(clue(self.all_bits, Technique::MineCount));
+        }
+        constraints.extend(self.unsolved_values().copied());
+        constraints.extend(self.solved.keys().copied());
+        constraints
+    }
+
+    /// Every clue a finished board shows, and the squares that show numbers.
+    fn full_information(&self) -> (Vec<Constraint>, Bits) {
+        let base = &self.puzzle.base;
+        let numbered = self.all_bits & !base.mines & !base.unknowns;
+
+        let mut constraints = base.hints.iter().map(|&hint| Constraint::exact(hint, base.displayed_count(hint), Technique::Hint)).collect::<Vec<_>>();
+        for square in numbered.iter_ones() {
+            let neighbors = base.neighbors[square];
+            constraints.push(Constraint::exact(neighbors, base.displayed_count(neighbors), Technique::NakedSingle));
+        }
+        if self.config.known_mine_count {
+            constraints.push(Constraint::exact(self.all_bits, base.displayed_count(self.all_bits), Technique::MineCount));
+        }
+
+        (constraints, numbered)
+    }
+
+    /// Steps until the puzzle is finished or the solver gets stuck, returning the final result.
+    pub fn solve(&mut self) -> StepResult {
         loop {
-            if let Some(next) = self.processing_stack.iter_mut().flatten().find_map(|f| f.pop_back()) {
-                if !self.removed.remove(&next) {
-                    self.add_all_crosses(next);
-                    return StepResult::CrossConstraint(next);
+            if let result @ (StepResult::Finished | StepResult::Stuck | StepResult::UnexpectedStop(_) | StepResult::Contradiction(_)) = self.step() {
+                return result;
+            }
+        }
+    }
+
+    /// Like `solve`, but whenever the solver gets stuck it reveals a safe square as a player would
+    /// by guessing, and carries on.
+    pub fn solve_guessing(&mut self) -> StepResult {
+        loop {
+            match self.solve() {
+                StepResult::Stuck => {
+                    if self.guess().is_none() {
+                        return if self.remaining() == 0 { StepResult::Finished } else { StepResult::Stuck };
+                    }
                 }
+                result => return result,
+            }
+        }
+    }
+
+    /// Reveals an unresolved safe square, preferring ones next to what the solver already knows.
+    /// Returns the square, or `None` if there are no safe squares left.
+    pub fn guess(&mut self) -> Option<usize> {
+        let unresolved_safe = self.all_bits & !self.puzzle.revealed & !self.puzzle.flagged & !self.puzzle.base.mines;
+        let frontier = self.unsolved_values().fold(Bits::zeroed(), |frontier, c| frontier | c.bits);
+        let square = (unresolved_safe & frontier).first_one().or_else(|| unresolved_safe.first_one())?;
+        self.guess_square(square);
+        Some(square)
+    }
+
+    /// Guesses `square` as a player would, against the real layout: reveals it and returns `true`
+    /// if it's safe, or returns `false` and leaves the board alone if it's a mine.
+    pub fn guess_square(&mut self, square: usize) -> bool {
+        if self.puzzle.base.mines[square] {
+            return false;
+        }
+
+        self.reveal_square(square);
+        self.stats.guesses += 1;
+        self.stats.cells_guessed += 1;
+        true
+    }
+
+    fn remaining(&self) -> usize {
+        self.puzzle.base.size() - (self.puzzle.revealed | self.puzzle.flagged).count_ones()
+    }
+
+    fn apply_solved(&mut self) -> StepResult {
+        // Sorted so that partial steps, and the attribution of squares shared by several
+        // constraints, happen in a stable order
+        let mut solved = self.solved.keys().copied().collect::<Vec<_>>();
+        solved.sort_by_key(|c| (c.bits.first_one(), c.size, c.max_mines));
+
+        let granularity = self.config.granularity;
+        let chosen = match granularity {
+            StepGranularity::AllSolved => solved.into_iter().map(|c| (c, c.bits)).collect(),
+            StepGranularity::Constraint => vec![(solved[0], solved[0].bits)],
+            StepGranularity::Cell => {
+                let mut square = Bits::zeroed();
+                square.set(solved[0].bits.first_one().expect("Constraint of size 0 in solved!"), true);
+                vec![(solved[0], square)]
+            }
+        };
+
+        let mut to_reveal = Bits::zeroed();
+        let mut to_flag = Bits::zeroed();
+        let mut deductions = Vec::new();
+        for (constraint, bits) in chosen {
+            assert!(constraint.size > 0, "Constraint of size 0 in solved!");
+            let cells = bits & !to_reveal & !to_flag;
+            if cells.not_any() {
+                continue;
+            }
+
+            let action = if constraint.max_mines == 0 {
+                to_reveal |= cells;
+                Action::Reveal
             } else {
-                // This can happen if a previous constraint combination was ignored due to size
-                for constraint in self.puzzle.base.hints.clone() {
-                    self.add_constraint_from_mine_count(constraint);
-                }
+                to_flag |= cells;
+                Action::Flag
+            };
 
-                for neighborhood in self.puzzle.base.neighbors.clone(){
-                    self.add_constraint_from_mine_count(neighborhood);
-                }
+            // The rest of a partially applied constraint stays solved, so it is only counted once it is used up
+            if granularity != StepGranularity::Cell || constraint.size == 1 {
+                self.stats.techniques.record(constraint.technique);
+                self.stats.total_depth += constraint.depth;
+                self.stats.max_depth = self.stats.max_depth.max(constraint.depth);
+            }
+
+            deductions.push(Deduction { constraint, action, cells });
+        }
+
+        // A constraint that claims a mine is safe (or the reverse) disagrees with the puzzle's layout
+        let wrong = (to_reveal & self.puzzle.base.mines) | (to_flag & !self.puzzle.base.mines);
+        if wrong.any() {
+            let conflicting = deductions.iter().filter(|d| (d.cells & wrong).any()).map(|d| d.constraint).collect::<Vec<_>>();
+            self.contradict(&conflicting);
+            return StepResult::Contradiction(self.contradiction.clone());
+        }
+
+        assert!((to_flag & self.puzzle.revealed).not_any(), "Revealing existing squares! \nSquares:\n{}\nPuzzle:\n{}\nConstraints: \n{}", self.puzzle.base.draw(to_reveal), self.puzzle.to_string(), self.solved.keys().map(|c| c.to_string(self.puzzle.base.size())).collect::<Vec<String>>().join("\n"));
+        assert!((to_flag & self.puzzle.flagged).not_any(), "Flagging existing flags! \nFlags:\n{}\nExisting:\n{}\nConstraints: {}", self.puzzle.base.draw(to_flag), self.puzzle.to_string(), self.solved.keys().map(|c| c.to_string(self.puzzle.base.size())).collect::<Vec<String>>().join("\n"));
+
+        for square in to_reveal.iter_ones() {
+            //println!("Revealing squares: {}", to_reveal);
+            self.reveal_square(square);
+        }
+        if granularity == StepGranularity::AllSolved {
+            to_reveal |= self.open_zeros(to_reveal, &mut deductions);
+        }
+        
+        for square in to_flag.iter_ones() {
+            //println!("Flagging squares: {}", to_flag);
+            self.flag_square(square);
+            if !self.contradiction.is_empty() {
+                break;
             }
         }
+        if !self.contradiction.is_empty() {
+            return StepResult::Contradiction(self.contradiction.clone());
+        }
+
+        self.stats.cells_forced += (to_reveal | to_flag).count_ones();
+        if self.remaining() == 0 {
+            return StepResult::Finished;
+        }
+
+        StepResult::Progress {
+            revealed: to_reveal,
+            flagged: to_flag,
+            deductions,
+        }
     }
 }
 
+/// How a solve limited to one inference tier splits the board between deductions and guesses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GuessAccounting {
+    pub tier: InferenceTier,
+    pub forced: usize,
+    pub guessed: usize,
+    /// How the solve ended.  Only `Finished` means every square was resolved.
+    pub finished: bool,
+}
+
+/// Solves the puzzle once per inference tier, guessing whenever that tier gets stuck, to show how
+/// much of it can be done without guessing at each level of reasoning.
+pub fn account_guesses(puzzle: &Puzzle, config: SolverConfig) -> Vec<GuessAccounting> {
+    InferenceTier::ALL.iter().map(|&tier| {
+        let mut solver = Solver::new(puzzle.clone(), SolverConfig { tier, ..config });
+        let finished = matches!(solver.solve_guessing(), StepResult::Finished);
+        GuessAccounting {
+            tier,
+            forced: solver.stats.cells_forced,
+            guessed: solver.stats.cells_guessed,
+            finished,
+        }
+    }).collect()
+}
+
+/// A square a player could guess, from `Solver::rank_guesses`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GuessCandidate {
+    pub square: usize,
+    /// The chance the square is safe.
+    pub safety: f64,
+    /// How many other squares its number is expected to settle, counting nothing when it's a mine.
+    pub information: f64,
+}
+
+/// Squares that can be proven safe or mined from what a player can see.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Provable {
+    pub safe: Bits,
+    pub mines: Bits,
+    /// Whether every other square is known to be undecided, rather than some searches giving up.
+    pub complete: bool,
+}
+
+/// Other mine layouts that fit a puzzle's clues.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Alternatives {
+    /// Squares that are mines in some layouts and safe in others.
+    pub ambiguous: Bits,
+    /// The mines of each alternative, restricted to `ambiguous`.
+    pub layouts: Vec<Bits>,
+    /// Whether these are all the alternatives, rather than a sample.
+    pub complete: bool,
+}
+
+/// Whether a puzzle's mine layout is the only one its clues allow.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Uniqueness {
+    Unique,
+    /// Another layout fits every clue.  Holds its mines.
+    Ambiguous(Bits),
+    /// The search gave up before reaching an answer.
+    Undetermined,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Reveal,
+    Flag,
+}
+
+/// Squares resolved by a single solved constraint.
+#[derive(Clone, Copy, Debug)]
+pub struct Deduction {
+    pub constraint: Constraint,
+    pub action: Action,
+    /// The squares this constraint resolved, excluding any already credited to an earlier deduction in the same step.
+    pub cells: Bits,
+}
+
+#[derive(Clone)]
 pub enum StepResult {
-    Progress{revealed: Bits, flagged: Bits},
+    /// `revealed` and `flagged` are the union of the cells of all `deductions`.
+    Progress{revealed: Bits, flagged: Bits, deductions: Vec<Deduction>},
     CrossConstraint(Constraint),
     CliqueConstraint(Constraint),
+    /// Nothing more can be deduced from what is known.
+    Stuck,
     UnexpectedStop(String),
+    /// The listed constraints can't all be satisfied, so the puzzle is inconsistent.
+    Contradiction(Vec<Constraint>),
     Finished,
 }
 
-fn get_neighbor_constraint(puzzle: &PuzzleState, square_index: usize) -> Constraint {
-    let unknown_neighbors = puzzle.base.neighbors[square_index] & !puzzle.revealed & !puzzle.flagged;
-    let remaining_mines =  (unknown_neighbors & puzzle.base.mines).count_ones();
+/// How a solve ended.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum SolveOutcome {
+    Solved,
+    Stuck,
+    Stopped(String),
+    /// Holds the number of constraints involved.
+    Contradiction(usize),
+}
 
-    Constraint {
-        bits: unknown_neighbors,
-        max_mines: remaining_mines,
-        min_mines: remaining_mines,
-        size: unknown_neighbors.count_ones(),
+impl SolveOutcome {
+    /// The outcome a step represents, if the solve ended there.
+    pub fn from_step(result: &StepResult) -> Option<SolveOutcome> {
+        match result {
+            StepResult::Finished => Some(SolveOutcome::Solved),
+            StepResult::Stuck => Some(SolveOutcome::Stuck),
+            StepResult::UnexpectedStop(why) => Some(SolveOutcome::Stopped(why.clone())),
+            StepResult::Contradiction(constraints) => Some(SolveOutcome::Contradiction(constraints.len())),
+            _ => None,
+        }
     }
 }
 
-fn cross_constraints(left: Constraint, right: Constraint) -> Vec<Constraint> {    
-    let mut constraints = Vec::new();
+impl fmt::Display for SolveOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveOutcome::Solved => write!(f, "solved"),
+            SolveOutcome::Stuck => write!(f, "stuck"),
+            SolveOutcome::Stopped(why) => write!(f, "stopped: {}", why),
+            SolveOutcome::Contradiction(count) => write!(f, "contradiction between {} constraints", count),
+        }
+    }
+}
+
+/// Whether crossing two constraints can produce anything tighter than "anywhere from none to all".
+/// Uses the same bounds as `cross_constraints`, without building any of the constraints.
+fn can_tighten(left: Constraint, right: Constraint) -> bool {
+    let intersection_count = (left.bits & right.bits).count_ones();
+    let intersection_min = (left.min_mines + intersection_count).saturating_sub(left.size).max((right.min_mines + intersection_count).saturating_sub(right.size));
+    let intersection_max = intersection_count.min(left.max_mines).min(right.max_mines);
+
+    if intersection_min > 0 || intersection_max < intersection_count {
+        return true;
+    }
+
+    let left_rest = left.size - intersection_count;
+    let right_rest = right.size - intersection_count;
+    left.min_mines > intersection_max || left.max_mines < left_rest + intersection_min
+        || right.min_mines > intersection_max || right.max_mines < right_rest + intersection_min
+}
+
+/// The most mine counts `cross_three` tries over the regions of three constraints.
+const MAX_TRIPLE_COUNTS: usize = 4_096;
+
+/// Bounds on the mines in each region of three overlapping constraints, found by trying every
+/// count of mines per region.  `None` if no counts satisfy all three.  Regions that can't be
+/// tightened, or with too many counts to try, are left out.
+fn cross_three(constraints: [Constraint; 3]) -> Option<SmallVec<[Constraint; 7]>> {
+    // Region `m` holds the squares in exactly the constraints whose bits are set in `m`
+    let mut regions = [Bits::zeroed(); 8];
+    for (m, region) in regions.iter_mut().enumerate().skip(1) {
+        let mut bits = !Bits::zeroed();
+        for (i, constraint) in constraints.iter().enumerate() {
+            bits &= if m & 1 << i != 0 { constraint.bits } else { !constraint.bits };
+        }
+        *region = bits;
+    }
+    let sizes = regions.map(|region| region.count_ones());
+    if sizes.iter().skip(1).try_fold(1usize, |total, &size| total.checked_mul(size + 1).filter(|&t| t <= MAX_TRIPLE_COUNTS)).is_none() {
+        return Some(SmallVec::new());
+    }
+
+    let mut bounds = [(usize::MAX, 0); 8];
+    let mut counts = [0; 8];
+    loop {
+        let fits = constraints.iter().enumerate().all(|(i, constraint)| {
+            let mines = (1..8).filter(|m| m & 1 << i != 0).map(|m| counts[m]).sum::<usize>();
+            constraint.min_mines <= mines && mines <= constraint.max_mines
+        });
+        if fits {
+            for m in 1..8 {
+                bounds[m] = (bounds[m].0.min(counts[m]), bounds[m].1.max(counts[m]));
+            }
+        }
+
+        // Next combination of counts, like an odometer
+        let mut m = 1;
+        while m < 8 && counts[m] == sizes[m] {
+            counts[m] = 0;
+            m += 1;
+        }
+        if m == 8 {
+            break;
+        }
+        counts[m] += 1;
+    }
+
+    if bounds[1].0 == usize::MAX {
+        return None;
+    }
+    let depth = constraints.iter().map(|c| c.depth).max().unwrap_or(0) + 1;
+    Some((1..8).filter(|&m| sizes[m] > 0 && (bounds[m].0 > 0 || bounds[m].1 < sizes[m])).map(|m| Constraint {
+        bits: regions[m],
+        min_mines: bounds[m].0,
+        max_mines: bounds[m].1,
+        size: sizes[m],
+        technique: Technique::Cross,
+        depth,
+    }).collect())
+}
+
+/// The intersection of two constraints and what's left of each, so never more than three.
+fn cross_constraints(left: Constraint, right: Constraint) -> SmallVec<[Constraint; 3]> {
+    let mut constraints = SmallVec::new();
+
+    let depth = left.depth.max(right.depth) + 1;
+    let technique = if (left.bits & !right.bits).not_any() || (right.bits & !left.bits).not_any() {
+        Technique::Subset
+    } else {
+        Technique::Cross
+    };
 
     let intersection = left.bits & right.bits;
     let intersection_count = intersection.count_ones();
@@ -393,6 +1754,8 @@ fn cross_constraints(left: Constraint, right: Constraint) -> Vec<Constraint> {
         min_mines: intersection_min,
         max_mines: intersection_max,
         size: intersection.count_ones(),
+        technique,
+        depth,
     });
     
 
@@ -405,6 +1768,8 @@ fn cross_constraints(left: Constraint, right: Constraint) -> Vec<Constraint> {
             min_mines: left_overlap_min,
             max_mines: left_overlap_max,
             size: left_overlap.count_ones(),
+            technique,
+            depth,
         })
     }
     
@@ -418,6 +1783,8 @@ fn cross_constraints(left: Constraint, right: Constraint) -> Vec<Constraint> {
             min_mines: right_overlap_min,
             max_mines: right_overlap_max,
             size: right_overlap.count_ones(),
+            technique,
+            depth,
         })
     }
 
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::core::{Bits, Puzzle};
+
+/// The most symmetries kept for a puzzle.  Boards with more are treated as having none, since
+/// checking every one of them would cost more than the duplicate reasoning it saves.
+const MAX_SYMMETRIES: usize = 48;
+
+/// How many partial mappings the search tries before giving up.
+const BUDGET: usize = 100_000;
+
+/// A permutation of a puzzle's squares.
+pub type Permutation = Vec<usize>;
+
+/// Every symmetry of the puzzle other than the identity: permutations of its squares that keep
+/// neighborhoods, hints, mines, unknowns and the starting reveals and flags the same.  A solver working from a
+/// state those permutations also keep can map any deduction onto its mirror images.
+///
+/// Empty if there are none, or too many to be worth using.
+pub fn automorphisms(puzzle: &Puzzle) -> Vec<Permutation> {
+    let size = puzzle.size();
+    let mut incoming = vec![Bits::zeroed(); size];
+    for (square, neighbors) in puzzle.neighbors.iter().enumerate() {
+        for neighbor in neighbors.iter_ones() {
+            incoming[neighbor].set(square, true);
+        }
+    }
+
+    let colors = refine(puzzle, &incoming);
+    let mut classes = HashMap::new();
+    for &color in &colors {
+        *classes.entry(color).or_insert(0) += 1;
+    }
+    if classes.len() == size {
+        return Vec::new();
+    }
+
+    // Each square after the first in its component is mapped next to an already mapped neighbor
+    let mut order = Vec::with_capacity(size);
+    let mut parent = vec![None; size];
+    let mut seen = Bits::zeroed();
+    let mut roots = (0..size).collect::<Vec<_>>();
+    roots.sort_by_key(|&square| classes[&colors[square]]);
+    for root in roots {
+        if seen[root] {
+            continue;
+        }
+        seen.set(root, true);
+        let mut next = order.len();
+        order.push(root);
+        while next < order.len() {
+            let square = order[next];
+            next += 1;
+            for neighbor in (puzzle.neighbors[square] | incoming[square]).iter_ones() {
+                if !seen[neighbor] {
+                    seen.set(neighbor, true);
+                    parent[neighbor] = Some(square);
+                    order.push(neighbor);
+                }
+            }
+        }
+    }
+
+    let hints = puzzle.hints.iter().copied().collect::<HashSet<_>>();
+    let search = Search { puzzle, incoming: &incoming, colors: &colors, order: &order, parent: &parent, hints: &hints };
+    let mut found = Vec::new();
+    let mut image = vec![usize::MAX; size];
+    let mut used = Bits::zeroed();
+    let mut budget = BUDGET;
+    if !search.extend(0, &mut image, &mut used, &mut found, &mut budget) {
+        return Vec::new();
+    }
+
+    found.retain(|permutation: &Permutation| permutation.iter().enumerate().any(|(square, &target)| square != target));
+    found
+}
+
+/// Maps a set of squares through a permutation.
+pub fn map(permutation: &[usize], bits: Bits) -> Bits {
+    let mut image = Bits::zeroed();
+    for square in bits.iter_ones() {
+        image.set(permutation[square], true);
+    }
+    image
+}
+
+/// Splits squares into classes no symmetry can mix, by what they are and, repeatedly, by the
+/// classes of their neighbors.
+fn refine(puzzle: &Puzzle, incoming: &[Bits]) -> Vec<usize> {
+    let size = puzzle.size();
+    let mut hint_counts = vec![0; size];
+    for hint in &puzzle.hints {
+        for square in hint.iter_ones() {
+            hint_counts[square] += 1;
+        }
+    }
+
+    let mut colors = renumber((0..size).map(|square| {
+        vec![puzzle.mines[square] as usize, puzzle.unknowns[square] as usize, puzzle.revealed[square] as usize, puzzle.flagged[square] as usize, puzzle.neighbors[square].count_ones(), incoming[square].count_ones(), hint_counts[square]]
+    }).collect());
+
+    loop {
+        let signatures = (0..size).map(|square| {
+            let mut out = puzzle.neighbors[square].iter_ones().map(|n| colors[n]).collect::<Vec<_>>();
+            let mut into = incoming[square].iter_ones().map(|n| colors[n]).collect::<Vec<_>>();
+            out.sort_unstable();
+            into.sort_unstable();
+            let mut signature = vec![colors[square], usize::MAX];
+            signature.extend(out);
+            signature.push(usize::MAX);
+            signature.extend(into);
+            signature
+        }).collect();
+        let refined = renumber(signatures);
+
+        let classes = |colors: &[usize]| colors.iter().collect::<HashSet<_>>().len();
+        if classes(&refined) == classes(&colors) {
+            return refined;
+        }
+        colors = refined;
+    }
+}
+
+/// Numbers distinct signatures in order of first appearance.
+fn renumber(signatures: Vec<Vec<usize>>) -> Vec<usize> {
+    let mut numbers = HashMap::new();
+    signatures.into_iter().map(|signature| {
+        let next = numbers.len();
+        *numbers.entry(signature).or_insert(next)
+    }).collect()
+}
+
+struct Search<'a> {
+    puzzle: &'a Puzzle,
+    incoming: &'a [Bits],
+    colors: &'a [usize],
+    order: &'a [usize],
+    parent: &'a [Option<usize>],
+    hints: &'a HashSet<Bits>,
+}
+
+impl Search<'_> {
+    /// Tries every image for the `depth`th square in order, recording each complete symmetry.
+    /// Returns false if the search ran out of budget or found too many.
+    fn extend(&self, depth: usize, image: &mut Vec<usize>, used: &mut Bits, found: &mut Vec<Permutation>, budget: &mut usize) -> bool {
+        if depth == self.order.len() {
+            if self.puzzle.hints.iter().all(|&hint| self.hints.contains(&map(image, hint))) {
+                found.push(image.clone());
+            }
+            return found.len() <= MAX_SYMMETRIES + 1;
+        }
+
+        let square = self.order[depth];
+        let candidates = match self.parent[square] {
+            Some(parent) => self.puzzle.neighbors[image[parent]] | self.incoming[image[parent]],
+            None => !Bits::zeroed(),
+        };
+        for target in candidates.iter_ones().take_while(|&target| target < self.puzzle.size()) {
+            if used[target] || self.colors[target] != self.colors[square] || !self.fits(square, target, image, *used) {
+                continue;
+            }
+            if *budget == 0 {
+                return false;
+            }
+            *budget -= 1;
+
+            image[square] = target;
+            used.set(target, true);
+            let completed = self.extend(depth + 1, image, used, found, budget);
+            used.set(target, false);
+            image[square] = usize::MAX;
+            if !completed {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether mapping `square` to `target` keeps every edge to the squares mapped so far, whose
+    /// images are `used`.
+    fn fits(&self, square: usize, target: usize, image: &[usize], used: Bits) -> bool {
+        let mapped = |bits: Bits| bits.iter_ones().filter(|&n| image[n] != usize::MAX).collect::<Vec<_>>();
+        let out = mapped(self.puzzle.neighbors[square]);
+        let into = mapped(self.incoming[square]);
+
+        out.iter().all(|&n| self.puzzle.neighbors[target][image[n]])
+            && into.iter().all(|&n| self.incoming[target][image[n]])
+            && (self.puzzle.neighbors[target] & used).count_ones() == out.len()
+            && (self.incoming[target] & used).count_ones() == into.len()
+    }
+}
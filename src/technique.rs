@@ -0,0 +1,94 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The kind of reasoning that produced a constraint.  Every deduction the solver makes
+/// (a constraint that ends up revealing or flagging squares) is tagged with one of these.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum Technique {
+    /// A revealed square's number resolves its neighborhood on its own.
+    NakedSingle,
+    /// A hint (column or color) resolves its squares on its own.
+    Hint,
+    /// One constraint is contained in another, so their difference is known.
+    Subset,
+    /// Two partially overlapping constraints bound their intersection (1-2-1 style).
+    Cross,
+    /// The remaining mine count of the whole board.
+    MineCount,
+    /// Ruling out a possibility because it leads to an inconsistent board, by searching the layouts
+    /// of the open squares.
+    Contradiction,
+}
+
+impl Technique {
+    pub const ALL: [Technique; 6] = [
+        Technique::NakedSingle,
+        Technique::Hint,
+        Technique::Subset,
+        Technique::Cross,
+        Technique::MineCount,
+        Technique::Contradiction,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Technique::NakedSingle => "naked single",
+            Technique::Hint => "hint",
+            Technique::Subset => "subset",
+            Technique::Cross => "cross",
+            Technique::MineCount => "mine counting",
+            Technique::Contradiction => "contradiction",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Technique> {
+        Technique::ALL.iter().copied().find(|technique| technique.name() == name)
+    }
+
+    fn index(self) -> usize {
+        Technique::ALL.iter().position(|&t| t == self).unwrap()
+    }
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Number of deductions made with each technique over a solve.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct TechniqueCounts {
+    counts: [usize; 6],
+}
+
+impl TechniqueCounts {
+    pub fn record(&mut self, technique: Technique) {
+        self.counts[technique.index()] += 1;
+    }
+
+    pub fn get(&self, technique: Technique) -> usize {
+        self.counts[technique.index()]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// The techniques used at least once, in taxonomy order.
+    pub fn used(&self) -> impl Iterator<Item = (Technique, usize)> + '_ {
+        Technique::ALL.iter().map(move |&t| (t, self.get(t))).filter(|&(_, count)| count > 0)
+    }
+}
+
+impl fmt::Display for TechniqueCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = self.used().map(|(t, count)| format!("{}: {}", t, count)).collect::<Vec<_>>();
+        if parts.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{core::{Bits, Puzzle}, solver::{PuzzleState, SolveOutcome, Solver, SolverConfig, StepResult}};
+
+/// The squares a solve resolved, step by step, saved so later versions of the solver can be
+/// checked against it.  Only steps that resolve squares count, so a change in how many crossings
+/// the solver tries between them doesn't show up as a difference.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Trace {
+    pub title: String,
+    /// The solver settings it was recorded with.
+    pub settings: String,
+    pub outcome: SolveOutcome,
+    pub steps: Vec<Vec<Resolved>>,
+}
+
+/// A square resolved by a step.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Resolved {
+    pub square: String,
+    pub flagged: bool,
+    /// The technique of the deduction that resolved it, unless the step didn't say, as the one that
+    /// finishes the puzzle doesn't.
+    pub technique: Option<String>,
+}
+
+impl Trace {
+    /// Solves the puzzle from the start, recording every square each step resolves.
+    pub fn record(title: &str, puzzle: &Puzzle, config: SolverConfig) -> Trace {
+        let mut solver = Solver::new(puzzle.clone(), config);
+        let mut steps = Vec::new();
+        loop {
+            let before = solver.puzzle.revealed | solver.puzzle.flagged;
+            let result = solver.step();
+            let resolved = (solver.puzzle.revealed | solver.puzzle.flagged) & !before;
+            if resolved.any() {
+                steps.push(resolved_by(&solver.puzzle, resolved, &result));
+            }
+            if let Some(outcome) = SolveOutcome::from_step(&result) {
+                return Trace { title: title.to_string(), settings: format!("{:?}", config), outcome, steps };
+            }
+        }
+    }
+
+    /// The trace of steps already taken from `start`, each with the board after it.  If the last
+    /// didn't end the solve, it's recorded as stopped there.
+    pub fn from_steps<'a>(title: &str, start: &PuzzleState, config: SolverConfig, taken: impl IntoIterator<Item = (&'a PuzzleState, &'a StepResult)>) -> Trace {
+        let mut before = start.revealed | start.flagged;
+        let mut steps = Vec::new();
+        let mut outcome = None;
+        for (state, result) in taken {
+            let now = state.revealed | state.flagged;
+            if (now & !before).any() {
+                steps.push(resolved_by(state, now & !before, result));
+            }
+            before = now;
+            outcome = SolveOutcome::from_step(result);
+        }
+        let outcome = outcome.unwrap_or_else(|| SolveOutcome::Stopped(String::from("saved partway through")));
+        Trace { title: title.to_string(), settings: format!("{:?}", config), outcome, steps }
+    }
+
+    /// How `actual` differs from this trace: squares it resolves that this doesn't or the other way
+    /// around, squares resolved the other way, at a different step or by a different technique, and a
+    /// different outcome.  Empty if they match.
+    pub fn compare(&self, actual: &Trace) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.settings != actual.settings {
+            differences.push(String::from("recorded with different solver settings"));
+        }
+        if self.outcome != actual.outcome {
+            differences.push(format!("{} instead of {}", actual.outcome, self.outcome));
+        }
+
+        let expected = by_square(self);
+        let found = by_square(actual);
+        let describe = |flagged: bool| if flagged { "flagged" } else { "revealed" };
+        for (square, &(step, resolved)) in &expected {
+            match found.get(square) {
+                None => differences.push(format!("missing: {} {} at step {}", square, describe(resolved.flagged), step + 1)),
+                Some(&(_, now)) if now.flagged != resolved.flagged => differences.push(format!("{} {} instead of {}", square, describe(now.flagged), describe(resolved.flagged))),
+                Some(&(now_step, now)) => {
+                    if now_step != step {
+                        differences.push(format!("{} resolved at step {} instead of {}", square, now_step + 1, step + 1));
+                    }
+                    if now.technique != resolved.technique {
+                        let name = |technique: &Option<String>| technique.clone().unwrap_or_else(|| String::from("an unnamed step"));
+                        differences.push(format!("{} resolved by {} instead of {}", square, name(&now.technique), name(&resolved.technique)));
+                    }
+                }
+            }
+        }
+        for (square, &(step, resolved)) in &found {
+            if !expected.contains_key(square) {
+                differences.push(format!("extra: {} {} at step {}", square, describe(resolved.flagged), step + 1));
+            }
+        }
+        differences
+    }
+}
+
+/// How two traces line up, step by step.
+pub struct TraceDiff {
+    /// The first step, counting from 0, that resolves different squares in each, or the end of the
+    /// shorter trace if it stops first.  `None` if they resolve the same squares at every step.
+    pub first_divergence: Option<usize>,
+    /// Squares resolved one way by one technique in the first trace but not the second.
+    pub only_first: Vec<Resolved>,
+    pub only_second: Vec<Resolved>,
+    /// How many more steps the second trace takes than the first.
+    pub step_delta: isize,
+}
+
+impl Trace {
+    /// Lines this trace up against `other`, step for step.
+    pub fn diff(&self, other: &Trace) -> TraceDiff {
+        let first_divergence = (0..self.steps.len().max(other.steps.len())).find(|&i| {
+            match (self.steps.get(i), other.steps.get(i)) {
+                (Some(a), Some(b)) => squares(a) != squares(b),
+                _ => true,
+            }
+        });
+
+        let all = |trace: &Trace| trace.steps.iter().flatten().cloned().collect::<Vec<_>>();
+        let (first, second) = (all(self), all(other));
+        TraceDiff {
+            first_divergence,
+            only_first: first.iter().filter(|r| !second.contains(r)).cloned().collect(),
+            only_second: second.iter().filter(|r| !first.contains(r)).cloned().collect(),
+            step_delta: other.steps.len() as isize - self.steps.len() as isize,
+        }
+    }
+}
+
+impl Resolved {
+    /// The square, which way it went, and how.
+    pub fn describe(&self) -> String {
+        let how = if self.flagged { "flagged" } else { "revealed" };
+        match &self.technique {
+            Some(technique) => format!("{} {} by {}", self.square, how, technique),
+            None => format!("{} {}", self.square, how),
+        }
+    }
+}
+
+/// The squares of `resolved`, with the techniques `result` gives them.
+fn resolved_by(state: &PuzzleState, resolved: Bits, result: &StepResult) -> Vec<Resolved> {
+    let base = &state.base;
+    resolved.iter_ones().map(|square| {
+        let technique = match result {
+            StepResult::Progress { deductions, .. } => deductions.iter().find(|d| d.cells[square]).map(|d| d.constraint.technique.to_string()),
+            _ => None,
+        };
+        Resolved { square: base.id_labels[square].clone(), flagged: state.flagged[square], technique }
+    }).collect()
+}
+
+/// The squares a step resolves and which way, in order.
+fn squares(step: &[Resolved]) -> Vec<(&str, bool)> {
+    let mut squares = step.iter().map(|r| (r.square.as_str(), r.flagged)).collect::<Vec<_>>();
+    squares.sort_unstable();
+    squares
+}
+
+/// Each square in a trace, with the step that resolved it.
+fn by_square(trace: &Trace) -> BTreeMap<&str, (usize, &Resolved)> {
+    trace.steps.iter().enumerate().flat_map(|(step, resolved)| resolved.iter().map(move |r| (r.square.as_str(), (step, r)))).collect()
+}
+
@@ -0,0 +1,226 @@
+use std::{fs, io::Write, path::{Path, PathBuf}, process::Command};
+
+use eframe::egui::{Color32, FontDefinitions, TextStyle, epaint::text::Fonts};
+use flate2::{Compression, write::ZlibEncoder};
+
+use crate::{app::highlighted_squares, core::PuzzleGui, export::Theme, solver::{PuzzleState, StepResult}};
+
+/// A picture of the board, three bytes per pixel.
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// The frame as a binary PPM image, which ffmpeg and most image tools read.
+    pub fn ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend_from_slice(&self.pixels);
+        bytes
+    }
+
+    /// The frame as a PNG image, for pages that can't show PPM.
+    pub fn png(&self) -> Vec<u8> {
+        // Each row starts with its filter type, and 0 leaves it as it is
+        let mut rows = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks_exact(self.width * 3) {
+            rows.push(0);
+            rows.extend_from_slice(row);
+        }
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rows).expect("Writing to memory can't fail");
+        let data = encoder.finish().expect("Writing to memory can't fail");
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits a channel, RGB, then the only compression, filtering and interlacing there are
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, contents) in [(b"IHDR", &header), (b"IDAT", &data), (b"IEND", &Vec::new())] {
+            bytes.extend_from_slice(&(contents.len() as u32).to_be_bytes());
+            let start = bytes.len();
+            bytes.extend_from_slice(kind);
+            bytes.extend_from_slice(contents);
+            let crc = crc32fast::hash(&bytes[start..]);
+            bytes.extend_from_slice(&crc.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Blends `color` over the pixel at `x`, `y`, by `coverage` from 0 to 1.
+    fn blend(&mut self, x: usize, y: usize, color: Color32, coverage: f32) {
+        let pixel = &mut self.pixels[(y * self.width + x) * 3..][..3];
+        for (channel, value) in pixel.iter_mut().zip([color.r(), color.g(), color.b()]) {
+            *channel = (*channel as f32 * (1.0 - coverage) + value as f32 * coverage).round() as u8;
+        }
+    }
+
+    /// Fills a polygon by the even-odd rule, testing the center of each pixel.
+    fn fill(&mut self, points: &[(f32, f32)], color: Color32) {
+        let top = points.iter().map(|p| p.1).fold(f32::MAX, f32::min).max(0.0) as usize;
+        let bottom = (points.iter().map(|p| p.1).fold(f32::MIN, f32::max).ceil().max(0.0) as usize).min(self.height);
+        for y in top..bottom {
+            let center = y as f32 + 0.5;
+            let mut crossings = points.iter().zip(points.iter().cycle().skip(1)).filter(|(a, b)| (a.1 <= center) != (b.1 <= center)).map(|(a, b)| {
+                a.0 + (center - a.1) / (b.1 - a.1) * (b.0 - a.0)
+            }).collect::<Vec<_>>();
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0] - 0.5).ceil().max(0.0) as usize;
+                let end = ((span[1] - 0.5).floor() + 1.0).clamp(0.0, self.width as f32) as usize;
+                for x in start..end {
+                    self.blend(x, y, color, 1.0);
+                }
+            }
+        }
+    }
+}
+
+/// Draws boards for one puzzle at one resolution in a theme, as the HTML walkthrough does: squares
+/// in their resolved colors, dimmed unless the step is about them, with outlines and numbers.
+pub struct Renderer {
+    width: usize,
+    height: usize,
+    theme: Theme,
+    /// Pixels per puzzle unit, and where the puzzle's origin lands.
+    scale: f32,
+    origin: (f32, f32),
+    fonts: Fonts,
+}
+
+impl Renderer {
+    pub fn new(gui: &PuzzleGui, width: usize, height: usize, theme: Theme) -> Renderer {
+        let corners = gui.squares.iter().flat_map(|object| object.points.iter().map(move |a| (object.x + a.0, object.y + a.1)));
+        let (min_x, min_y, max_x, max_y) = corners.fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+        });
+        // A margin of a twentieth of the frame on every side, with the board centered in the rest
+        let margin = width.min(height) as f32 / 20.0;
+        let scale = ((width as f32 - margin * 2.0) / (max_x - min_x).max(0.001)).min((height as f32 - margin * 2.0) / (max_y - min_y).max(0.001));
+        let origin = (
+            (width as f32 - (max_x - min_x) * scale) / 2.0 - min_x * scale,
+            (height as f32 - (max_y - min_y) * scale) / 2.0 - min_y * scale,
+        );
+
+        // Numbers are as tall as in the walkthrough
+        let (_, square_height) = square_size(gui);
+        let definitions = FontDefinitions::default();
+        let points = definitions.family_and_size[&TextStyle::Body].1;
+        let pixels_per_point = (square_height * scale * theme.text_size() / points).clamp(0.1, 99.0);
+        Renderer { width, height, theme, scale, origin, fonts: Fonts::from_definitions(pixels_per_point, definitions) }
+    }
+
+    pub fn frame(&self, gui: &PuzzleGui, state: &PuzzleState, result: Option<&StepResult>) -> Frame {
+        let background = self.theme.background();
+        let mut frame = Frame { width: self.width, height: self.height, pixels: [background.r(), background.g(), background.b()].repeat(self.width * self.height) };
+        let highlighted = result.map(highlighted_squares);
+        let (outline_color, outline_width) = self.theme.outline();
+        let outline = (self.scale * square_size(gui).0 * outline_width).max(1.0);
+        for (i, object) in gui.squares.iter().enumerate() {
+            let (color, text, text_color) = self.theme.appearance(state, i);
+            let color = if highlighted.is_some_and(|highlighted| highlighted[i]) { color } else { mix(color, background) };
+            let corners = object.points.iter().map(|a| self.to_pixels(object.x + a.0, object.y + a.1)).collect::<Vec<_>>();
+            let center = self.to_pixels(object.x, object.y);
+            frame.fill(&corners, outline_color);
+            let inset = corners.iter().map(|&(x, y)| {
+                let (dx, dy) = (x - center.0, y - center.1);
+                let shrink = 1.0 - outline / (dx * dx + dy * dy).sqrt().max(outline * 2.0);
+                (center.0 + dx * shrink, center.1 + dy * shrink)
+            }).collect::<Vec<_>>();
+            frame.fill(&inset, color);
+            if !text.is_empty() {
+                self.text(&mut frame, center, text, text_color);
+            }
+        }
+        frame
+    }
+
+    fn to_pixels(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.origin.0 + x * self.scale, self.origin.1 + y * self.scale)
+    }
+
+    /// Draws text centered on `center`, copying glyphs from the font atlas.
+    fn text(&self, frame: &mut Frame, center: (f32, f32), text: String, color: Color32) {
+        let galley = self.fonts.layout_no_wrap(TextStyle::Body, text);
+        let texture = self.fonts.texture();
+        let pixels_per_point = self.fonts.pixels_per_point();
+        let left = center.0 - galley.size.x * pixels_per_point / 2.0;
+        let top = center.1 - galley.size.y * pixels_per_point / 2.0;
+        for row in &galley.rows {
+            for (x_offset, glyph) in row.x_offsets.iter().zip(&row.uv_rects) {
+                let glyph = match glyph {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let x0 = (left + (x_offset + glyph.offset.x) * pixels_per_point).round() as isize;
+                let y0 = (top + (row.y_min + glyph.offset.y) * pixels_per_point).round() as isize;
+                for v in glyph.min.1..glyph.max.1 {
+                    for u in glyph.min.0..glyph.max.0 {
+                        let (x, y) = (x0 + (u - glyph.min.0) as isize, y0 + (v - glyph.min.1) as isize);
+                        if x < 0 || y < 0 || x as usize >= frame.width || y as usize >= frame.height {
+                            continue;
+                        }
+                        let alpha = texture.pixels[v as usize * texture.width + u as usize];
+                        if alpha > 0 {
+                            frame.blend(x as usize, y as usize, color, alpha as f32 / 255.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Halfway between a square's color and the background, as the GUI dims squares a step isn't about.
+fn mix(color: Color32, background: Color32) -> Color32 {
+    let half = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+    Color32::from_rgb(half(color.r(), background.r()), half(color.g(), background.g()), half(color.b(), background.b()))
+}
+
+/// The width and height of the first square, in puzzle units.
+fn square_size(gui: &PuzzleGui) -> (f32, f32) {
+    gui.squares.first().map_or((1.0, 1.0), |object| {
+        let span = |axis: fn(&(f32, f32)) -> f32| object.points.iter().map(axis).fold(f32::MIN, f32::max) - object.points.iter().map(axis).fold(f32::MAX, f32::min);
+        (span(|p| p.0), span(|p| p.1))
+    })
+}
+
+/// Writes a frame for the starting board and one for every step to `folder`, numbered in order.
+/// Returns how many were written.
+pub fn write_frames(folder: &Path, gui: &PuzzleGui, start: &PuzzleState, steps: &[(PuzzleState, StepResult)], width: usize, height: usize, theme: Theme) -> Result<usize, String> {
+    fs::create_dir_all(folder).map_err(|e| format!("Unable to create {}: {}", folder.display(), e))?;
+    let renderer = Renderer::new(gui, width, height, theme);
+    let frames = std::iter::once(renderer.frame(gui, start, None)).chain(steps.iter().map(|(state, result)| renderer.frame(gui, state, Some(result))));
+    let mut count = 0;
+    for (number, frame) in frames.enumerate() {
+        let path = frame_path(folder, number);
+        fs::write(&path, frame.ppm()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn frame_path(folder: &Path, number: usize) -> PathBuf {
+    folder.join(format!("frame_{:05}.ppm", number))
+}
+
+/// Stitches the frames in `folder` into a WebM video with ffmpeg, which has to be on the path.
+/// Each frame shows for `1 / fps` seconds.
+pub fn stitch(folder: &Path, video: &Path, fps: u32) -> Result<(), String> {
+    let status = Command::new("ffmpeg")
+        .args(["-loglevel", "error", "-y", "-framerate"])
+        .arg(fps.to_string())
+        .arg("-i")
+        .arg(folder.join("frame_%05d.ppm"))
+        .args(["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p"])
+        .arg(video)
+        .status()
+        .map_err(|e| format!("Unable to run ffmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed making {}: {}", video.display(), status));
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Solves every sample again and checks it against its trace in fixtures/traces, which are recorded
+//! with the default settings.  Only the solver is used, so this runs without the GUI.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use tametsi::{Bits, Puzzle, SolverConfig, trace::Trace};
+
+/// The squares, mines, revealed and flagged squares, hidden numbers and hints of a puzzle file.
+/// The samples are well formed, so unlike the app's parser this doesn't look for problems.
+fn read_sample(contents: &str) -> (String, Puzzle) {
+    let doc = roxmltree::Document::parse(contents).expect("Unable to parse XML!");
+    let root = doc.root_element();
+    let text = |node: roxmltree::Node, tag: &str| node.children().find(|a| a.has_tag_name(tag)).and_then(|a| a.text()).unwrap_or("").to_string();
+    let has = |node: roxmltree::Node, tag: &str| node.children().any(|a| a.has_tag_name(tag));
+    let nodes = root.children().find(|a| a.has_tag_name("GRAPH")).expect("No graph in document!").children().filter(|node| node.is_element()).collect::<Vec<_>>();
+    let ids = nodes.iter().enumerate().map(|(i, &node)| (text(node, "ID"), i)).collect::<HashMap<_, _>>();
+    let bits = |list: &str| list.split(',').filter(|id| !id.is_empty()).fold(Bits::zeroed(), |mut bits, id| {
+        bits.set(ids[id], true);
+        bits
+    });
+
+    let mut mines = Bits::zeroed();
+    let mut revealed = Bits::zeroed();
+    for (i, &node) in nodes.iter().enumerate() {
+        mines.set(i, has(node, "HAS_MINE"));
+        revealed.set(i, has(node, "REVEALED"));
+    }
+    let mut puzzle = Puzzle::new(nodes.iter().map(|&node| bits(&text(node, "EDGES"))).collect(), mines, revealed);
+    for (i, &node) in nodes.iter().enumerate() {
+        puzzle.flagged.set(i, has(node, "FLAGGED"));
+        puzzle.unknowns.set(i, has(node, "SECRET"));
+        puzzle.id_labels[i] = text(node, "ID");
+    }
+    puzzle.hints = root.children().filter(|a| a.has_tag_name("HINT_LIST") || a.has_tag_name("COLUMN_HINT_LIST")).flat_map(|list| list.children()).filter(|hint| hint.is_element()).map(|hint| bits(&text(hint, "IDS"))).collect();
+    (text(root, "TITLE"), puzzle)
+}
+
+/// The name of a trace file for a puzzle title, the way `tametsi trace record` names them.
+fn file_stem(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+#[test]
+fn samples_match_their_traces() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut samples = fs::read_dir(root.join("samples")).expect("No samples folder").map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+    samples.sort();
+    assert!(!samples.is_empty(), "No samples to check");
+    let mut failures = Vec::new();
+    for sample in samples {
+        let (title, puzzle) = read_sample(&fs::read_to_string(&sample).unwrap_or_else(|e| panic!("Unable to read {}: {}", sample.display(), e)));
+        let path = root.join("fixtures/traces").join(format!("{}.json", file_stem(&title)));
+        let json = fs::read_to_string(&path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path.display(), e));
+        let expected: Trace = serde_json::from_str(&json).unwrap_or_else(|e| panic!("Unable to read {}: {}", path.display(), e));
+        let differences = expected.compare(&Trace::record(&title, &puzzle, SolverConfig::default()));
+        if !differences.is_empty() {
+            failures.push(format!("{}:\n    {}", title, differences.join("\n    ")));
+        }
+    }
+    assert!(failures.is_empty(), "Steps differ from the saved traces, which `tametsi trace record` updates if the change is meant:\n{}", failures.join("\n"));
+}